@@ -0,0 +1 @@
+print(input().replace("X", "b"))
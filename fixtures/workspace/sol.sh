@@ -0,0 +1 @@
+tr X b
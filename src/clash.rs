@@ -1,7 +1,9 @@
+mod mode;
 mod public_handle;
 mod testcase;
 
-pub use public_handle::PublicHandle;
+pub use mode::Mode;
+pub use public_handle::{HandleQuery, PublicHandle};
 use serde::{Deserialize, Serialize};
 use testcase::deserialize_testcases;
 pub use testcase::Testcase;
@@ -22,14 +24,50 @@ pub struct Clash {
     downvotes: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-enum PuzzleType {
+/// `PuzzleType` tells whether a puzzle is a Clash of Code or a classic I/O
+/// puzzle.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::clash::PuzzleType;
+/// use std::str::FromStr;
+///
+/// assert_eq!(PuzzleType::from_str("classic").unwrap(), PuzzleType::ClassicInOut);
+/// assert_eq!(PuzzleType::Clash.to_string(), "clash");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PuzzleType {
     #[serde(rename = "CLASHOFCODE")]
     Clash,
     #[serde(rename = "PUZZLE_INOUT")]
     ClassicInOut,
 }
 
+impl std::str::FromStr for PuzzleType {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clash" => Ok(PuzzleType::Clash),
+            "classic" => Ok(PuzzleType::ClassicInOut),
+            _ => Err(crate::Error::InvalidInput(format!(
+                "Invalid puzzle type {:?} (expected clash or classic)",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for PuzzleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PuzzleType::Clash => write!(f, "clash"),
+            PuzzleType::ClassicInOut => write!(f, "classic"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ClashVersion {
     version: u32,
@@ -98,6 +136,18 @@ impl Clash {
         &self.last_version.data.output_description
     }
 
+    pub fn puzzle_type(&self) -> PuzzleType {
+        self.puzzle_type
+    }
+
+    pub fn upvotes(&self) -> i32 {
+        self.upvotes
+    }
+
+    pub fn downvotes(&self) -> i32 {
+        self.downvotes
+    }
+
     pub fn is_reverse(&self) -> bool {
         self.last_version.data.reverse
     }
@@ -113,4 +163,17 @@ impl Clash {
     pub fn is_reverse_only(&self) -> bool {
         self.is_reverse() && !self.is_fastest() && !self.is_shortest()
     }
+
+    pub fn has_mode(&self, mode: Mode) -> bool {
+        match mode {
+            Mode::Fastest => self.is_fastest(),
+            Mode::Shortest => self.is_shortest(),
+            Mode::Reverse => self.is_reverse(),
+        }
+    }
+
+    /// The game modes that are available for this clash.
+    pub fn modes(&self) -> Vec<Mode> {
+        Mode::ALL.into_iter().filter(|mode| self.has_mode(*mode)).collect()
+    }
 }
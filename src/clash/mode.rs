@@ -0,0 +1,56 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// `Mode` is a game mode of a Clash of Code.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::clash::Mode;
+/// use std::str::FromStr;
+///
+/// assert_eq!(Mode::from_str("shortest").unwrap(), Mode::Shortest);
+/// assert_eq!(Mode::Fastest.to_string(), "fastest");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Solve the puzzle as fast as possible.
+    Fastest,
+    /// Solve the puzzle with as few characters as possible.
+    Shortest,
+    /// Solve the puzzle without a statement, only the testcases are visible.
+    Reverse,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 3] = [Mode::Fastest, Mode::Shortest, Mode::Reverse];
+}
+
+impl FromStr for Mode {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fastest" => Ok(Mode::Fastest),
+            "shortest" => Ok(Mode::Shortest),
+            "reverse" => Ok(Mode::Reverse),
+            other => Err(Error::InvalidInput(format!(
+                "unknown mode '{other}' (valid modes are fastest, shortest and reverse)"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            Mode::Fastest => "fastest",
+            Mode::Shortest => "shortest",
+            Mode::Reverse => "reverse",
+        };
+        write!(f, "{name}")
+    }
+}
@@ -1,11 +1,22 @@
 use std::str::FromStr;
 
-use anyhow::anyhow;
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::{Error, Result};
+
+/// Handles are the (hexadecimal) id of the contribution followed by a 32
+/// digit hexadecimal token.
+const HANDLE_LENGTHS: std::ops::RangeInclusive<usize> = 33..=40;
+
+/// Prefixes shorter than this are too likely to be ambiguous.
+const MIN_PREFIX_LENGTH: usize = 4;
+
+/// Contribution URLs on CodinGame are this followed by the handle.
+const CONTRIBUTION_URL_PREFIX: &str = "codingame.com/contribute/view/";
+
 /// `PublicHandle` is a hexadecimal string that uniquely identifies a clash
 /// or a puzzle. It is the last part of the URL when viewing a clash or a puzzle
-/// on the CodinGame contribution page.
+/// on the CodinGame contribution page, and the whole URL is accepted as well.
 ///
 /// # Examples
 ///
@@ -15,23 +26,62 @@ use serde::{Deserialize, Deserializer, Serialize};
 ///
 /// let handle = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924");
 /// assert!(handle.is_ok());
+/// let url = "https://www.codingame.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924";
+/// assert_eq!(PublicHandle::from_str(url).unwrap(), handle.unwrap());
 /// let invalid_handle = PublicHandle::from_str("xyz");
 /// assert!(invalid_handle.is_err());
+/// let too_short = PublicHandle::from_str("6821");
+/// assert!(too_short.is_err());
 /// ```
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct PublicHandle(String);
 
+impl PublicHandle {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl FromStr for PublicHandle {
-    type Err = anyhow::Error;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.chars().all(|ch| ch.is_ascii_hexdigit()) {
-            Ok(PublicHandle(String::from(s)))
-        } else {
-            Err(anyhow!("valid handles only contain characters 0-9 and a-f"))
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        // Handles are stored in lowercase, but CodinGame accepts them in any case
+        let s = s.to_ascii_lowercase();
+        let hex = strip_contribution_url(&s)?;
+        if !HANDLE_LENGTHS.contains(&hex.len()) {
+            return Err(Error::InvalidInput(format!(
+                "valid handles are {} to {} characters long (got {})",
+                HANDLE_LENGTHS.start(),
+                HANDLE_LENGTHS.end(),
+                hex.len()
+            )))
         }
+        Ok(PublicHandle(hex.to_string()))
     }
 }
 
+/// Extracts the handle from a contribution URL (or returns `s` if it is not
+/// a URL) and checks that it is hexadecimal.
+fn strip_contribution_url(s: &str) -> Result<&str> {
+    let s = s.trim();
+    let hex = match s.split_once("://") {
+        Some((_, url)) => {
+            let url = url.strip_prefix("www.").unwrap_or(url);
+            let url = url.split(['?', '#']).next().unwrap_or(url).trim_end_matches('/');
+            url.strip_prefix(CONTRIBUTION_URL_PREFIX)
+                .ok_or_else(|| Error::InvalidInput(format!("{:?} is not a CodinGame contribution URL", s)))?
+        }
+        None => s,
+    };
+    if hex.is_empty() {
+        return Err(Error::InvalidInput("handles can not be empty".to_string()))
+    }
+    if !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(Error::InvalidInput("valid handles only contain characters 0-9 and a-f".to_string()))
+    }
+    Ok(hex)
+}
+
 impl std::fmt::Display for PublicHandle {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -39,7 +89,7 @@ impl std::fmt::Display for PublicHandle {
 }
 
 impl<'de> Deserialize<'de> for PublicHandle {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -47,3 +97,151 @@ impl<'de> Deserialize<'de> for PublicHandle {
         FromStr::from_str(&s).map_err(serde::de::Error::custom)
     }
 }
+
+/// `HandleQuery` is a handle as given by a user: a complete [PublicHandle]
+/// (or contribution URL) or a unique prefix of a known handle, like a git
+/// short hash.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::clash::{HandleQuery, PublicHandle};
+/// use std::str::FromStr;
+///
+/// let known = ["682102420fbce0fce95e0ee56095ea2b9924", "682199999fbce0fce95e0ee56095ea2b9924"]
+///     .map(|h| PublicHandle::from_str(h).unwrap());
+/// let query = HandleQuery::from_str("68210").unwrap();
+/// assert_eq!(query.resolve(&known).unwrap(), known[0]);
+/// let ambiguous = HandleQuery::from_str("6821").unwrap();
+/// assert!(ambiguous.resolve(&known).is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandleQuery {
+    Handle(PublicHandle),
+    Prefix(String),
+}
+
+impl HandleQuery {
+    /// The handle that the query refers to. Prefixes have to match exactly
+    /// one of the `known` handles, complete handles are returned as is.
+    pub fn resolve<'a, I>(&self, known: I) -> Result<PublicHandle>
+    where
+        I: IntoIterator<Item = &'a PublicHandle>,
+    {
+        let prefix = match self {
+            HandleQuery::Handle(handle) => return Ok(handle.clone()),
+            HandleQuery::Prefix(prefix) => prefix,
+        };
+        let mut matches: Vec<&PublicHandle> =
+            known.into_iter().filter(|handle| handle.0.starts_with(prefix.as_str())).collect();
+        matches.sort_by_key(|handle| handle.as_str());
+        matches.dedup();
+        match matches.as_slice() {
+            [] => Err(Error::NotFound(format!("No stored clash has a handle that starts with {}", prefix))),
+            [handle] => Ok((*handle).clone()),
+            candidates => {
+                let candidates: Vec<&str> = candidates.iter().map(|handle| handle.as_str()).collect();
+                Err(Error::InvalidInput(format!(
+                    "Handle prefix {} is ambiguous, it matches:\n  {}",
+                    prefix,
+                    candidates.join("\n  ")
+                )))
+            }
+        }
+    }
+}
+
+impl FromStr for HandleQuery {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_ascii_lowercase();
+        let hex = strip_contribution_url(&s)?;
+        if hex.len() >= *HANDLE_LENGTHS.start() || s.contains("://") {
+            return Ok(HandleQuery::Handle(PublicHandle::from_str(&s)?))
+        }
+        if hex.len() < MIN_PREFIX_LENGTH {
+            return Err(Error::InvalidInput(format!(
+                "handle prefixes have to be at least {} characters long (handles are {} to {})",
+                MIN_PREFIX_LENGTH,
+                HANDLE_LENGTHS.start(),
+                HANDLE_LENGTHS.end()
+            )))
+        }
+        Ok(HandleQuery::Prefix(hex.to_string()))
+    }
+}
+
+impl From<PublicHandle> for HandleQuery {
+    fn from(handle: PublicHandle) -> Self {
+        HandleQuery::Handle(handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLE: &str = "682102420fbce0fce95e0ee56095ea2b9924";
+
+    #[test]
+    fn test_contribution_urls() {
+        for url in [
+            "https://www.codingame.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924",
+            "https://codingame.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924/",
+            "http://www.codingame.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924?tab=tests",
+            "https://www.codingame.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924#comments",
+        ] {
+            assert_eq!(PublicHandle::from_str(url).unwrap().as_str(), HANDLE, "{}", url);
+        }
+        assert!(PublicHandle::from_str("https://www.codingame.com/training/easy/onboarding").is_err());
+        assert!(PublicHandle::from_str(
+            "https://example.com/contribute/view/682102420fbce0fce95e0ee56095ea2b9924"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_handles_are_lowercased() {
+        let url = "HTTPS://www.CodinGame.com/contribute/view/682102420FBCE0FCE95E0EE56095EA2B9924";
+        assert_eq!(PublicHandle::from_str(url).unwrap().as_str(), HANDLE);
+        assert_eq!(PublicHandle::from_str(&HANDLE.to_uppercase()).unwrap().as_str(), HANDLE);
+        assert_eq!(
+            HandleQuery::from_str(url).unwrap(),
+            HandleQuery::Handle(PublicHandle(HANDLE.to_string()))
+        );
+        assert_eq!(HandleQuery::from_str("6821AB").unwrap(), HandleQuery::Prefix(String::from("6821ab")));
+    }
+
+    #[test]
+    fn test_invalid_handles() {
+        assert!(PublicHandle::from_str("").is_err());
+        assert!(PublicHandle::from_str("6821").is_err());
+        assert!(PublicHandle::from_str(&HANDLE.repeat(2)).is_err());
+        assert!(PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b992g").is_err());
+    }
+
+    #[test]
+    fn test_prefix_resolution() {
+        let known = [
+            "682102420fbce0fce95e0ee56095ea2b9924",
+            "682199999fbce0fce95e0ee56095ea2b9924",
+            "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2",
+        ]
+        .map(|h| PublicHandle::from_str(h).unwrap());
+
+        let query = HandleQuery::from_str("9043").unwrap();
+        assert_eq!(query.resolve(&known).unwrap(), known[2]);
+
+        let err = HandleQuery::from_str("6821").unwrap().resolve(&known).unwrap_err().to_string();
+        assert!(err.contains(known[0].as_str()) && err.contains(known[1].as_str()), "{}", err);
+
+        assert!(HandleQuery::from_str("abcd").unwrap().resolve(&known).is_err());
+        assert!(HandleQuery::from_str("682").is_err());
+    }
+
+    #[test]
+    fn test_complete_handles_resolve_to_themselves() {
+        let query = HandleQuery::from_str(HANDLE).unwrap();
+        assert_eq!(query.resolve(&[]).unwrap().as_str(), HANDLE);
+    }
+}
@@ -0,0 +1,154 @@
+use std::error::Error as StdError;
+use std::process::ExitStatus;
+
+use crate::hooks::HookEvent;
+use crate::solution::build::BuildError;
+use crate::stub::{ConfigError, ParseError};
+
+/// The error type of all fallible operations in clashlib.
+///
+/// The `Display` implementation only shows the top level message. The
+/// underlying cause (if any) is available through `source()`, so wrapping the
+/// error in `anyhow::Error` shows the whole chain.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file (or a directory) failed.
+    #[error("{context}")]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The contents of a file or an HTTP response could not be deserialized.
+    #[error("{context}")]
+    Deserialize {
+        context: String,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    /// A value could not be serialized.
+    #[error("Unable to serialize {what}")]
+    Serialize {
+        what: String,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    /// The stub generator is not valid.
+    #[error(transparent)]
+    StubParse(#[from] ParseError),
+    /// The stub generator nests loops deeper than there are names for their
+    /// index variables.
+    #[error("Loops can not be nested more than {max_depth} levels deep")]
+    TooDeeplyNested { max_depth: usize },
+    /// A stub template could not be loaded or rendered.
+    #[error("{context}")]
+    Template {
+        context: String,
+        #[source]
+        source: tera::Error,
+    },
+    /// A stub generator configuration is missing or invalid.
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    /// A value given by the user (a handle, a tag, a player name...) is not
+    /// valid.
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The requested clash, library, solution... does not exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// Downloading a clash from CodinGame failed.
+    #[error("{0}")]
+    Http(String),
+    /// The solution could not be built.
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    /// A hook could not be executed.
+    #[error("Unable to run {event} hook")]
+    HookNotRun {
+        event: HookEvent,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A hook exited with a non-zero status.
+    #[error("{event} hook failed ({status})")]
+    HookFailed { event: HookEvent, status: ExitStatus },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Attaches a message describing what was being done to the errors of other
+/// crates, turning them into the matching [`Error`] variant.
+pub(crate) trait Context<T> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, std::io::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| Error::Io {
+            context: context(),
+            source,
+        })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, serde_json::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| Error::Deserialize {
+            context: context(),
+            source: source.into(),
+        })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, toml::de::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| Error::Deserialize {
+            context: context(),
+            source: source.into(),
+        })
+    }
+}
+
+impl<T> Context<T> for std::result::Result<T, tera::Error> {
+    fn with_context<F: FnOnce() -> String>(self, context: F) -> Result<T> {
+        self.map_err(|source| Error::Template {
+            context: context(),
+            source,
+        })
+    }
+}
+
+impl Error {
+    pub(crate) fn serialize(what: &str, source: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+        Error::Serialize {
+            what: what.to_string(),
+            source: source.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_keep_their_source() {
+        let result: std::result::Result<(), _> =
+            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no such file"));
+        let err = result.with_context(|| "Unable to read \"clash.json\"".to_string()).unwrap_err();
+        assert!(matches!(err, Error::Io { .. }));
+        assert_eq!(err.to_string(), "Unable to read \"clash.json\"");
+        assert_eq!(err.source().unwrap().to_string(), "no such file");
+    }
+
+    #[test]
+    fn deserialization_errors_are_not_io_errors() {
+        let err = serde_json::from_str::<Vec<u32>>("[1, 2")
+            .with_context(|| "Unable to deserialize clash".to_string())
+            .unwrap_err();
+        assert!(matches!(err, Error::Deserialize { .. }));
+        assert!(err.source().is_some());
+    }
+}
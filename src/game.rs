@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::{Mode, PublicHandle};
+use crate::{Error, Result};
+
+/// Default time limit of a clash (same as on CodinGame).
+pub const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(15 * 60);
+
+/// Seconds since the Unix epoch.
+pub fn unix_time_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Formats a duration as `MM:SS`.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::game::format_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(format_duration(Duration::from_secs(754)), "12:34");
+/// ```
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// `PracticeSession` is a timed attempt at solving a clash in a specific
+/// mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PracticeSession {
+    pub handle: PublicHandle,
+    pub mode: Mode,
+    /// Start of the session in seconds since the Unix epoch.
+    pub started_at: u64,
+    /// Time limit of the session in seconds.
+    pub time_limit: u64,
+}
+
+impl PracticeSession {
+    pub fn start(handle: PublicHandle, mode: Mode, time_limit: Duration) -> Self {
+        PracticeSession {
+            handle,
+            mode,
+            started_at: unix_time_now(),
+            time_limit: time_limit.as_secs(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_secs(unix_time_now().saturating_sub(self.started_at))
+    }
+
+    /// Time left before the session is over (zero if time is up).
+    pub fn remaining(&self) -> Duration {
+        Duration::from_secs(self.time_limit).saturating_sub(self.elapsed())
+    }
+
+    pub fn is_time_up(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// `GameResult` is the outcome of solving a clash in a specific mode, scored
+/// like on CodinGame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameResult {
+    pub handle: PublicHandle,
+    pub mode: Mode,
+    /// Percentage of testcases (including validators) that passed.
+    pub score: u32,
+    /// Seconds it took to submit the solution.
+    pub time: u64,
+    /// Number of characters in the solution (used for ranking in shortest
+    /// mode).
+    pub length: Option<usize>,
+    /// When the solution was submitted in seconds since the Unix epoch.
+    pub submitted_at: u64,
+}
+
+impl GameResult {
+    /// Formats the score and the criteria that is used to break ties in the
+    /// mode of the result.
+    pub fn summary(&self) -> String {
+        let time = format_duration(Duration::from_secs(self.time));
+        match (self.mode, self.length) {
+            (Mode::Shortest, Some(length)) => format!("{}% with {} chars in {}", self.score, length, time),
+            _ => format!("{}% in {}", self.score, time),
+        }
+    }
+
+    /// Returns true if `self` ranks higher than `other` by CodinGame rules:
+    /// higher score wins, ties are broken by code length in shortest mode and
+    /// by time otherwise.
+    pub fn is_better_than(&self, other: &GameResult) -> bool {
+        ranking_key(self) < ranking_key(other)
+    }
+
+    /// Orders results from best to worst by CodinGame rules.
+    pub fn cmp_ranking(&self, other: &GameResult) -> std::cmp::Ordering {
+        ranking_key(self).cmp(&ranking_key(other))
+    }
+}
+
+/// `Hotseat` is a clash that several players compete in on the same machine.
+/// The time of a submission is measured from the start of the clash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hotseat {
+    pub handle: PublicHandle,
+    pub mode: Mode,
+    /// Start of the clash in seconds since the Unix epoch.
+    pub started_at: u64,
+    pub players: Vec<String>,
+    /// Latest submission of each player.
+    #[serde(default)]
+    pub submissions: BTreeMap<String, GameResult>,
+}
+
+impl Hotseat {
+    pub fn start(handle: PublicHandle, mode: Mode, players: Vec<String>) -> Result<Self> {
+        if players.is_empty() {
+            return Err(Error::InvalidInput("A hot-seat clash needs at least one player".to_string()))
+        }
+        if let Some(duplicate) =
+            players.iter().enumerate().find_map(|(i, p)| players[..i].contains(p).then_some(p))
+        {
+            return Err(Error::InvalidInput(format!("Player {duplicate} was given more than once")))
+        }
+        Ok(Hotseat {
+            handle,
+            mode,
+            started_at: unix_time_now(),
+            players,
+            submissions: BTreeMap::new(),
+        })
+    }
+
+    /// Returns an error if `player` is not playing in this clash.
+    pub fn check_player(&self, player: &str) -> Result<()> {
+        if !self.players.iter().any(|p| p == player) {
+            return Err(Error::InvalidInput(format!(
+                "{player} is not playing in this clash (players: {})",
+                self.players.join(", ")
+            )))
+        }
+        Ok(())
+    }
+
+    /// Records the submission of `player`, replacing their previous one.
+    pub fn submit(&mut self, player: &str, score: u32, length: Option<usize>) -> Result<&GameResult> {
+        self.check_player(player)?;
+        let submitted_at = unix_time_now();
+        let result = GameResult {
+            handle: self.handle.clone(),
+            mode: self.mode,
+            score,
+            time: submitted_at.saturating_sub(self.started_at),
+            length,
+            submitted_at,
+        };
+        self.submissions.insert(player.to_string(), result);
+        Ok(&self.submissions[player])
+    }
+
+    /// Players that have not submitted a solution yet.
+    pub fn waiting_for(&self) -> Vec<&str> {
+        self.players
+            .iter()
+            .filter(|p| !self.submissions.contains_key(*p))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Ranks the submissions by CodinGame rules. Returns `(rank, player,
+    /// result)` sorted from best to worst, players with equal results share
+    /// the same rank.
+    pub fn ranking(&self) -> Vec<(usize, &str, &GameResult)> {
+        let mut submissions: Vec<(&str, &GameResult)> =
+            self.submissions.iter().map(|(player, result)| (player.as_str(), result)).collect();
+        submissions.sort_by(|(_, a), (_, b)| a.cmp_ranking(b));
+
+        submissions
+            .iter()
+            .map(|&(player, result)| {
+                let rank = 1 + submissions.iter().filter(|(_, other)| other.is_better_than(result)).count();
+                (rank, player, result)
+            })
+            .collect()
+    }
+}
+
+/// Sort key for ranking results by CodinGame rules (smaller is better).
+fn ranking_key(result: &GameResult) -> (std::cmp::Reverse<u32>, usize, u64) {
+    let length = match result.mode {
+        Mode::Shortest => result.length.unwrap_or(usize::MAX),
+        Mode::Fastest | Mode::Reverse => 0,
+    };
+    (std::cmp::Reverse(result.score), length, result.time)
+}
+
+/// Percentage of passed testcases, rounded down like on CodinGame.
+pub fn score(num_passed: usize, num_tests: usize) -> u32 {
+    if num_tests == 0 {
+        return 0
+    }
+    (100 * num_passed / num_tests) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn result(mode: Mode, score: u32, time: u64, length: Option<usize>) -> GameResult {
+        GameResult {
+            handle: PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap(),
+            mode,
+            score,
+            time,
+            length,
+            submitted_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(score(8, 8), 100);
+        assert_eq!(score(2, 3), 66);
+        assert_eq!(score(0, 0), 0);
+    }
+
+    #[test]
+    fn test_higher_score_is_better() {
+        let a = result(Mode::Fastest, 100, 500, None);
+        let b = result(Mode::Fastest, 87, 100, None);
+        assert!(a.is_better_than(&b));
+        assert!(!b.is_better_than(&a));
+    }
+
+    #[test]
+    fn test_fastest_ties_broken_by_time() {
+        let a = result(Mode::Fastest, 100, 100, Some(500));
+        let b = result(Mode::Fastest, 100, 200, Some(50));
+        assert!(a.is_better_than(&b));
+    }
+
+    #[test]
+    fn test_shortest_ties_broken_by_length_then_time() {
+        let a = result(Mode::Shortest, 100, 800, Some(50));
+        let b = result(Mode::Shortest, 100, 100, Some(60));
+        let c = result(Mode::Shortest, 100, 900, Some(50));
+        assert!(a.is_better_than(&b));
+        assert!(a.is_better_than(&c));
+    }
+
+    #[test]
+    fn test_hotseat_ranking() {
+        let handle = result(Mode::Shortest, 0, 0, None).handle;
+        let players = ["alice", "bob", "carol", "dave"].map(String::from).to_vec();
+        let mut hotseat = Hotseat::start(handle, Mode::Shortest, players).unwrap();
+        hotseat.submit("alice", 100, Some(80)).unwrap();
+        hotseat.submit("bob", 100, Some(40)).unwrap();
+        hotseat.submit("carol", 50, Some(10)).unwrap();
+        assert!(hotseat.submit("eve", 100, Some(1)).is_err());
+        assert_eq!(hotseat.waiting_for(), vec!["dave"]);
+
+        let ranking: Vec<(usize, &str)> = hotseat.ranking().iter().map(|(rank, p, _)| (*rank, *p)).collect();
+        assert_eq!(ranking, vec![(1, "bob"), (2, "alice"), (3, "carol")]);
+    }
+
+    #[test]
+    fn test_hotseat_shared_rank() {
+        let handle = result(Mode::Fastest, 0, 0, None).handle;
+        let players = ["alice", "bob"].map(String::from).to_vec();
+        let mut hotseat = Hotseat::start(handle, Mode::Fastest, players).unwrap();
+        hotseat.submit("alice", 100, None).unwrap();
+        hotseat.submit("bob", 100, None).unwrap();
+        hotseat.submissions.get_mut("bob").unwrap().time = hotseat.submissions["alice"].time;
+        let ranks: Vec<usize> = hotseat.ranking().iter().map(|(rank, _, _)| *rank).collect();
+        assert_eq!(ranks, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_hotseat_requires_unique_players() {
+        let handle = result(Mode::Fastest, 0, 0, None).handle;
+        assert!(Hotseat::start(handle.clone(), Mode::Fastest, vec![]).is_err());
+        let players = ["alice", "bob", "alice"].map(String::from).to_vec();
+        assert!(Hotseat::start(handle, Mode::Fastest, players).is_err());
+    }
+
+    #[test]
+    fn test_session_time_limit() {
+        let mut session = PracticeSession::start(
+            result(Mode::Fastest, 0, 0, None).handle,
+            Mode::Fastest,
+            DEFAULT_TIME_LIMIT,
+        );
+        assert!(!session.is_time_up());
+        session.started_at -= 16 * 60;
+        assert!(session.is_time_up());
+    }
+}
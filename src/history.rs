@@ -0,0 +1,41 @@
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// `RunRecord` is an entry of the run history: the outcome of testing a
+/// solution to a clash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub handle: PublicHandle,
+    /// `passed` is true if all testcases that were run passed.
+    pub passed: bool,
+    /// When the solution was tested in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// Reads the run history stored in `path` (one JSON object per line), oldest
+/// first. A missing file is an empty history and invalid lines are skipped.
+pub fn read_history(path: &Path) -> Vec<RunRecord> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Appends `record` to the run history stored in `path`.
+pub fn append_to_history(path: &Path, record: &RunRecord) -> Result<()> {
+    let mut line = serde_json::to_string(record).map_err(|err| Error::serialize("run record", err))?;
+    line.push('\n');
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open {:?}", path))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Unable to write {:?}", path))
+}
@@ -0,0 +1,173 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::{Clash, Mode, PublicHandle};
+use crate::solution::CommandOutput;
+use crate::{Error, Result};
+
+/// User configured commands that are executed when something happens. Hooks
+/// receive context about the event as `COCTUS_*` environment variables and
+/// as a JSON object on STDIN (see [HookContext]).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Hooks {
+    /// Executed before the solution is built and tested. If the command
+    /// fails the run is aborted.
+    pub pre_build: Option<String>,
+    /// Executed after the testcases have been run.
+    pub post_run: Option<String>,
+    /// Executed when all testcases pass.
+    pub on_pass: Option<String>,
+    /// Executed when some testcases fail.
+    pub on_fail: Option<String>,
+    /// Executed after the current clash has changed.
+    pub on_next: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    PreBuild,
+    PostRun,
+    OnPass,
+    OnFail,
+    OnNext,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 5] = [
+        HookEvent::PreBuild,
+        HookEvent::PostRun,
+        HookEvent::OnPass,
+        HookEvent::OnFail,
+        HookEvent::OnNext,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HookEvent::PreBuild => "pre-build",
+            HookEvent::PostRun => "post-run",
+            HookEvent::OnPass => "on-pass",
+            HookEvent::OnFail => "on-fail",
+            HookEvent::OnNext => "on-next",
+        }
+    }
+}
+
+impl std::fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Information about the clash (and the test run) that is passed to hooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct HookContext {
+    pub handle: PublicHandle,
+    pub title: String,
+    pub modes: Vec<Mode>,
+    pub passed: Option<usize>,
+    pub total: Option<usize>,
+    pub solution: Option<PathBuf>,
+}
+
+impl HookContext {
+    pub fn new(handle: &PublicHandle, clash: &Clash) -> Self {
+        HookContext {
+            handle: handle.clone(),
+            title: clash.title().to_string(),
+            modes: clash.modes(),
+            passed: None,
+            total: None,
+            solution: None,
+        }
+    }
+
+    fn env_vars(&self, event: HookEvent) -> Vec<(&'static str, String)> {
+        let mut vars = vec![
+            ("COCTUS_EVENT", event.name().to_string()),
+            ("COCTUS_HANDLE", self.handle.to_string()),
+            ("COCTUS_TITLE", self.title.clone()),
+            ("COCTUS_MODES", self.modes.iter().map(Mode::to_string).collect::<Vec<_>>().join(",")),
+        ];
+        if let Some(passed) = self.passed {
+            vars.push(("COCTUS_PASSED", passed.to_string()));
+        }
+        if let Some(total) = self.total {
+            vars.push(("COCTUS_TOTAL", total.to_string()));
+        }
+        if let Some(solution) = &self.solution {
+            vars.push(("COCTUS_SOLUTION", solution.to_string_lossy().to_string()));
+        }
+        vars
+    }
+}
+
+impl Hooks {
+    /// The configured hooks and the events they are for.
+    pub fn configured(&self) -> impl Iterator<Item = (HookEvent, &str)> {
+        HookEvent::ALL
+            .into_iter()
+            .filter_map(|event| Some((event, self.command_for(event)?.as_str())))
+    }
+
+    /// Returns true if a hook is configured for `event`.
+    pub fn is_configured(&self, event: HookEvent) -> bool {
+        self.command_for(event).is_some()
+    }
+
+    fn command_for(&self, event: HookEvent) -> Option<&String> {
+        match event {
+            HookEvent::PreBuild => self.pre_build.as_ref(),
+            HookEvent::PostRun => self.post_run.as_ref(),
+            HookEvent::OnPass => self.on_pass.as_ref(),
+            HookEvent::OnFail => self.on_fail.as_ref(),
+            HookEvent::OnNext => self.on_next.as_ref(),
+        }
+    }
+
+    /// Runs the hook configured for `event` (if any) and waits for it to
+    /// finish. Returns an error if the hook could not be executed or exited
+    /// with a non-zero status.
+    pub fn run(&self, event: HookEvent, context: &HookContext, output: CommandOutput) -> Result<()> {
+        let Some(hook) = self.command_for(event) else {
+            return Ok(())
+        };
+
+        let mut cmd = match shlex::split(hook).as_deref() {
+            Some([exe, exe_args @ ..]) => {
+                let mut cmd = std::process::Command::new(exe);
+                cmd.args(exe_args);
+                cmd
+            }
+            Some([]) => return Ok(()),
+            None => return Err(Error::InvalidInput(format!("Invalid {} hook command", event))),
+        };
+
+        output.apply(&mut cmd);
+        let mut child = cmd
+            .envs(context.env_vars(event))
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|source| Error::HookNotRun { event, source })?;
+
+        let json = serde_json::json!({
+            "event": event,
+            "context": context,
+        });
+        if let Some(mut stdin) = child.stdin.take() {
+            // The hook is free to ignore its input so a broken pipe is fine here
+            let _ = stdin.write_all(json.to_string().as_bytes());
+        }
+
+        let status = child.wait().map_err(|source| Error::HookNotRun { event, source })?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(Error::HookFailed { event, status })
+        }
+    }
+}
@@ -1,5 +1,18 @@
+mod completion;
+mod config;
+mod diff;
+mod doctor;
 mod formatter;
+pub mod jsonrpc;
 mod lines_with_endings;
+mod lsp;
 mod outputstyle;
+mod tui;
 
+pub use completion::{add_dynamic_completion, completion_target, CompletionTarget, COMPLETE_COMMAND};
+pub use config::{ColorChoice, Config, SettingSource, CONFIG_FILE_NAME, SETTINGS};
+pub use diff::DiffMode;
+pub use doctor::Doctor;
+pub use lsp::LanguageServer;
 pub use outputstyle::OutputStyle;
+pub use tui::{TestRun, Tui, TuiExit};
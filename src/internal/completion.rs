@@ -0,0 +1,207 @@
+use clap::{Arg, ArgAction, Command};
+use clap_complete::Shell;
+
+/// Name of the hidden subcommand that the completion scripts call to get the
+/// candidates for the word under the cursor.
+pub const COMPLETE_COMMAND: &str = "complete";
+
+/// Kind of value that can be completed from local data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionTarget {
+    /// Handles of the stored clashes.
+    Handle,
+    /// Languages that have stub templates.
+    Language,
+}
+
+impl CompletionTarget {
+    fn of_arg(arg: &Arg) -> Option<Self> {
+        match arg.get_id().as_str() {
+            "PUBLIC_HANDLE" => Some(CompletionTarget::Handle),
+            "PROGRAMMING_LANGUAGE" | "LANGUAGE" | "lang" => Some(CompletionTarget::Language),
+            _ => None,
+        }
+    }
+}
+
+/// Finds out what the word under the cursor (the last of `words`) is for.
+/// `words` are the words of the command line after the program name.
+pub fn completion_target(mut cmd: Command, words: &[String]) -> Option<CompletionTarget> {
+    let (current, previous) = words.split_last()?;
+    // Building propagates the global arguments to the subcommands
+    cmd.build();
+    let mut cmd = &cmd;
+    let mut num_positionals = 0;
+    let mut only_positionals = false;
+    let mut pending_option: Option<&Arg> = None;
+
+    for word in previous {
+        if pending_option.take().is_some() {
+            continue
+        }
+        if !only_positionals && word == "--" {
+            only_positionals = true;
+        } else if !only_positionals && word.starts_with('-') && word.len() > 1 {
+            pending_option = option_expecting_value(cmd, word);
+        } else if let Some(subcommand) = cmd.find_subcommand(word).filter(|_| num_positionals == 0) {
+            cmd = subcommand;
+        } else {
+            num_positionals += 1;
+        }
+    }
+
+    if let Some(option) = pending_option {
+        return CompletionTarget::of_arg(option)
+    }
+    if !only_positionals && current.starts_with('-') {
+        return None
+    }
+    let positionals: Vec<&Arg> = cmd.get_positionals().collect();
+    let positional = match positionals.get(num_positionals) {
+        Some(positional) => positional,
+        None => positionals.last().filter(|last| takes_many_values(last))?,
+    };
+    CompletionTarget::of_arg(positional)
+}
+
+fn takes_values(arg: &Arg) -> bool {
+    arg.get_action().takes_values() && arg.get_num_args().is_none_or(|range| range.min_values() > 0)
+}
+
+fn takes_many_values(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Append)
+        || arg.get_num_args().is_some_and(|range| range.max_values() > 1)
+}
+
+/// The option in `word` if the next word is its value.
+fn option_expecting_value<'a>(cmd: &'a Command, word: &str) -> Option<&'a Arg> {
+    if let Some(long) = word.strip_prefix("--") {
+        if long.contains('=') {
+            return None
+        }
+        return cmd
+            .get_arguments()
+            .find(|arg| {
+                arg.get_long() == Some(long) || arg.get_all_aliases().into_iter().flatten().any(|a| a == long)
+            })
+            .filter(|arg| takes_values(arg))
+    }
+    // A group of short flags like `-rv`, where only the last one can take the
+    // next word as its value
+    let shorts: Vec<char> = word[1..].chars().collect();
+    for (i, short) in shorts.iter().enumerate() {
+        let arg = cmd.get_arguments().find(|arg| arg.get_short() == Some(*short))?;
+        if takes_values(arg) {
+            return Some(arg).filter(|_| i == shorts.len() - 1)
+        }
+    }
+    None
+}
+
+/// Adds dynamic completion of handles and languages to the clap generated
+/// completion `script`. The candidates come from `BIN complete -- WORDS`,
+/// one per line as `VALUE<TAB>DESCRIPTION`.
+pub fn add_dynamic_completion(shell: Shell, script: String, bin: &str) -> String {
+    match shell {
+        Shell::Bash => {
+            let function = format!(
+                r#"_{bin}_dynamic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local candidates
+    mapfile -t candidates < <({bin} {COMPLETE_COMMAND} -- "${{COMP_WORDS[@]:1:COMP_CWORD}}" 2>/dev/null | cut -f1)
+    if [[ ${{#candidates[@]}} -gt 0 ]]; then
+        COMPREPLY=($(compgen -W "${{candidates[*]}}" -- "$cur"))
+    else
+        _{bin} "$@"
+    fi
+}}
+
+"#
+            );
+            let script =
+                script.replace(&format!("complete -F _{bin} "), &format!("complete -F _{bin}_dynamic "));
+            insert_before(script, "if [[ \"${BASH_VERSINFO[0]}\"", &function)
+        }
+        Shell::Zsh => {
+            // The clap generated function is renamed, so that the function
+            // that zsh calls (also when autoloading the script) is this one
+            let function = format!(
+                r#"_{bin}() {{
+    local -a candidates
+    candidates=("${{(@f)$({bin} {COMPLETE_COMMAND} -- "${{(@)words[2,CURRENT]}}" 2>/dev/null)}}")
+    if [[ -n "${{candidates[1]}}" ]]; then
+        candidates=("${{(@)candidates//$'\t'/:}}")
+        _describe -t values 'value' candidates
+    else
+        _{bin}_static "$@"
+    fi
+}}
+
+"#
+            );
+            let script = script.replacen(&format!("\n_{bin}() {{"), &format!("\n_{bin}_static() {{"), 1);
+            insert_before(script, "if [ \"$funcstack[1]\"", &function)
+        }
+        Shell::Fish => format!(
+            "{script}\nfunction __{bin}_dynamic\n    \
+                 {bin} {COMPLETE_COMMAND} -- (commandline -opc)[2..-1] (commandline -ct) 2>/dev/null\n\
+             end\n\
+             complete -c {bin} -a '(__{bin}_dynamic)'\n"
+        ),
+        _ => script,
+    }
+}
+
+/// Inserts `text` before the last occurrence of `marker` in `script` (or at
+/// the end if there is none).
+fn insert_before(mut script: String, marker: &str, text: &str) -> String {
+    let index = script.rfind(marker).unwrap_or(script.len());
+    script.insert_str(index, text);
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(command_line: &str) -> Option<CompletionTarget> {
+        let mut words: Vec<String> = command_line.split(' ').map(String::from).collect();
+        if command_line.ends_with(' ') {
+            words.pop();
+            words.push(String::new());
+        }
+        completion_target(crate::cli(), &words)
+    }
+
+    #[test]
+    fn complete_positionals() {
+        assert_eq!(target("show "), Some(CompletionTarget::Handle));
+        assert_eq!(target("show 9043"), Some(CompletionTarget::Handle));
+        assert_eq!(target("start "), Some(CompletionTarget::Language));
+        assert_eq!(target("start rust "), Some(CompletionTarget::Handle));
+        assert_eq!(target("checkout 9043 "), Some(CompletionTarget::Language));
+        assert_eq!(target("fetch 9043 6821 "), Some(CompletionTarget::Handle));
+        assert_eq!(target("status "), None);
+        assert_eq!(target("sh"), None);
+    }
+
+    #[test]
+    fn complete_after_options() {
+        assert_eq!(target("show --reverse "), Some(CompletionTarget::Handle));
+        assert_eq!(target("show --show-whitespace "), Some(CompletionTarget::Handle));
+        assert_eq!(target("next --strategy daily "), Some(CompletionTarget::Handle));
+        assert_eq!(target("verify --lang "), Some(CompletionTarget::Language));
+        assert_eq!(target("show --library team "), Some(CompletionTarget::Handle));
+        assert_eq!(target("show --"), None);
+    }
+
+    #[test]
+    fn zsh_script_calls_dynamic_completion() {
+        let mut cmd = crate::cli();
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Zsh, &mut cmd, "coctus", &mut script);
+        let script = add_dynamic_completion(Shell::Zsh, String::from_utf8(script).unwrap(), "coctus");
+        assert!(script.contains("\n_coctus_static() {"));
+        assert!(script.find("\n_coctus() {").unwrap() < script.find("if [ \"$funcstack[1]\"").unwrap());
+    }
+}
@@ -0,0 +1,413 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use clashlib::hooks::Hooks;
+use clashlib::selection::Strategy;
+use serde::Deserialize;
+
+/// Name of the config file in the config directory.
+pub const CONFIG_FILE_NAME: &str = "coctus.toml";
+
+/// Default number of seconds before the execution of a solution is timed out.
+pub const DEFAULT_TIMEOUT: f64 = 5.0;
+
+/// Default number of seconds before the build of a solution is timed out.
+pub const DEFAULT_BUILD_TIMEOUT: f64 = 30.0;
+
+/// User configuration, read from `coctus.toml` in the config directory.
+/// Some settings can be overridden with `COCTUS_*` environment variables (see
+/// [SETTINGS]).
+///
+/// # Examples
+///
+/// ```toml
+/// spoiler-free = true
+/// leaderboard-dir = "/mnt/team/coctus-leaderboard"
+/// player-name = "alice"
+/// timeout = 2.5
+/// build-timeout = 60
+/// color = "never"
+/// default-language = "ruby"
+/// strategy = "unseen"
+///
+/// [hooks]
+/// on-pass = "git commit -am 'Solved a clash'"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    /// Only show and run the testcases that are visible on CodinGame (hide
+    /// validators).
+    pub spoiler_free: bool,
+    /// Shared directory where the results of practice clashes are published
+    /// for the team leaderboard.
+    pub leaderboard_dir: Option<PathBuf>,
+    /// Name under which results are published (default: the user name).
+    pub player_name: Option<String>,
+    /// Directory where clashes, solutions and history are stored (default:
+    /// the OS dependent data directory).
+    pub data_dir: Option<PathBuf>,
+    /// Seconds before the execution of a solution is timed out (0 for no
+    /// timeout).
+    pub timeout: Option<f64>,
+    /// Seconds before the build of a solution is timed out (0 for no
+    /// timeout).
+    pub build_timeout: Option<f64>,
+    /// Render newlines and spaces in testcases. Subcommands have their own
+    /// default when this is not set.
+    pub show_whitespace: Option<bool>,
+    pub color: ColorChoice,
+    /// Programming language used when a command needs one and none is given.
+    pub default_language: Option<String>,
+    /// Strategy used whenever a random clash is picked (unless `coctus next`
+    /// is given one).
+    pub strategy: Option<Strategy>,
+    pub hooks: Hooks,
+}
+
+/// Whether the output is colored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Colored unless the `NO_COLOR` environment variable is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Type of the value of a [Setting].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingKind {
+    Bool,
+    Number,
+    Text,
+}
+
+/// A setting of the configuration file that can be inspected and changed
+/// with `coctus config`.
+#[derive(Debug, Clone, Copy)]
+pub struct Setting {
+    /// Key in the configuration file, settings in a table use a dotted key.
+    pub key: &'static str,
+    pub kind: SettingKind,
+    /// Environment variable that overrides the setting (if any).
+    pub env_var: Option<&'static str>,
+    pub description: &'static str,
+}
+
+pub const SETTINGS: [Setting; 15] = [
+    Setting::new("spoiler-free", SettingKind::Bool, None, "hide validators (default: false)"),
+    Setting::new(
+        "leaderboard-dir",
+        SettingKind::Text,
+        None,
+        "shared directory of the team leaderboard",
+    ),
+    Setting::new(
+        "player-name",
+        SettingKind::Text,
+        None,
+        "name on the leaderboard (default: user name)",
+    ),
+    Setting::new(
+        "data-dir",
+        SettingKind::Text,
+        Some("COCTUS_DATA_DIR"),
+        "where clashes, solutions and history are stored",
+    ),
+    Setting::new(
+        "timeout",
+        SettingKind::Number,
+        Some("COCTUS_TIMEOUT"),
+        "seconds before a solution is timed out (default: 5)",
+    ),
+    Setting::new(
+        "build-timeout",
+        SettingKind::Number,
+        Some("COCTUS_BUILD_TIMEOUT"),
+        "seconds before a build is timed out (default: 30)",
+    ),
+    Setting::new(
+        "show-whitespace",
+        SettingKind::Bool,
+        Some("COCTUS_SHOW_WHITESPACE"),
+        "render ⏎ and • in place of newlines and spaces",
+    ),
+    Setting::new(
+        "color",
+        SettingKind::Text,
+        Some("COCTUS_COLOR"),
+        "auto, always or never (default: auto)",
+    ),
+    Setting::new(
+        "default-language",
+        SettingKind::Text,
+        Some("COCTUS_DEFAULT_LANGUAGE"),
+        "language used when none is given",
+    ),
+    Setting::new(
+        "strategy",
+        SettingKind::Text,
+        Some("COCTUS_STRATEGY"),
+        "how random clashes are picked (default: uniform)",
+    ),
+    Setting::new("hooks.pre-build", SettingKind::Text, None, "command executed before a build"),
+    Setting::new("hooks.post-run", SettingKind::Text, None, "command executed after a run"),
+    Setting::new("hooks.on-pass", SettingKind::Text, None, "command executed when all tests pass"),
+    Setting::new("hooks.on-fail", SettingKind::Text, None, "command executed when some tests fail"),
+    Setting::new(
+        "hooks.on-next",
+        SettingKind::Text,
+        None,
+        "command executed when the current clash changes",
+    ),
+];
+
+impl Setting {
+    const fn new(
+        key: &'static str,
+        kind: SettingKind,
+        env_var: Option<&'static str>,
+        description: &'static str,
+    ) -> Self {
+        Setting {
+            key,
+            kind,
+            env_var,
+            description,
+        }
+    }
+
+    pub fn find(key: &str) -> Result<Setting> {
+        SETTINGS.into_iter().find(|setting| setting.key == key).ok_or_else(|| {
+            anyhow!("Unknown setting {:?} (see `coctus config list` for the available settings)", key)
+        })
+    }
+
+    /// Parses `value` as given on the command line or in an environment
+    /// variable.
+    pub fn parse_value(&self, value: &str) -> Result<toml::Value> {
+        match self.kind {
+            SettingKind::Bool => match value.to_lowercase().as_str() {
+                "true" | "1" | "yes" | "on" => Ok(toml::Value::Boolean(true)),
+                "false" | "0" | "no" | "off" => Ok(toml::Value::Boolean(false)),
+                _ => Err(anyhow!("Invalid value {:?} for {} (expected true or false)", value, self.key)),
+            },
+            SettingKind::Number => value
+                .parse::<f64>()
+                .map(toml::Value::Float)
+                .map_err(|_| anyhow!("Invalid value {:?} for {} (expected a number)", value, self.key)),
+            SettingKind::Text => Ok(toml::Value::String(value.to_string())),
+        }
+    }
+
+    /// The value of the setting in `table` (if set).
+    pub fn get<'a>(&self, table: &'a toml::Table) -> Option<&'a toml::Value> {
+        match self.key.split_once('.') {
+            Some((section, key)) => table.get(section)?.as_table()?.get(key),
+            None => table.get(self.key),
+        }
+    }
+
+    /// Sets (or removes if `value` is `None`) the setting in `table`.
+    pub fn set(&self, table: &mut toml::Table, value: Option<toml::Value>) -> Result<()> {
+        let (table, key) = match self.key.split_once('.') {
+            Some((section, key)) => {
+                let section = table
+                    .entry(section)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                    .as_table_mut()
+                    .with_context(|| format!("{} is not a table in the config file", section))?;
+                (section, key)
+            }
+            None => (table, self.key),
+        };
+        match value {
+            Some(value) => table.insert(key.to_string(), value),
+            None => table.remove(key),
+        };
+        Ok(())
+    }
+}
+
+/// Where the value of a setting comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingSource {
+    Default,
+    ConfigFile,
+    Environment(&'static str),
+}
+
+impl Config {
+    /// Reads the configuration from `path` and applies the overrides of the
+    /// `COCTUS_*` environment variables. A missing file is not an error, the
+    /// default configuration is used instead.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut table = read_table(path)?;
+        for setting in SETTINGS {
+            if let Some(value) = setting.env_var.and_then(|var| std::env::var(var).ok()) {
+                let value = setting
+                    .parse_value(&value)
+                    .with_context(|| format!("Invalid environment variable {}", setting.env_var.unwrap()))?;
+                setting.set(&mut table, Some(value))?;
+            }
+        }
+        Config::from_table(table).with_context(|| format!("Invalid configuration (file {:?})", path))
+    }
+
+    fn from_table(table: toml::Table) -> Result<Self> {
+        let config: Config = table.try_into()?;
+        for (key, timeout) in [
+            ("timeout", config.timeout),
+            ("build-timeout", config.build_timeout),
+        ] {
+            if timeout.is_some_and(|timeout| timeout.is_nan() || timeout < 0.0) {
+                return Err(anyhow!("{key} must be a positive number of seconds (or 0 for no timeout)"))
+            }
+        }
+        Ok(config)
+    }
+
+    /// Sets (or removes if `value` is `None`) the setting with `key` in the
+    /// config file at `path`. The file is only written if the value is valid
+    /// and, unless the configuration was invalid already (so that it can be
+    /// fixed one setting at a time), the resulting configuration is valid.
+    pub fn set_in_file(path: &Path, key: &str, value: Option<&str>) -> Result<()> {
+        let setting = Setting::find(key)?;
+        let value = value.map(|value| setting.parse_value(value)).transpose()?;
+        let mut only_setting = toml::Table::new();
+        setting.set(&mut only_setting, value.clone())?;
+        Config::from_table(only_setting).with_context(|| format!("Invalid value for {}", key))?;
+
+        let mut table = read_table(path)?;
+        let was_valid = Config::from_table(table.clone()).is_ok();
+        setting.set(&mut table, value)?;
+        if was_valid {
+            Config::from_table(table.clone())?;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let contents = toml::to_string(&table)?;
+        std::fs::write(path, contents).with_context(|| format!("Unable to write {:?}", path))
+    }
+
+    /// The value of every setting with its source, as given by the config
+    /// file at `path` and the environment variables.
+    pub fn settings_with_sources(path: &Path) -> Result<Vec<(Setting, Option<toml::Value>, SettingSource)>> {
+        let table = read_table(path)?;
+        let settings = SETTINGS
+            .into_iter()
+            .map(|setting| {
+                let env_value = setting.env_var.and_then(|var| Some((var, std::env::var(var).ok()?)));
+                match (env_value, setting.get(&table)) {
+                    (Some((var, value)), _) => {
+                        (setting, setting.parse_value(&value).ok(), SettingSource::Environment(var))
+                    }
+                    (None, Some(value)) => (setting, Some(value.clone()), SettingSource::ConfigFile),
+                    (None, None) => (setting, None, SettingSource::Default),
+                }
+            })
+            .collect();
+        Ok(settings)
+    }
+
+    /// The configured player name, falling back to the name of the user.
+    pub fn player_name(&self) -> Option<String> {
+        self.player_name
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+    }
+
+    /// Seconds before the execution of a solution is timed out (0 for no
+    /// timeout).
+    pub fn timeout(&self) -> f64 {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// Seconds before the build of a solution is timed out (0 for no
+    /// timeout).
+    pub fn build_timeout(&self) -> f64 {
+        self.build_timeout.unwrap_or(DEFAULT_BUILD_TIMEOUT)
+    }
+}
+
+/// Reads the config file at `path` as a TOML table. A missing file is an
+/// empty table.
+fn read_table(path: &Path) -> Result<toml::Table> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents).with_context(|| format!("Invalid config file {:?}", path)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(err) => Err(err).with_context(|| format!("Unable to read config file {:?}", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_match_config_fields() {
+        let mut table = toml::Table::new();
+        for setting in SETTINGS {
+            let value = match setting.kind {
+                SettingKind::Bool => "true",
+                SettingKind::Number => "1.5",
+                SettingKind::Text if setting.key == "color" => "never",
+                SettingKind::Text if setting.key == "strategy" => "retry-failed",
+                SettingKind::Text => "text",
+            };
+            setting.set(&mut table, Some(setting.parse_value(value).unwrap())).unwrap();
+        }
+        let config = Config::from_table(table).unwrap();
+        assert_eq!(config.timeout, Some(1.5));
+        assert_eq!(config.build_timeout, Some(1.5));
+        assert_eq!(config.color, ColorChoice::Never);
+        assert_eq!(config.strategy, Some(Strategy::RetryFailed));
+        assert_eq!(config.hooks.on_next.as_deref(), Some("text"));
+    }
+
+    #[test]
+    fn set_and_remove_dotted_key() {
+        let setting = Setting::find("hooks.on-pass").unwrap();
+        let mut table = toml::Table::new();
+        setting.set(&mut table, Some(setting.parse_value("echo yes").unwrap())).unwrap();
+        assert_eq!(setting.get(&table).and_then(toml::Value::as_str), Some("echo yes"));
+        setting.set(&mut table, None).unwrap();
+        assert_eq!(setting.get(&table), None);
+    }
+
+    #[test]
+    fn set_in_invalid_file() {
+        let path = std::env::temp_dir().join(format!("coctus-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "timeout = \"x\"\ncolor = \"never\"\n").unwrap();
+        assert!(Config::set_in_file(&path, "timeout", Some("soon")).is_err());
+        assert!(Config::set_in_file(&path, "strategy", Some("best")).is_err());
+        Config::set_in_file(&path, "timeout", Some("2")).unwrap();
+
+        let config = Config::from_table(read_table(&path).unwrap()).unwrap();
+        assert_eq!(config.timeout, Some(2.0));
+        assert_eq!(config.color, ColorChoice::Never);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_values() {
+        assert!(Setting::find("no-such-setting").is_err());
+        assert!(Setting::find("timeout").unwrap().parse_value("soon").is_err());
+        assert!(Setting::find("show-whitespace").unwrap().parse_value("maybe").is_err());
+
+        let strategy = Setting::find("strategy").unwrap();
+        let mut table = toml::Table::new();
+        strategy.set(&mut table, Some(strategy.parse_value("best").unwrap())).unwrap();
+        assert!(Config::from_table(table).is_err());
+
+        let timeout = Setting::find("timeout").unwrap();
+        let mut table = toml::Table::new();
+        timeout.set(&mut table, Some(timeout.parse_value("-1").unwrap())).unwrap();
+        assert!(Config::from_table(table).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+/// How the output of a failed testcase is compared to the expected output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DiffMode {
+    /// Print the actual output with the differences highlighted.
+    #[default]
+    Inline,
+    /// Print the expected and actual output next to each other with line
+    /// numbers.
+    SideBySide,
+    /// Print a line-based diff with `-` and `+` markers (readable without
+    /// colors).
+    Unified,
+}
+
+/// A line of a line-based diff between the expected and actual output. Line
+/// numbers start from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    /// Line is the same in both outputs.
+    Equal {
+        expected_no: usize,
+        actual_no: usize,
+        line: &'a str,
+    },
+    /// Line is only present in the expected output.
+    Missing { expected_no: usize, line: &'a str },
+    /// Line is only present in the actual output.
+    Extra { actual_no: usize, line: &'a str },
+}
+
+/// The LCS table of [line_diff] has one cell per pair of lines, so outputs
+/// (of wrong solutions) with more lines than this are not aligned.
+const MAX_LCS_CELLS: usize = 1 << 20;
+
+/// Computes a line-based diff between `expected` and `actual` using the
+/// longest common subsequence of lines. The lines between the common prefix
+/// and suffix are shown as missing and extra without aligning them if there
+/// are too many of them.
+pub fn line_diff<'a>(expected: &'a str, actual: &'a str) -> Vec<DiffLine<'a>> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let prefix = expected.iter().zip(&actual).take_while(|(a, b)| a == b).count();
+    let suffix = expected[prefix..]
+        .iter()
+        .rev()
+        .zip(actual[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (n, m) = (expected.len() - suffix, actual.len() - suffix);
+
+    let mut diff = Vec::with_capacity(expected.len().max(actual.len()));
+    let equal = |i: usize, j: usize| DiffLine::Equal {
+        expected_no: i + 1,
+        actual_no: j + 1,
+        line: expected[i],
+    };
+    let missing = |i: usize| DiffLine::Missing {
+        expected_no: i + 1,
+        line: expected[i],
+    };
+    let extra = |j: usize| DiffLine::Extra {
+        actual_no: j + 1,
+        line: actual[j],
+    };
+
+    diff.extend((0..prefix).map(|i| equal(i, i)));
+    if (n - prefix).saturating_mul(m - prefix) > MAX_LCS_CELLS {
+        diff.extend((prefix..n).map(missing));
+        diff.extend((prefix..m).map(extra));
+    } else {
+        // lcs[i][j] is the length of the LCS of expected[prefix + i..n] and
+        // actual[prefix + j..m]
+        let (rows, cols) = (n - prefix, m - prefix);
+        let mut lcs = vec![vec![0u32; cols + 1]; rows + 1];
+        for i in (0..rows).rev() {
+            for j in (0..cols).rev() {
+                lcs[i][j] = if expected[prefix + i] == actual[prefix + j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < rows || j < cols {
+            if i < rows && j < cols && expected[prefix + i] == actual[prefix + j] {
+                diff.push(equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < rows && (j == cols || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                diff.push(missing(prefix + i));
+                i += 1;
+            } else {
+                diff.push(extra(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    diff.extend((0..suffix).map(|k| equal(n + k, m + k)));
+    diff
+}
+
+/// A row of a side-by-side diff: the expected line and the actual line (with
+/// their line numbers) that are shown next to each other.
+pub type SideBySideRow<'a> = (Option<(usize, &'a str)>, Option<(usize, &'a str)>);
+
+/// Arranges a line-based diff into rows for a side-by-side view. Consecutive
+/// missing and extra lines are paired up on the same rows.
+pub fn side_by_side<'a>(diff: &[DiffLine<'a>]) -> Vec<SideBySideRow<'a>> {
+    let mut rows = Vec::new();
+    let mut missing = Vec::new();
+    let mut extra = Vec::new();
+
+    fn flush<'a>(
+        rows: &mut Vec<SideBySideRow<'a>>,
+        missing: &mut Vec<(usize, &'a str)>,
+        extra: &mut Vec<(usize, &'a str)>,
+    ) {
+        for i in 0..missing.len().max(extra.len()) {
+            rows.push((missing.get(i).copied(), extra.get(i).copied()));
+        }
+        missing.clear();
+        extra.clear();
+    }
+
+    for diff_line in diff {
+        match *diff_line {
+            DiffLine::Equal {
+                expected_no,
+                actual_no,
+                line,
+            } => {
+                flush(&mut rows, &mut missing, &mut extra);
+                rows.push((Some((expected_no, line)), Some((actual_no, line))));
+            }
+            DiffLine::Missing { expected_no, line } => missing.push((expected_no, line)),
+            DiffLine::Extra { actual_no, line } => extra.push((actual_no, line)),
+        }
+    }
+    flush(&mut rows, &mut missing, &mut extra);
+    rows
+}
+
+/// Returns the position (line, column) of the first character where `actual`
+/// differs from `expected`, or `None` if they are equal. Both line and column
+/// start from 1.
+pub fn first_divergence(expected: &str, actual: &str) -> Option<(usize, usize)> {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line_no = 0;
+
+    loop {
+        line_no += 1;
+        match (expected_lines.next(), actual_lines.next()) {
+            (None, None) => return None,
+            (Some(_), None) | (None, Some(_)) => return Some((line_no, 1)),
+            (Some(exp), Some(act)) if exp == act => continue,
+            (Some(exp), Some(act)) => {
+                let common_prefix = exp.chars().zip(act.chars()).take_while(|(a, b)| a == b).count();
+                return Some((line_no, common_prefix + 1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_diff_equal_outputs() {
+        let diff = line_diff("a\nb", "a\nb");
+        assert!(diff.iter().all(|line| matches!(line, DiffLine::Equal { .. })));
+        assert_eq!(diff.len(), 2);
+    }
+
+    #[test]
+    fn line_diff_changed_line() {
+        let diff = line_diff("a\nb\nc", "a\nx\nc");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Equal {
+                    expected_no: 1,
+                    actual_no: 1,
+                    line: "a"
+                },
+                DiffLine::Missing {
+                    expected_no: 2,
+                    line: "b"
+                },
+                DiffLine::Extra {
+                    actual_no: 2,
+                    line: "x"
+                },
+                DiffLine::Equal {
+                    expected_no: 3,
+                    actual_no: 3,
+                    line: "c"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn line_diff_missing_and_extra_lines() {
+        let diff = line_diff("a\nb\nc", "b\nc\nd");
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Missing {
+                    expected_no: 1,
+                    line: "a"
+                },
+                DiffLine::Equal {
+                    expected_no: 2,
+                    actual_no: 1,
+                    line: "b"
+                },
+                DiffLine::Equal {
+                    expected_no: 3,
+                    actual_no: 2,
+                    line: "c"
+                },
+                DiffLine::Extra {
+                    actual_no: 3,
+                    line: "d"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn line_diff_huge_output() {
+        let expected = "1\n2\n3";
+        let actual = "x\n".repeat(1_000_000) + "3";
+        let diff = line_diff(expected, &actual);
+        assert_eq!(diff.len(), 1_000_003);
+        assert!(matches!(diff[0], DiffLine::Missing { line: "1", .. }));
+        assert!(matches!(diff.last(), Some(DiffLine::Equal { line: "3", .. })));
+    }
+
+    #[test]
+    fn side_by_side_pairs_changed_lines() {
+        let diff = line_diff("a\nb\nc\nd", "a\nx\nd\ne");
+        assert_eq!(
+            side_by_side(&diff),
+            vec![
+                (Some((1, "a")), Some((1, "a"))),
+                (Some((2, "b")), Some((2, "x"))),
+                (Some((3, "c")), None),
+                (Some((4, "d")), Some((3, "d"))),
+                (None, Some((4, "e"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_divergence_positions() {
+        assert_eq!(first_divergence("abc", "abc"), None);
+        assert_eq!(first_divergence("abc\ndef", "abc\ndxf"), Some((2, 2)));
+        assert_eq!(first_divergence("abc", "abcd"), Some((1, 4)));
+        assert_eq!(first_divergence("abc\ndef", "abc"), Some((2, 1)));
+        assert_eq!(first_divergence("", "x"), Some((1, 1)));
+    }
+}
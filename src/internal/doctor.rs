@@ -0,0 +1,51 @@
+use clashlib::workspace::{Finding, Severity};
+
+use super::OutputStyle;
+
+/// Prints the outcome of the checks of `coctus doctor` and keeps count of
+/// the problems.
+pub struct Doctor {
+    ostyle: OutputStyle,
+    pub warnings: usize,
+    pub errors: usize,
+}
+
+impl Doctor {
+    pub fn new(ostyle: OutputStyle) -> Self {
+        Doctor {
+            ostyle,
+            warnings: 0,
+            errors: 0,
+        }
+    }
+
+    pub fn section(&self, title: &str) {
+        println!("\n{}", self.ostyle.title.paint(title));
+    }
+
+    pub fn ok(&self, message: impl AsRef<str>) {
+        println!("{} {}", self.ostyle.success.paint("✓"), message.as_ref());
+    }
+
+    /// Something that may be a problem, depending on how coctus is used.
+    pub fn warning(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.warnings += 1;
+        println!("{} {}", self.ostyle.secondary_title.paint("!"), message.as_ref());
+        println!("  {}", self.ostyle.dim_color.paint(fix.as_ref()));
+    }
+
+    pub fn error(&mut self, message: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.errors += 1;
+        println!("{} {}", self.ostyle.failure.paint("✗"), message.as_ref());
+        println!("  {}", self.ostyle.dim_color.paint(fix.as_ref()));
+    }
+
+    /// Prints the outcome of a check of the workspace.
+    pub fn report(&mut self, finding: Finding) {
+        match finding.severity {
+            Severity::Ok => self.ok(finding.message),
+            Severity::Warning => self.warning(finding.message, finding.fix),
+            Severity::Error => self.error(finding.message, finding.fix),
+        }
+    }
+}
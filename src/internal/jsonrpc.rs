@@ -0,0 +1,265 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Error codes defined by the JSON-RPC 2.0 specification.
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// Error code for requests that were understood but could not be carried
+/// out (the range -32000 to -32099 is reserved for such server errors).
+pub const SERVER_ERROR: i64 = -32000;
+
+/// A request (or a notification if it has no id) from the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub id: Option<Value>,
+    pub method: String,
+    pub params: Value,
+}
+
+/// An error that is sent back to the client as the response to a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {:?}", method))
+    }
+
+    pub fn invalid_params(err: impl std::fmt::Display) -> Self {
+        RpcError::new(INVALID_PARAMS, format!("Invalid params: {}", err))
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        RpcError::new(SERVER_ERROR, format!("{:#}", err))
+    }
+}
+
+impl From<clashlib::Error> for RpcError {
+    fn from(err: clashlib::Error) -> Self {
+        RpcError::from(anyhow::Error::from(err))
+    }
+}
+
+/// Deserializes the params of a request (missing params are an empty
+/// object, so that params with only optional fields can be left out).
+pub fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params).map_err(RpcError::invalid_params)
+}
+
+/// JSON-RPC 2.0 connection that uses the same framing as the Language Server
+/// Protocol: every message is preceded by a `Content-Length` header and an
+/// empty line.
+pub struct Connection<R, W> {
+    reader: R,
+    writer: W,
+    /// Id of the next request of the server to the client.
+    next_id: i64,
+}
+
+impl Connection<std::io::StdinLock<'static>, std::io::Stdout> {
+    pub fn stdio() -> Self {
+        Connection::new(std::io::stdin().lock(), std::io::stdout())
+    }
+}
+
+impl<R: BufRead, W: Write> Connection<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Connection {
+            reader,
+            writer,
+            next_id: 1,
+        }
+    }
+
+    /// Reads the next request. Returns `None` when the client closes the
+    /// connection. Malformed requests are answered with an error and skipped.
+    pub fn receive(&mut self) -> Result<Option<Request>> {
+        loop {
+            let Some(content) = self.read_content()? else {
+                return Ok(None)
+            };
+            let message: Value = match serde_json::from_str(&content) {
+                Ok(message) => message,
+                Err(err) => {
+                    self.respond(Value::Null, Err(RpcError::new(PARSE_ERROR, err.to_string())))?;
+                    continue
+                }
+            };
+            let id = message.get("id").cloned();
+            match message.get("method").and_then(Value::as_str) {
+                Some(method) => {
+                    return Ok(Some(Request {
+                        id,
+                        method: method.to_string(),
+                        params: message.get("params").cloned().unwrap_or(Value::Null),
+                    }))
+                }
+                // Responses to requests of the server are not expected
+                None if message.get("result").is_some() || message.get("error").is_some() => continue,
+                None => {
+                    let error = RpcError::new(INVALID_REQUEST, "Request has no method");
+                    self.respond(id.unwrap_or(Value::Null), Err(error))?;
+                }
+            }
+        }
+    }
+
+    fn read_content(&mut self) -> Result<Option<String>> {
+        let mut content_length = None;
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return match content_length {
+                    None => Ok(None),
+                    Some(_) => Err(anyhow!("Connection closed in the middle of a message")),
+                }
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                if content_length.is_some() {
+                    break
+                }
+                continue
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("Content-Length") {
+                    let length = value.trim().parse::<usize>().context("Invalid Content-Length header")?;
+                    content_length = Some(length);
+                }
+            }
+        }
+        let mut content = vec![0; content_length.unwrap_or_default()];
+        self.reader.read_exact(&mut content)?;
+        Ok(Some(String::from_utf8(content).context("Message is not valid UTF-8")?))
+    }
+
+    pub fn respond(&mut self, id: Value, result: Result<Value, RpcError>) -> Result<()> {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": error.code, "message": error.message },
+            }),
+        };
+        self.send(&message)
+    }
+
+    pub fn notify(&mut self, method: &str, params: impl Serialize) -> Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    /// Sends a request to the client. The response is not waited for (it is
+    /// skipped by [receive](Self::receive)).
+    pub fn request(&mut self, method: &str, params: impl Serialize) -> Result<()> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &Value) -> Result<()> {
+        let content = serde_json::to_string(message)?;
+        write!(self.writer, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn framed(messages: &[&str]) -> Vec<u8> {
+        messages
+            .iter()
+            .map(|m| format!("Content-Length: {}\r\n\r\n{}", m.len(), m))
+            .collect::<String>()
+            .into()
+    }
+
+    fn sent(output: &[u8]) -> Vec<Value> {
+        let mut connection = Connection::new(output, Vec::new());
+        std::iter::from_fn(|| connection.read_content().unwrap())
+            .map(|content| serde_json::from_str(&content).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn receive_requests_and_notifications() {
+        let input = framed(&[
+            r#"{"jsonrpc":"2.0","id":1,"method":"clash/current"}"#,
+            r#"{"jsonrpc":"2.0","method":"exit","params":{"now":true}}"#,
+        ]);
+        let mut connection = Connection::new(input.as_slice(), Vec::new());
+        let request = connection.receive().unwrap().unwrap();
+        assert_eq!(request.id, Some(json!(1)));
+        assert_eq!(request.method, "clash/current");
+        assert_eq!(request.params, Value::Null);
+        let notification = connection.receive().unwrap().unwrap();
+        assert_eq!(notification.id, None);
+        assert_eq!(notification.params, json!({"now": true}));
+        assert_eq!(connection.receive().unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_requests_are_answered_with_errors() {
+        let input = framed(&["{not json", r#"{"jsonrpc":"2.0","id":7}"#]);
+        let mut output = Vec::new();
+        let mut connection = Connection::new(input.as_slice(), &mut output);
+        assert_eq!(connection.receive().unwrap(), None);
+
+        let responses = sent(&output);
+        assert_eq!(responses[0]["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(responses[1]["id"], json!(7));
+        assert_eq!(responses[1]["error"]["code"], json!(INVALID_REQUEST));
+    }
+
+    #[test]
+    fn responses_are_framed() {
+        let mut output = Vec::new();
+        let mut connection = Connection::new(&b""[..], &mut output);
+        connection.respond(json!(1), Ok(json!("ok"))).unwrap();
+        connection.respond(json!(2), Err(RpcError::method_not_found("nope"))).unwrap();
+        connection.notify("tests/result", json!({"index": 1})).unwrap();
+        connection.request("window/showDocument", json!({})).unwrap();
+        connection.request("window/showDocument", json!({})).unwrap();
+
+        let messages = sent(&output);
+        assert_eq!(messages[0], json!({"jsonrpc": "2.0", "id": 1, "result": "ok"}));
+        assert_eq!(messages[1]["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(messages[2]["method"], json!("tests/result"));
+        assert_eq!(messages[2].get("id"), None);
+        assert_eq!(messages[4]["id"], json!(2));
+    }
+
+    #[test]
+    fn missing_params_are_an_empty_object() {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            handle: Option<String>,
+        }
+        assert_eq!(parse_params::<Params>(Value::Null).unwrap().handle, None);
+        assert!(parse_params::<Params>(json!({"handle": 3})).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use clashlib::stub::analysis::{self, analyze, Analysis};
+use clashlib::stub::{self, StubConfig};
+use lsp_types::{
+    CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    ExecuteCommandOptions, ExecuteCommandParams, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    InitializeParams, InitializeResult, MarkupContent, MarkupKind, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, ServerInfo, ShowDocumentParams, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Uri,
+};
+use serde_json::{json, Value};
+
+use super::jsonrpc::{parse_params, Connection, RpcError, INVALID_REQUEST};
+
+/// Command of the code actions that preview the stub of a generator.
+pub const PREVIEW_STUB_COMMAND: &str = "coctus.previewStub";
+
+/// Language server for stub generator files: reports the mistakes in the
+/// generator, shows the type of a variable on hover and previews the stub
+/// that the generator renders to.
+///
+/// Positions are sent as character offsets, which only differ from the
+/// UTF-16 offsets of the protocol outside of the ASCII that generators are
+/// written in.
+pub struct LanguageServer<'a> {
+    languages: Vec<String>,
+    stub_config: &'a dyn Fn(&str) -> Result<StubConfig>,
+    documents: BTreeMap<Uri, String>,
+    /// Whether the client can open the previewed stub (`window/showDocument`).
+    show_document: bool,
+    /// Where the previewed stubs are written. It belongs to the user, unlike
+    /// a fixed path in the shared temporary directory.
+    preview_dir: PathBuf,
+    shutdown: bool,
+}
+
+impl<'a> LanguageServer<'a> {
+    pub fn new(
+        languages: Vec<String>,
+        stub_config: &'a dyn Fn(&str) -> Result<StubConfig>,
+        preview_dir: PathBuf,
+    ) -> Self {
+        LanguageServer {
+            languages,
+            stub_config,
+            documents: BTreeMap::new(),
+            show_document: false,
+            preview_dir,
+            shutdown: false,
+        }
+    }
+
+    /// Serves the client until it sends `exit` or closes the connection.
+    pub fn run<R: BufRead, W: Write>(&mut self, connection: &mut Connection<R, W>) -> Result<()> {
+        while let Some(request) = connection.receive()? {
+            match request.id {
+                Some(id) => {
+                    let result = self.handle_request(connection, &request.method, request.params);
+                    connection.respond(id, result)?;
+                }
+                None if request.method == "exit" => break,
+                None => self.handle_notification(connection, &request.method, request.params)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request<R: BufRead, W: Write>(
+        &mut self,
+        connection: &mut Connection<R, W>,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        if self.shutdown {
+            return Err(RpcError::new(INVALID_REQUEST, "The server is shut down"))
+        }
+        match method {
+            "initialize" => {
+                let params: InitializeParams = parse_params(params)?;
+                self.show_document = params
+                    .capabilities
+                    .window
+                    .and_then(|window| window.show_document)
+                    .is_some_and(|show_document| show_document.support);
+                Ok(json!(initialize_result()))
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let params: HoverParams = parse_params(params)?;
+                let position = params.text_document_position_params;
+                Ok(json!(self.hover(&position.text_document.uri, position.position)))
+            }
+            "textDocument/codeAction" => {
+                let params: CodeActionParams = parse_params(params)?;
+                Ok(json!(self.code_actions(&params.text_document.uri)))
+            }
+            "workspace/executeCommand" => {
+                let params: ExecuteCommandParams = parse_params(params)?;
+                if params.command != PREVIEW_STUB_COMMAND {
+                    return Err(RpcError::invalid_params(format!("unknown command {:?}", params.command)))
+                }
+                let (uri, language) = match params.arguments.as_slice() {
+                    [uri, Value::String(language)] => (
+                        serde_json::from_value::<Uri>(uri.clone()).map_err(RpcError::invalid_params)?,
+                        language,
+                    ),
+                    _ => return Err(RpcError::invalid_params("expected the document and the language")),
+                };
+                Ok(json!(self.preview_stub(connection, &uri, language)?))
+            }
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    fn handle_notification<R: BufRead, W: Write>(
+        &mut self,
+        connection: &mut Connection<R, W>,
+        method: &str,
+        params: Value,
+    ) -> Result<()> {
+        // Notifications can not be answered, so invalid ones are ignored
+        match method {
+            "textDocument/didOpen" => {
+                let Ok(params) = parse_params::<DidOpenTextDocumentParams>(params) else {
+                    return Ok(())
+                };
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), document.text);
+                self.publish_diagnostics(connection, document.uri, Some(document.version))
+            }
+            "textDocument/didChange" => {
+                let Ok(mut params) = parse_params::<DidChangeTextDocumentParams>(params) else {
+                    return Ok(())
+                };
+                // Only full documents are synced
+                let Some(change) = params.content_changes.pop() else {
+                    return Ok(())
+                };
+                let document = params.text_document;
+                self.documents.insert(document.uri.clone(), change.text);
+                self.publish_diagnostics(connection, document.uri, Some(document.version))
+            }
+            "textDocument/didClose" => {
+                let Ok(params) = parse_params::<DidCloseTextDocumentParams>(params) else {
+                    return Ok(())
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                // Clear the diagnostics of the closed document
+                let params = PublishDiagnosticsParams::new(uri, Vec::new(), None);
+                connection.notify("textDocument/publishDiagnostics", params)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn analysis(&self, uri: &Uri) -> Option<Analysis> {
+        self.documents.get(uri).map(|generator| analyze(generator))
+    }
+
+    fn publish_diagnostics<R: BufRead, W: Write>(
+        &self,
+        connection: &mut Connection<R, W>,
+        uri: Uri,
+        version: Option<i32>,
+    ) -> Result<()> {
+        let diagnostics = self
+            .analysis(&uri)
+            .unwrap_or_default()
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| lsp_types::Diagnostic {
+                range: range(diagnostic.span),
+                severity: Some(match diagnostic.severity {
+                    analysis::Severity::Error => DiagnosticSeverity::ERROR,
+                    analysis::Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                source: Some(String::from("coctus")),
+                message: diagnostic.message,
+                ..Default::default()
+            })
+            .collect();
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, version);
+        connection.notify("textDocument/publishDiagnostics", params)
+    }
+
+    fn hover(&self, uri: &Uri, position: Position) -> Option<Hover> {
+        let analysis = self.analysis(uri)?;
+        let variable = analysis.variable_at(position.line as usize, position.character as usize)?;
+        let mut value = format!("```\n{}:{}\n```", variable.name, variable.var_type);
+        if let Some(comment) = &variable.input_comment {
+            value.push_str(&format!("\n\n{}", comment));
+        }
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: None,
+        })
+    }
+
+    /// A preview for every language, as long as the stub can be generated.
+    fn code_actions(&self, uri: &Uri) -> Vec<CodeActionOrCommand> {
+        if !self.analysis(uri).is_some_and(|analysis| analysis.is_valid()) {
+            return Vec::new()
+        }
+        self.languages
+            .iter()
+            .map(|language| {
+                CodeActionOrCommand::Command(lsp_types::Command {
+                    title: format!("Preview stub in {}", language),
+                    command: String::from(PREVIEW_STUB_COMMAND),
+                    arguments: Some(vec![json!(uri), json!(language)]),
+                })
+            })
+            .collect()
+    }
+
+    /// Renders the stub of a document, writes it to the preview directory
+    /// (opened in the client if it supports it) and returns it.
+    fn preview_stub<R: BufRead, W: Write>(
+        &self,
+        connection: &mut Connection<R, W>,
+        uri: &Uri,
+        language: &str,
+    ) -> Result<String> {
+        let generator = self.documents.get(uri).with_context(|| format!("{} is not open", uri.as_str()))?;
+        let stub_config = (self.stub_config)(language)?;
+        let extension = stub_config.source_file_ext().to_string();
+        let stub = stub::generate_from_config(stub_config, generator)?;
+
+        std::fs::create_dir_all(&self.preview_dir)
+            .with_context(|| format!("Unable to create {:?}", self.preview_dir))?;
+        let preview_file = self.preview_dir.join(format!("stub.{}", extension));
+        std::fs::write(&preview_file, &stub)
+            .with_context(|| format!("Unable to write the preview to {:?}", preview_file))?;
+
+        if self.show_document {
+            let params = ShowDocumentParams {
+                uri: file_uri(&preview_file)?,
+                external: None,
+                take_focus: Some(true),
+                selection: None,
+            };
+            connection.request("window/showDocument", params)?;
+        }
+        Ok(stub)
+    }
+}
+
+fn initialize_result() -> InitializeResult {
+    InitializeResult {
+        capabilities: ServerCapabilities {
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![String::from(PREVIEW_STUB_COMMAND)],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+        server_info: Some(ServerInfo {
+            name: String::from("coctus"),
+            version: Some(String::from(env!("CARGO_PKG_VERSION"))),
+        }),
+    }
+}
+
+fn range(span: analysis::Span) -> Range {
+    let line = span.line as u32;
+    Range::new(Position::new(line, span.start as u32), Position::new(line, span.end as u32))
+}
+
+/// URI of an absolute path.
+fn file_uri(path: &Path) -> Result<Uri> {
+    let path = path.to_string_lossy().replace('\\', "/").replace(' ', "%20");
+    let uri = match path.starts_with('/') {
+        true => format!("file://{}", path),
+        // Windows paths start with the drive letter
+        false => format!("file:///{}", path),
+    };
+    Uri::from_str(&uri).map_err(|err| anyhow!("Invalid file URI {}: {}", uri, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(method: &str, id: Option<i64>, params: Value) -> String {
+        let message = match id {
+            Some(id) => json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+            None => json!({ "jsonrpc": "2.0", "method": method, "params": params }),
+        };
+        let content = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{}", content.len(), content)
+    }
+
+    fn replies(output: &[u8]) -> Vec<Value> {
+        let output = String::from_utf8(output.to_vec()).unwrap();
+        output
+            .split("Content-Length: ")
+            .skip(1)
+            .map(|message| serde_json::from_str(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_hover_and_preview() {
+        let uri = "file:///clash/stub.txt";
+        let document =
+            json!({ "uri": uri, "languageId": "coctus-stub", "version": 1, "text": "read n:int\nwrite hi" });
+        let position = json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 5 } });
+        let input = [
+            message("initialize", Some(1), json!({ "capabilities": {} })),
+            message("textDocument/didOpen", None, json!({ "textDocument": document })),
+            message("textDocument/hover", Some(2), position),
+            message(
+                "workspace/executeCommand",
+                Some(3),
+                json!({ "command": PREVIEW_STUB_COMMAND, "arguments": [uri, "python"] }),
+            ),
+            message(
+                "textDocument/didChange",
+                None,
+                json!({
+                    "textDocument": { "uri": uri, "version": 2 },
+                    "contentChanges": [{ "text": "read n" }],
+                }),
+            ),
+            message("shutdown", Some(4), Value::Null),
+            message("exit", None, Value::Null),
+        ]
+        .concat();
+
+        let mut output = Vec::new();
+        let mut connection = Connection::new(input.as_bytes(), &mut output);
+        let stub_config = |language: &str| Ok(StubConfig::read_from_embedded(language)?);
+        let preview_dir = std::env::temp_dir().join(format!("coctus-lsp-preview-{}", std::process::id()));
+        LanguageServer::new(vec![String::from("python")], &stub_config, preview_dir.clone())
+            .run(&mut connection)
+            .unwrap();
+        assert!(preview_dir.join("stub.py").is_file());
+        std::fs::remove_dir_all(&preview_dir).unwrap();
+
+        let replies = replies(&output);
+        assert_eq!(replies[0]["result"]["capabilities"]["hoverProvider"], json!(true));
+        assert_eq!(replies[1]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[2]["result"]["contents"]["value"], json!("```\nn:int\n```"));
+        assert_eq!(replies[3]["result"], json!("n = int(input())\nprint(\"hi\")"));
+        let diagnostics = &replies[4]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 5 }));
+        assert_eq!(replies[4]["params"]["version"], json!(2));
+        assert_eq!(replies[5]["result"], Value::Null);
+    }
+
+    #[test]
+    fn previews_are_only_offered_for_valid_generators() {
+        let stub_config = |language: &str| Ok(StubConfig::read_from_embedded(language)?);
+        let languages = vec![String::from("python"), String::from("rust")];
+        let mut server = LanguageServer::new(languages, &stub_config, PathBuf::from("preview"));
+        let uri = Uri::from_str("file:///stub.txt").unwrap();
+        server.documents.insert(uri.clone(), String::from("read x:int"));
+        assert_eq!(server.code_actions(&uri).len(), 2);
+        server.documents.insert(uri.clone(), String::from("read x"));
+        assert_eq!(server.code_actions(&uri).len(), 0);
+    }
+}
@@ -1,11 +1,17 @@
+use std::fmt::{self, Write};
+
 use ansi_term::{Color, Style};
 use clashlib::clash::{Clash, Testcase};
+use clashlib::metadata::ClashMetadata;
+use clashlib::solution::hint::{explain_wrong_output, Hint};
 use clashlib::solution::TestResult;
 
+use super::diff::{first_divergence, line_diff, side_by_side, DiffLine, DiffMode};
 use super::formatter::show_whitespace;
 use super::lines_with_endings::LinesWithEndings;
 use crate::internal::formatter::format_cg;
 
+#[derive(Clone)]
 pub struct OutputStyle {
     pub title: Style,
     pub secondary_title: Style,
@@ -27,6 +33,7 @@ pub struct OutputStyle {
     pub diff_green_whitespace: Option<Style>,
     pub diff_red: Style,
     pub diff_red_whitespace: Option<Style>,
+    pub diff_mode: DiffMode,
 }
 
 impl OutputStyle {
@@ -52,26 +59,32 @@ impl OutputStyle {
             diff_green_whitespace: Some(Style::default()),
             diff_red: Style::default(),
             diff_red_whitespace: Some(Style::default()),
+            diff_mode: DiffMode::default(),
         }
     }
     pub fn from_env(show_whitespace: bool) -> Self {
-        let mut ostyle = match std::env::var_os("NO_COLOR") {
+        let ostyle = match std::env::var_os("NO_COLOR") {
             Some(s) if s.is_empty() => OutputStyle::default(),
             Some(_) => OutputStyle::plain(),
             None => OutputStyle::default(),
         };
+        ostyle.with_whitespace(show_whitespace)
+    }
+
+    /// Enables or disables rendering whitespace as visible characters.
+    pub fn with_whitespace(mut self, show_whitespace: bool) -> Self {
         if show_whitespace {
-            ostyle.input_whitespace = ostyle.input_whitespace.or(Some(ostyle.input));
-            ostyle.output_whitespace = ostyle.output_whitespace.or(Some(ostyle.output));
-            ostyle.diff_green_whitespace = ostyle.diff_green_whitespace.or(Some(ostyle.diff_green));
-            ostyle.diff_red_whitespace = ostyle.diff_red_whitespace.or(Some(ostyle.diff_red));
+            self.input_whitespace = self.input_whitespace.or(Some(self.input));
+            self.output_whitespace = self.output_whitespace.or(Some(self.output));
+            self.diff_green_whitespace = self.diff_green_whitespace.or(Some(self.diff_green));
+            self.diff_red_whitespace = self.diff_red_whitespace.or(Some(self.diff_red));
         } else {
-            ostyle.input_whitespace = None;
-            ostyle.output_whitespace = None;
-            ostyle.diff_green_whitespace = None;
-            ostyle.diff_red_whitespace = None;
+            self.input_whitespace = None;
+            self.output_whitespace = None;
+            self.diff_green_whitespace = None;
+            self.diff_red_whitespace = None;
         }
-        ostyle
+        self
     }
 }
 
@@ -98,6 +111,7 @@ impl Default for OutputStyle {
             diff_green_whitespace: Some(Style::new().fg(Color::RGB(0, 70, 0))),
             diff_red: Style::new().fg(Color::RGB(255, 111, 111)),
             diff_red_whitespace: Some(Style::new().fg(Color::Red).on(Color::RGB(70, 0, 0))),
+            diff_mode: DiffMode::default(),
         }
     }
 }
@@ -116,26 +130,60 @@ impl OutputStyle {
     }
 
     pub fn print_headers(&self, clash: &Clash) {
-        println!("{}\n", self.title.paint(format!("=== {} ===", clash.title())));
-        println!("{}\n", self.link.paint(clash.codingame_link()));
+        print!("{}", self.format_headers(clash));
+    }
+
+    pub fn format_headers(&self, clash: &Clash) -> String {
+        format!(
+            "{}\n\n{}\n\n",
+            self.title.paint(format!("=== {} ===", clash.title())),
+            self.link.paint(clash.codingame_link())
+        )
+    }
+
+    /// Prints the notes and tags of a clash (if any).
+    pub fn print_metadata(&self, metadata: &ClashMetadata) {
+        let mut labels: Vec<String> = metadata.tags.iter().map(|tag| format!("#{tag}")).collect();
+        if metadata.favorite {
+            labels.insert(0, String::from("★ favorite"));
+        }
+        if metadata.hidden {
+            labels.push(String::from("(hidden)"));
+        }
+        if !labels.is_empty() {
+            println!("{}\n", self.dim_color.paint(labels.join(" ")));
+        }
+        if !metadata.notes.is_empty() {
+            println!("{}\n{}\n", self.secondary_title.paint("Notes:"), metadata.notes);
+        }
     }
 
     pub fn print_statement(&self, clash: &Clash) {
-        println!("{}\n", format_cg(clash.statement(), self));
-        println!("{}\n{}\n", self.title.paint("Input:"), format_cg(clash.input_description(), self));
-        println!("{}\n{}\n", self.title.paint("Output:"), format_cg(clash.output_description(), self));
+        print!("{}", self.format_statement(clash));
+    }
+
+    pub fn format_statement(&self, clash: &Clash) -> String {
+        let mut text = format!("{}\n\n", format_cg(clash.statement(), self));
+        text +=
+            &format!("{}\n{}\n\n", self.title.paint("Input:"), format_cg(clash.input_description(), self));
+        text += &format!(
+            "{}\n{}\n\n",
+            self.title.paint("Output:"),
+            format_cg(clash.output_description(), self)
+        );
         if let Some(constraints) = clash.constraints() {
-            println!("{}\n{}\n", self.title.paint("Constraints:"), format_cg(constraints, self));
+            text += &format!("{}\n{}\n\n", self.title.paint("Constraints:"), format_cg(constraints, self));
         }
 
         let example = clash.testcases().first().expect("example puzzle should have at least one testcase");
-        println!(
-            "{}\n{}\n{}\n{}",
+        text += &format!(
+            "{}\n{}\n{}\n{}\n",
             self.title.paint("Example:"),
             self.styled_testcase_input(example),
             self.title.paint("Expected output:"),
             self.styled_testcase_output(example),
         );
+        text
     }
 
     pub fn print_testcases(&self, clash: &Clash, selection: Vec<usize>) {
@@ -160,7 +208,7 @@ impl OutputStyle {
         self.print_testcases(clash, selection);
     }
 
-    fn print_diff(&self, testcase: &Testcase, stdout: &str) {
+    fn write_diff(&self, out: &mut impl Write, testcase: &Testcase, stdout: &str) -> fmt::Result {
         use dissimilar::Chunk::*;
         use itertools::EitherOrBoth::{Both, Left, Right};
         use itertools::Itertools;
@@ -171,8 +219,7 @@ impl OutputStyle {
         let diff_ws_green = &self.diff_green_whitespace;
 
         if stdout.is_empty() {
-            println!("{}", self.dim_color.paint("(no output)"));
-            return
+            return writeln!(out, "{}", self.dim_color.paint("(no output)"))
         }
 
         let expected_lines = LinesWithEndings::from(&testcase.test_out);
@@ -182,7 +229,7 @@ impl OutputStyle {
         for either_or_both in expected_lines.zip_longest(actual_lines) {
             match either_or_both {
                 Left(_) => missing_lines += 1,
-                Right(s) => print!("{}", show_whitespace(s, diff_red, diff_ws_red)),
+                Right(s) => write!(out, "{}", show_whitespace(s, diff_red, diff_ws_red))?,
                 Both(a, b) => {
                     let mut prev_deleted = false;
 
@@ -192,13 +239,19 @@ impl OutputStyle {
                                 let mut chars = text.chars();
                                 let first_char = chars.next().expect("diff chunk should not be empty");
                                 let rest = chars.as_str();
-                                print!("{}", show_whitespace(&first_char.to_string(), diff_red, diff_ws_red));
+                                write!(
+                                    out,
+                                    "{}",
+                                    show_whitespace(&first_char.to_string(), diff_red, diff_ws_red)
+                                )?;
                                 if !rest.is_empty() {
-                                    print!("{}", show_whitespace(rest, diff_green, diff_ws_green));
+                                    write!(out, "{}", show_whitespace(rest, diff_green, diff_ws_green))?;
                                 }
                             }
-                            Equal(text) => print!("{}", show_whitespace(text, diff_green, diff_ws_green)),
-                            Insert(text) => print!("{}", show_whitespace(text, diff_red, diff_ws_red)),
+                            Equal(text) => {
+                                write!(out, "{}", show_whitespace(text, diff_green, diff_ws_green))?
+                            }
+                            Insert(text) => write!(out, "{}", show_whitespace(text, diff_red, diff_ws_red))?,
                             Delete(_) => {}
                         }
 
@@ -209,62 +262,236 @@ impl OutputStyle {
         }
 
         if !stdout.ends_with('\n') {
-            println!()
+            writeln!(out)?
         }
 
         if missing_lines > 0 {
             let msg = format!("(expected {} more lines)", missing_lines);
-            println!("{}", self.dim_color.paint(msg));
+            writeln!(out, "{}", self.dim_color.paint(msg))?;
         }
+        Ok(())
     }
 
-    pub fn print_result(&self, testcase: &Testcase, test_result: &TestResult) {
+    fn write_side_by_side_diff(
+        &self,
+        out: &mut impl Write,
+        testcase: &Testcase,
+        stdout: &str,
+    ) -> fmt::Result {
+        let diff = line_diff(&testcase.test_out, stdout);
+        let rows = side_by_side(&diff);
+
+        let num_width = rows.len().max(1).to_string().len();
+        let column_width =
+            testcase.test_out.lines().map(|line| line.chars().count()).max().unwrap_or(0).max(8);
+
+        writeln!(
+            out,
+            "{:num_width$} {:column_width$}   {:num_width$} {}",
+            "",
+            self.secondary_title.paint("EXPECTED"),
+            "",
+            self.secondary_title.paint("ACTUAL")
+        )?;
+
+        for row in rows {
+            let marker = match row {
+                (Some((_, a)), Some((_, b))) if a == b => ' ',
+                (Some(_), Some(_)) => '|',
+                (Some(_), None) => '<',
+                _ => '>',
+            };
+            let ((expected_style, expected_ws), (actual_style, actual_ws)) = if marker == ' ' {
+                ((&self.output, &self.output_whitespace), (&self.output, &self.output_whitespace))
+            } else {
+                (
+                    (&self.diff_green, &self.diff_green_whitespace),
+                    (&self.diff_red, &self.diff_red_whitespace),
+                )
+            };
+
+            let (expected_no, expected_line) = match row.0 {
+                Some((no, line)) => (no.to_string(), line),
+                None => (String::new(), ""),
+            };
+            let padding = " ".repeat(column_width.saturating_sub(expected_line.chars().count()));
+            let expected_line = show_whitespace(expected_line, expected_style, expected_ws);
+
+            write!(
+                out,
+                "{} {expected_line}{padding} {marker} ",
+                self.dim_color.paint(format!("{expected_no:>num_width$}"))
+            )?;
+            match row.1 {
+                Some((no, line)) => writeln!(
+                    out,
+                    "{} {}",
+                    self.dim_color.paint(format!("{no:>num_width$}")),
+                    show_whitespace(line, actual_style, actual_ws)
+                )?,
+                None => writeln!(out)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_unified_diff(&self, out: &mut impl Write, testcase: &Testcase, stdout: &str) -> fmt::Result {
+        let diff = line_diff(&testcase.test_out, stdout);
+
+        writeln!(out, "{}", self.diff_green.paint("--- expected"))?;
+        writeln!(out, "{}", self.diff_red.paint("+++ actual"))?;
+        writeln!(
+            out,
+            "{}",
+            self.dim_color.paint(format!(
+                "@@ -1,{} +1,{} @@",
+                testcase.test_out.lines().count(),
+                stdout.lines().count()
+            ))
+        )?;
+
+        for diff_line in diff {
+            match diff_line {
+                DiffLine::Equal { line, .. } => {
+                    writeln!(out, " {}", show_whitespace(line, &self.output, &self.output_whitespace))?
+                }
+                DiffLine::Missing { line, .. } => writeln!(
+                    out,
+                    "{}{}",
+                    self.diff_green.paint("-"),
+                    show_whitespace(line, &self.diff_green, &self.diff_green_whitespace)
+                )?,
+                DiffLine::Extra { line, .. } => writeln!(
+                    out,
+                    "{}{}",
+                    self.diff_red.paint("+"),
+                    show_whitespace(line, &self.diff_red, &self.diff_red_whitespace)
+                )?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the result of running a testcase. `previous` is the testcase that
+    /// was run before this one (used for explaining failures).
+    pub fn print_result(&self, testcase: &Testcase, test_result: &TestResult, previous: Option<&Testcase>) {
+        print!("{}", self.format_result(testcase, test_result, previous));
+    }
+
+    pub fn format_result(
+        &self,
+        testcase: &Testcase,
+        test_result: &TestResult,
+        previous: Option<&Testcase>,
+    ) -> String {
+        let mut text = String::new();
+        self.write_result(&mut text, testcase, test_result, previous)
+            .expect("writing to a String should not fail");
+        text
+    }
+
+    fn write_result(
+        &self,
+        out: &mut impl Write,
+        testcase: &Testcase,
+        test_result: &TestResult,
+        previous: Option<&Testcase>,
+    ) -> fmt::Result {
         let title = self.styled_testcase_title(testcase);
         match test_result {
-            TestResult::Success => {
-                println!("{} {}", self.success.paint("PASS"), title);
-            }
+            TestResult::Success => writeln!(out, "{} {}", self.success.paint("PASS"), title),
 
             TestResult::UnableToRun { error_msg } => {
-                println!("{} {}", self.failure.paint("ERROR"), title);
-                println!(" {}", self.stderr.paint(error_msg));
+                writeln!(out, "{} {}", self.failure.paint("ERROR"), title)?;
+                writeln!(out, " {}", self.stderr.paint(error_msg))
             }
 
             TestResult::WrongOutput { stdout, stderr } => {
-                println!("{} {}", self.failure.paint("FAIL"), title);
-                self.print_failure(testcase, stdout, stderr);
+                writeln!(out, "{} {}", self.failure.paint("FAIL"), title)?;
+                let previous_expected = previous.map(|t| t.test_out.as_str());
+                let hint = explain_wrong_output(&testcase.test_out, stdout, previous_expected);
+                self.write_failure(out, testcase, stdout, stderr, hint)
             }
 
             TestResult::RuntimeError { stdout, stderr } => {
-                println!("{} {}", self.error.paint("ERROR"), title);
-                self.print_failure(testcase, stdout, stderr);
+                writeln!(out, "{} {}", self.error.paint("ERROR"), title)?;
+                self.write_failure(out, testcase, stdout, stderr, None)
             }
 
             TestResult::Timeout { stdout, stderr } => {
-                println!("{} {}", self.error.paint("TIMEOUT"), title);
-                self.print_failure(testcase, stdout, stderr);
+                writeln!(out, "{} {}", self.error.paint("TIMEOUT"), title)?;
+                self.write_failure(out, testcase, stdout, stderr, None)
             }
         }
     }
 
-    fn print_failure(&self, testcase: &Testcase, stdout: &str, stderr: &str) {
-        println!(
-            "{}\n{}\n{}\n{}",
+    /// Prints only whether a testcase passed without revealing its input,
+    /// expected output or the output of the solution.
+    pub fn print_hidden_result(&self, testcase: &Testcase, test_result: &TestResult) {
+        let title = self.styled_testcase_title(testcase);
+        match test_result {
+            TestResult::Success => println!("{} {}", self.success.paint("PASS"), title),
+            TestResult::UnableToRun { .. } | TestResult::RuntimeError { .. } => {
+                println!("{} {}", self.error.paint("ERROR"), title)
+            }
+            TestResult::WrongOutput { .. } => println!("{} {}", self.failure.paint("FAIL"), title),
+            TestResult::Timeout { .. } => println!("{} {}", self.error.paint("TIMEOUT"), title),
+        }
+    }
+
+    fn write_failure(
+        &self,
+        out: &mut impl Write,
+        testcase: &Testcase,
+        stdout: &str,
+        stderr: &str,
+        hint: Option<Hint>,
+    ) -> fmt::Result {
+        writeln!(
+            out,
+            "{}\n{}",
             self.secondary_title.paint("===== INPUT ======"),
             self.styled_testcase_input(testcase),
-            self.secondary_title.paint("==== EXPECTED ===="),
-            self.styled_testcase_output(testcase)
-        );
+        )?;
+
+        match self.diff_mode {
+            DiffMode::Inline => {
+                writeln!(
+                    out,
+                    "{}\n{}",
+                    self.secondary_title.paint("==== EXPECTED ===="),
+                    self.styled_testcase_output(testcase)
+                )?;
+                writeln!(out, "{}", &self.secondary_title.paint("===== STDOUT ====="))?;
+                self.write_diff(out, testcase, stdout)?;
+            }
+            DiffMode::SideBySide => {
+                writeln!(out, "{}", &self.secondary_title.paint("====== DIFF ======"))?;
+                self.write_side_by_side_diff(out, testcase, stdout)?;
+            }
+            DiffMode::Unified => {
+                writeln!(out, "{}", &self.secondary_title.paint("====== DIFF ======"))?;
+                self.write_unified_diff(out, testcase, stdout)?;
+            }
+        }
 
-        println!("{}", &self.secondary_title.paint("===== STDOUT ====="));
-        self.print_diff(testcase, stdout);
+        if let Some((line, column)) = first_divergence(testcase.test_out.trim_end(), stdout) {
+            let msg = format!("(first difference at line {line}, column {column})");
+            writeln!(out, "{}", self.dim_color.paint(msg))?;
+        }
+
+        if let Some(hint) = hint {
+            writeln!(out, "{} {}", self.title.paint("Hint:"), hint)?;
+        }
 
         if !stderr.is_empty() {
-            println!(
+            writeln!(
+                out,
                 "{}\n{}",
                 self.secondary_title.paint("===== STDERR ====="),
                 self.stderr.paint(stderr.trim_end())
-            );
+            )?;
         }
+        Ok(())
     }
 }
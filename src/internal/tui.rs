@@ -0,0 +1,488 @@
+use std::io::Stdout;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::Duration;
+
+use ansi_to_tui::IntoText;
+use anyhow::{Context, Result};
+use clashlib::clash::{Clash, PublicHandle, Testcase};
+use clashlib::solution::build::BuildError;
+use clashlib::solution::TestResult;
+use clashlib::workspace::{FileStorage, RunProfile, TestEvent, TestOptions, Workspace};
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Text;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+
+use super::{DiffMode, OutputStyle};
+
+/// How the tests are (re)run. The output of the commands of `workspace`
+/// should be discarded, it would draw over the UI.
+#[derive(Debug, Clone)]
+pub struct TestRun {
+    pub workspace: Workspace<FileStorage>,
+    pub profile: RunProfile,
+    pub options: TestOptions,
+}
+
+/// Why the TUI was closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuiExit {
+    Quit,
+    /// The user wants to move on to the next clash.
+    NextClash,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Statement,
+    Testcases,
+    Details,
+}
+
+/// Progress of a run of the tests, sent from the thread that runs them.
+enum RunEvent {
+    /// Result of the testcase with the given index (starting from 1).
+    Result(usize, TestResult),
+    Finished(Duration),
+    /// The solution could not be built or run at all.
+    Error(String),
+}
+
+/// Full-screen terminal UI that shows the statement and the testcases of a
+/// clash side by side and runs the solution against them.
+pub struct Tui<'a> {
+    handle: &'a PublicHandle,
+    clash: &'a Clash,
+    testcases: Vec<&'a Testcase>,
+    test_run: Option<TestRun>,
+    /// Style with whitespace styles, which are turned off when whitespace is
+    /// not shown.
+    ostyle: OutputStyle,
+    pub show_whitespace: bool,
+    pub diff_mode: DiffMode,
+    focus: Pane,
+    statement_scroll: u16,
+    details_scroll: u16,
+    selected: ListState,
+    results: Vec<Option<TestResult>>,
+    status: String,
+    run: Option<Receiver<RunEvent>>,
+}
+
+impl<'a> Tui<'a> {
+    /// Validators are left out of the testcase browser and the runs if
+    /// `spoiler_free` is set.
+    pub fn new(
+        handle: &'a PublicHandle,
+        clash: &'a Clash,
+        test_run: Option<TestRun>,
+        ostyle: OutputStyle,
+        spoiler_free: bool,
+    ) -> Self {
+        let testcases: Vec<&Testcase> = clash
+            .testcases()
+            .iter()
+            .filter(|testcase| !(spoiler_free && testcase.is_validator))
+            .collect();
+        let status = match test_run {
+            Some(_) => String::from("Press r to run the tests"),
+            None => String::from("No command to run the solution (pass --command or use `coctus start`)"),
+        };
+        Tui {
+            handle,
+            clash,
+            results: vec![None; testcases.len()],
+            testcases,
+            test_run,
+            ostyle,
+            show_whitespace: true,
+            diff_mode: DiffMode::default(),
+            focus: Pane::Testcases,
+            statement_scroll: 0,
+            details_scroll: 0,
+            selected: ListState::default().with_selected(Some(0)),
+            status,
+            run: None,
+        }
+    }
+
+    /// Shows the UI until the user quits or asks for the next clash.
+    pub fn run(&mut self) -> Result<TuiExit> {
+        let mut terminal = TerminalGuard::enter()?;
+        loop {
+            if self.receive_results() {
+                // A thread that panicked may have written over the screen
+                terminal.0.clear()?;
+            }
+            terminal.0.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(100))? {
+                continue
+            }
+            if let Event::Key(key) = event::read()? {
+                if let Some(exit) = self.handle_key(key) {
+                    return Ok(exit)
+                }
+            }
+        }
+    }
+
+    fn style(&self) -> OutputStyle {
+        let mut ostyle = self.ostyle.clone().with_whitespace(self.show_whitespace);
+        ostyle.diff_mode = self.diff_mode;
+        ostyle
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Option<TuiExit> {
+        if key.kind != KeyEventKind::Press {
+            return None
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(TuiExit::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Some(TuiExit::Quit),
+            KeyCode::Char('n') => return Some(TuiExit::NextClash),
+            KeyCode::Char('r') => self.start_run(),
+            KeyCode::Char('w') => self.show_whitespace = !self.show_whitespace,
+            KeyCode::Char('d') => {
+                self.diff_mode = match self.diff_mode {
+                    DiffMode::Inline => DiffMode::SideBySide,
+                    DiffMode::SideBySide => DiffMode::Unified,
+                    DiffMode::Unified => DiffMode::Inline,
+                }
+            }
+            KeyCode::Tab => {
+                self.focus = match self.focus {
+                    Pane::Statement => Pane::Testcases,
+                    Pane::Testcases => Pane::Details,
+                    Pane::Details => Pane::Statement,
+                }
+            }
+            KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Pane::Statement => Pane::Details,
+                    Pane::Testcases => Pane::Statement,
+                    Pane::Details => Pane::Testcases,
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(10),
+            KeyCode::PageUp => self.move_by(-10),
+            _ => (),
+        }
+        None
+    }
+
+    /// Scrolls the focused pane or moves the testcase selection.
+    fn move_by(&mut self, delta: i32) {
+        let scroll = |scroll: u16| (scroll as i32 + delta).clamp(0, u16::MAX as i32) as u16;
+        match self.focus {
+            Pane::Statement => self.statement_scroll = scroll(self.statement_scroll),
+            Pane::Details => self.details_scroll = scroll(self.details_scroll),
+            Pane::Testcases => {
+                let last = self.testcases.len().saturating_sub(1) as i32;
+                let selected = self.selected.selected().unwrap_or(0) as i32;
+                self.select((selected + delta).clamp(0, last) as usize);
+            }
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected.select(Some(index));
+        self.details_scroll = 0;
+    }
+
+    fn start_run(&mut self) {
+        let Some(test_run) = self.test_run.clone() else {
+            return
+        };
+        if self.run.is_some() {
+            return
+        }
+        let handle = self.handle.clone();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || run_tests(&test_run, &handle, &sender));
+        self.results = vec![None; self.testcases.len()];
+        self.status = String::from("Running...");
+        self.run = Some(receiver);
+    }
+
+    /// Takes the results that are ready. Returns true if the run stopped
+    /// unexpectedly.
+    fn receive_results(&mut self) -> bool {
+        let Some(receiver) = &self.run else { return false };
+        loop {
+            match receiver.try_recv() {
+                Ok(RunEvent::Result(index, test_result)) => {
+                    if let Some(position) = self.testcases.iter().position(|testcase| testcase.index == index)
+                    {
+                        self.results[position] = Some(test_result);
+                    }
+                    let num_done = self.results.iter().flatten().count();
+                    self.status = format!("Running... {}/{}", num_done, self.testcases.len());
+                }
+                Ok(RunEvent::Finished(elapsed)) => {
+                    let num_passed =
+                        self.results.iter().flatten().filter(|result| result.is_success()).count();
+                    self.status =
+                        format!("{}/{} tests passed (in {:.2?})", num_passed, self.testcases.len(), elapsed);
+                    if let Some(failed) = self.results.iter().position(|result| !is_success(result)) {
+                        self.select(failed);
+                    }
+                    self.run = None;
+                    return false
+                }
+                Ok(RunEvent::Error(msg)) => {
+                    self.status = msg;
+                    self.run = None;
+                    return false
+                }
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    self.status = String::from("The run stopped unexpectedly");
+                    self.run = None;
+                    return true
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let ostyle = self.style();
+        let [header_area, body_area, footer_area] =
+            Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+                .areas(frame.area());
+        let [statement_area, right_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(body_area);
+        let list_height = (self.testcases.len() as u16 + 2).min(right_area.height / 3).max(3);
+        let [list_area, details_area] =
+            Layout::vertical([Constraint::Length(list_height), Constraint::Min(0)]).areas(right_area);
+
+        let header = format!(
+            "{} {}",
+            ostyle.title.paint(self.clash.title()),
+            ostyle.dim_color.paint(format!("({})", self.handle))
+        );
+        frame.render_widget(Paragraph::new(ansi_text(&header)), header_area);
+
+        let statement = match self.clash.is_reverse_only() {
+            true => format!("{}{}\n", ostyle.format_headers(self.clash), ostyle.title.paint("REVERSE!")),
+            false => ostyle.format_headers(self.clash) + &ostyle.format_statement(self.clash),
+        };
+        self.statement_scroll = self.draw_text(frame, statement_area, Pane::Statement, &statement);
+
+        let items: Vec<ListItem> = self
+            .testcases
+            .iter()
+            .zip(&self.results)
+            .map(|(testcase, test_result)| {
+                let label = format!("{} {}", result_label(&ostyle, test_result), testcase.title);
+                ListItem::new(ansi_text(&label))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(self.block(Pane::Testcases, "Testcases"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.selected);
+
+        let details = self.details(&ostyle);
+        self.details_scroll = self.draw_text(frame, details_area, Pane::Details, &details);
+
+        let footer = format!(
+            "{}  {}",
+            self.status,
+            ostyle.dim_color.paint(format!(
+                "q quit · tab switch pane · ↑↓ move · r run · n next clash · w whitespace ({}) · d diff ({:?})",
+                if self.show_whitespace { "on" } else { "off" },
+                self.diff_mode
+            ))
+        );
+        frame.render_widget(Paragraph::new(ansi_text(&footer)), footer_area);
+    }
+
+    /// Draws `text` in a scrollable pane. Returns the scroll position
+    /// clamped to the length of the text.
+    fn draw_text(&self, frame: &mut Frame, area: Rect, pane: Pane, text: &str) -> u16 {
+        let text = ansi_text(text);
+        let scroll = match pane {
+            Pane::Statement => self.statement_scroll,
+            _ => self.details_scroll,
+        };
+        let scroll = scroll.min(text.lines.len().saturating_sub(1) as u16);
+        let title = match pane {
+            Pane::Statement => "Statement",
+            _ => "Details",
+        };
+        let paragraph = Paragraph::new(text)
+            .block(self.block(pane, title))
+            .wrap(Wrap { trim: false })
+            .scroll((scroll, 0));
+        frame.render_widget(paragraph, area);
+        scroll
+    }
+
+    fn block(&self, pane: Pane, title: &str) -> Block<'static> {
+        let border_style = match self.focus == pane {
+            true => Style::new().fg(Color::Yellow),
+            false => Style::new().fg(Color::DarkGray),
+        };
+        Block::bordered().title(format!(" {} ", title)).border_style(border_style)
+    }
+
+    /// The input and expected output of the selected testcase, or the diff if
+    /// the solution failed it.
+    fn details(&self, ostyle: &OutputStyle) -> String {
+        let Some(index) = self.selected.selected().filter(|&index| index < self.testcases.len()) else {
+            return String::new()
+        };
+        let testcase = self.testcases[index];
+        match &self.results[index] {
+            Some(test_result) if !test_result.is_success() => {
+                let previous = index.checked_sub(1).map(|previous| self.testcases[previous]);
+                ostyle.format_result(testcase, test_result, previous)
+            }
+            test_result => format!(
+                "{} {}\n{}\n{}\n{}\n{}\n",
+                result_label(ostyle, test_result),
+                ostyle.styled_testcase_title(testcase),
+                ostyle.secondary_title.paint("===== INPUT ======"),
+                ostyle.styled_testcase_input(testcase),
+                ostyle.secondary_title.paint("==== EXPECTED ===="),
+                ostyle.styled_testcase_output(testcase),
+            ),
+        }
+    }
+}
+
+fn is_success(test_result: &Option<TestResult>) -> bool {
+    test_result.as_ref().is_some_and(TestResult::is_success)
+}
+
+fn result_label(ostyle: &OutputStyle, test_result: &Option<TestResult>) -> String {
+    match test_result {
+        None => String::from("    "),
+        Some(TestResult::Success) => ostyle.success.paint("PASS").to_string(),
+        Some(TestResult::WrongOutput { .. }) => ostyle.failure.paint("FAIL").to_string(),
+        Some(TestResult::Timeout { .. }) => ostyle.error.paint("TIME").to_string(),
+        Some(TestResult::UnableToRun { .. } | TestResult::RuntimeError { .. }) => {
+            ostyle.error.paint("ERR ").to_string()
+        }
+    }
+}
+
+/// Converts text styled by [OutputStyle] to text that ratatui can draw.
+fn ansi_text(text: &str) -> Text<'static> {
+    text.into_text().unwrap_or_else(|_| Text::raw(text.to_string()))
+}
+
+/// Runs the tests of the clash with `handle`, sending the results to
+/// `sender` as soon as they are ready. Stops when the receiver is gone.
+fn run_tests(test_run: &TestRun, handle: &PublicHandle, sender: &Sender<RunEvent>) {
+    let TestRun {
+        workspace,
+        profile,
+        options,
+    } = test_run;
+    let summary = workspace.run_tests(handle, profile, options, |event| match event {
+        TestEvent::Build(_) => ControlFlow::Continue(()),
+        TestEvent::Result { testcase, result, .. } => {
+            match sender.send(RunEvent::Result(testcase.index, result)) {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(_) => ControlFlow::Break(()),
+            }
+        }
+    });
+    let event = match summary {
+        Ok(summary) => RunEvent::Finished(summary.elapsed),
+        Err(err @ clashlib::Error::Build(BuildError::Failure(_))) => {
+            RunEvent::Error(format!("{err} (see `coctus run` for the compiler output)"))
+        }
+        Err(err) => RunEvent::Error(format!("{:#}", anyhow::Error::from(err))),
+    };
+    let _ = sender.send(event);
+}
+
+/// Terminal in raw mode on the alternate screen, restored when dropped (also
+/// when returning early with an error).
+struct TerminalGuard(Terminal<CrosstermBackend<Stdout>>);
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode().context("The TUI needs to be run in a terminal")?;
+        let guard = TerminalGuard(Terminal::new(CrosstermBackend::new(std::io::stdout()))?);
+        ratatui::crossterm::execute!(std::io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = ratatui::crossterm::execute!(std::io::stdout(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use ratatui::backend::TestBackend;
+
+    use super::*;
+
+    fn sample_clash() -> Clash {
+        let contents = std::fs::read_to_string("fixtures/puzzles/stub_and_solution_tester.json").unwrap();
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    fn screen(tui: &mut Tui) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal.draw(|frame| tui.draw(frame)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .fold(String::new(), |screen, row| {
+                screen + &row.iter().map(|cell| cell.symbol()).collect::<String>() + "\n"
+            })
+    }
+
+    fn press(tui: &mut Tui, code: KeyCode) -> Option<TuiExit> {
+        tui.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn draws_statement_testcases_and_details() {
+        let clash = sample_clash();
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let mut tui = Tui::new(&handle, &clash, None, OutputStyle::plain(), false);
+        let screen = screen(&mut tui);
+        assert!(screen.contains("Boggus test"), "{}", screen);
+        assert!(screen.contains(clash.testcases()[0].title.as_str()), "{}", screen);
+        assert!(screen.contains("===== INPUT ======"), "{}", screen);
+        assert!(screen.contains("No command to run the solution"), "{}", screen);
+    }
+
+    #[test]
+    fn keys_move_the_selection_and_toggle_settings() {
+        let clash = sample_clash();
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let mut tui = Tui::new(&handle, &clash, None, OutputStyle::plain(), false);
+        assert_eq!(press(&mut tui, KeyCode::Up), None);
+        assert_eq!(tui.selected.selected(), Some(0));
+        press(&mut tui, KeyCode::PageDown);
+        assert_eq!(tui.selected.selected(), Some(clash.testcases().len() - 1));
+
+        press(&mut tui, KeyCode::Char('w'));
+        press(&mut tui, KeyCode::Char('d'));
+        assert!(!tui.show_whitespace);
+        assert_eq!(tui.diff_mode, DiffMode::SideBySide);
+        assert_eq!(press(&mut tui, KeyCode::Char('n')), Some(TuiExit::NextClash));
+        assert_eq!(press(&mut tui, KeyCode::Char('q')), Some(TuiExit::Quit));
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::{Mode, PublicHandle};
+use crate::error::Context;
+use crate::game::GameResult;
+use crate::{Error, Result};
+
+/// Extension of the files in a leaderboard directory. Every player appends
+/// their results to their own `{PLAYER}.jsonl` file (one JSON object per
+/// line), so there is never more than one writer per file.
+pub const LEADERBOARD_FILE_EXT: &str = "jsonl";
+
+/// `LeaderboardEntry` is a result published by a player.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub player: String,
+    #[serde(flatten)]
+    pub result: GameResult,
+}
+
+/// Checks that `player` can be used as a file name in the leaderboard
+/// directory.
+pub fn validate_player_name(player: &str) -> Result<()> {
+    let is_valid_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_' || c == '.';
+    if player.is_empty() || player.starts_with('.') || !player.chars().all(is_valid_char) {
+        return Err(Error::InvalidInput(format!(
+            "Invalid player name {:?} (use letters, digits, '-', '_' and '.')",
+            player
+        )))
+    }
+    Ok(())
+}
+
+/// Appends `result` to the file of `player` in the leaderboard directory.
+/// The entry is written with a single call so that readers never see an
+/// interleaved line. If the last line of the file is incomplete (because a
+/// write was interrupted), the entry starts on a new line.
+pub fn publish(leaderboard_dir: &Path, player: &str, result: &GameResult) -> Result<()> {
+    validate_player_name(player)?;
+    std::fs::create_dir_all(leaderboard_dir)
+        .with_context(|| format!("Unable to create leaderboard directory {:?}", leaderboard_dir))?;
+
+    let entry = LeaderboardEntry {
+        player: player.to_string(),
+        result: result.clone(),
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|err| Error::serialize("leaderboard entry", err))?;
+    line.push('\n');
+
+    let path = leaderboard_dir.join(format!("{player}.{LEADERBOARD_FILE_EXT}"));
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Unable to open {:?}", path))?;
+    if !ends_with_newline(&mut file).with_context(|| format!("Unable to read {:?}", path))? {
+        line.insert(0, '\n');
+    }
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Unable to write {:?}", path))
+}
+
+/// Returns true if `file` is empty or ends with a newline.
+fn ends_with_newline(file: &mut std::fs::File) -> std::io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true)
+    }
+    let mut last_byte = [0];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last_byte)?;
+    Ok(last_byte[0] == b'\n')
+}
+
+/// Reads the entries published by all players in `leaderboard_dir`. Lines
+/// that can not be parsed (for example the last line of a file that is being
+/// written to) are skipped.
+pub fn read_entries(leaderboard_dir: &Path) -> Result<Vec<LeaderboardEntry>> {
+    let mut entries = Vec::new();
+
+    let dir_entries = std::fs::read_dir(leaderboard_dir)
+        .with_context(|| format!("Unable to read leaderboard directory {:?}", leaderboard_dir))?;
+
+    for dir_entry in dir_entries {
+        let path = dir_entry
+            .with_context(|| format!("Unable to read leaderboard directory {:?}", leaderboard_dir))?
+            .path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(LEADERBOARD_FILE_EXT) {
+            continue
+        }
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue
+        };
+        entries.extend(contents.lines().filter_map(|line| serde_json::from_str(line).ok()));
+    }
+
+    Ok(entries)
+}
+
+/// Keeps the best entry of every player for each clash and mode. The result
+/// is sorted by handle and mode, then by rank.
+pub fn best_entries(entries: Vec<LeaderboardEntry>) -> Vec<LeaderboardEntry> {
+    let mut best: BTreeMap<(String, Mode, String), LeaderboardEntry> = BTreeMap::new();
+
+    for entry in entries {
+        let key = (entry.result.handle.to_string(), entry.result.mode, entry.player.clone());
+        match best.get(&key) {
+            Some(current) if !entry.result.is_better_than(&current.result) => {}
+            _ => {
+                best.insert(key, entry);
+            }
+        }
+    }
+
+    let mut best: Vec<LeaderboardEntry> = best.into_values().collect();
+    best.sort_by(|a, b| {
+        (a.result.handle.to_string(), a.result.mode)
+            .cmp(&(b.result.handle.to_string(), b.result.mode))
+            .then_with(|| a.result.cmp_ranking(&b.result))
+            .then_with(|| a.player.cmp(&b.player))
+    });
+    best
+}
+
+/// `PlayerStats` summarizes the results of a player over all clashes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlayerStats {
+    /// Number of clashes (and modes) the player published a result for.
+    pub played: usize,
+    /// Number of clashes (and modes) the player scored 100% in.
+    pub solved: usize,
+    /// Number of clashes (and modes) where nobody has a better result.
+    pub first_places: usize,
+}
+
+/// Aggregates the best entries of every player into per-player statistics.
+pub fn player_stats(best_entries: &[LeaderboardEntry]) -> BTreeMap<String, PlayerStats> {
+    let mut stats: BTreeMap<String, PlayerStats> = BTreeMap::new();
+
+    for entry in best_entries {
+        let is_first = !best_entries.iter().any(|other| {
+            other.result.handle == entry.result.handle
+                && other.result.mode == entry.result.mode
+                && other.result.is_better_than(&entry.result)
+        });
+        let player_stats = stats.entry(entry.player.clone()).or_default();
+        player_stats.played += 1;
+        player_stats.solved += usize::from(entry.result.score == 100);
+        player_stats.first_places += usize::from(is_first);
+    }
+
+    stats
+}
+
+/// Filters entries to the ones for the clash with `handle`.
+pub fn entries_for_handle(entries: Vec<LeaderboardEntry>, handle: &PublicHandle) -> Vec<LeaderboardEntry> {
+    entries.into_iter().filter(|entry| &entry.result.handle == handle).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn leaderboard_dir() -> PathBuf {
+        ["fixtures", "leaderboard"].iter().collect()
+    }
+
+    #[test]
+    fn test_read_entries_skips_invalid_lines() {
+        let entries = read_entries(&leaderboard_dir()).unwrap();
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn test_best_entries() {
+        let best = best_entries(read_entries(&leaderboard_dir()).unwrap());
+        let summary: Vec<(&str, Mode, u32)> =
+            best.iter().map(|e| (e.player.as_str(), e.result.mode, e.result.score)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("bob", Mode::Fastest, 100),
+                ("alice", Mode::Fastest, 100),
+                ("alice", Mode::Shortest, 100),
+                ("bob", Mode::Shortest, 50),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_player_stats() {
+        let stats = player_stats(&best_entries(read_entries(&leaderboard_dir()).unwrap()));
+        let alice = PlayerStats {
+            played: 2,
+            solved: 2,
+            first_places: 1,
+        };
+        let bob = PlayerStats {
+            played: 2,
+            solved: 1,
+            first_places: 1,
+        };
+        assert_eq!(stats["alice"], alice);
+        assert_eq!(stats["bob"], bob);
+    }
+
+    #[test]
+    fn test_entries_for_handle() {
+        let entries = read_entries(&leaderboard_dir()).unwrap();
+        let handle = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap();
+        assert!(entries_for_handle(entries, &handle).is_empty());
+    }
+
+    #[test]
+    fn test_publish_after_truncated_line() {
+        let dir = std::env::temp_dir().join(format!("coctus-leaderboard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy(leaderboard_dir().join("alice.jsonl"), dir.join("alice.jsonl")).unwrap();
+        let num_entries = read_entries(&dir).unwrap().len();
+
+        let result = GameResult {
+            handle: PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap(),
+            mode: Mode::Reverse,
+            score: 75,
+            time: 120,
+            length: None,
+            submitted_at: 1700003600,
+        };
+        publish(&dir, "alice", &result).unwrap();
+        publish(&dir, "alice", &result).unwrap();
+        let entries = read_entries(&dir).unwrap();
+        assert_eq!(entries.len(), num_entries + 2);
+        assert!(entries.iter().all(|entry| entry.player == "alice"));
+        assert_eq!(entries_for_handle(entries, &result.handle).len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_player_name() {
+        assert!(validate_player_name("alice").is_ok());
+        assert!(validate_player_name("j.doe-2").is_ok());
+        assert!(validate_player_name("").is_err());
+        assert!(validate_player_name("../bob").is_err());
+        assert!(validate_player_name(".hidden").is_err());
+    }
+}
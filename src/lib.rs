@@ -1,6 +1,18 @@
 pub mod clash;
+pub mod error;
+pub mod game;
+pub mod history;
+pub mod hooks;
+pub mod leaderboard;
+pub mod library;
+pub mod metadata;
+pub mod navigation;
+pub mod selection;
 pub mod solution;
 pub mod stub;
+pub mod workspace;
+
+pub use error::{Error, Result};
 
 #[cfg(test)]
 pub mod test_helper;
@@ -0,0 +1,256 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// Name of the library in the data directory, which always exists.
+pub const MAIN_LIBRARY: &str = "main";
+
+/// `Libraries` is the registry of clash libraries: named directories that
+/// contain clashes as `HANDLE.json` files. One of them is the default
+/// library, which is used when no library is selected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Libraries {
+    /// Directory of the main library.
+    #[serde(skip)]
+    main_dir: PathBuf,
+    default: Option<String>,
+    libraries: BTreeMap<String, PathBuf>,
+}
+
+impl Libraries {
+    /// A registry with only the main library, stored in `main_dir`.
+    pub fn new(main_dir: &Path) -> Self {
+        Libraries {
+            main_dir: main_dir.to_path_buf(),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the registry from `path`. A missing file is a registry with only
+    /// the main library, stored in `main_dir`.
+    pub fn read_from_file(path: &Path, main_dir: &Path) -> Result<Self> {
+        let mut libraries: Libraries = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Unable to deserialize {:?}", path))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Libraries::new(main_dir)),
+            Err(err) => return Err(err).with_context(|| format!("Unable to read {:?}", path)),
+        };
+        libraries.main_dir = main_dir.to_path_buf();
+        Ok(libraries)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).map_err(|err| Error::serialize("libraries", err))?;
+        std::fs::write(path, contents).with_context(|| format!("Unable to write {:?}", path))
+    }
+
+    /// Registers the library `name` stored in `dir`.
+    pub fn add(&mut self, name: &str, dir: PathBuf) -> Result<()> {
+        self.check_new_name(name)?;
+        self.libraries.insert(name.to_string(), dir);
+        Ok(())
+    }
+
+    /// Registers the library `name` stored in `dir` by its absolute path,
+    /// creating the directory if it does not exist yet.
+    pub fn create(&mut self, name: &str, dir: &Path) -> Result<()> {
+        self.check_new_name(name)?;
+        std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {:?}", dir))?;
+        let dir = dir.canonicalize().with_context(|| format!("Unable to find {:?}", dir))?;
+        self.add(name, dir)
+    }
+
+    fn check_new_name(&self, name: &str) -> Result<()> {
+        if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c == '/') {
+            return Err(Error::InvalidInput(format!(
+                "Invalid library name {:?} (names can not be empty or contain spaces or slashes)",
+                name
+            )))
+        }
+        if self.contains(name) {
+            return Err(Error::InvalidInput(format!("There already is a library named {:?}", name)))
+        }
+        Ok(())
+    }
+
+    /// Unregisters the library `name` (its clashes are left alone) and
+    /// returns its directory.
+    pub fn forget(&mut self, name: &str) -> Result<PathBuf> {
+        if name == MAIN_LIBRARY {
+            return Err(Error::InvalidInput(format!("The {} library can not be removed", MAIN_LIBRARY)))
+        }
+        let dir = self.libraries.remove(name).ok_or_else(|| unknown_library(name))?;
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+        Ok(dir)
+    }
+
+    pub fn set_default(&mut self, name: &str) -> Result<()> {
+        if !self.contains(name) {
+            return Err(unknown_library(name))
+        }
+        self.default = Some(name.to_string()).filter(|name| name != MAIN_LIBRARY);
+        Ok(())
+    }
+
+    /// Name of the default library.
+    pub fn default_name(&self) -> &str {
+        self.default.as_deref().unwrap_or(MAIN_LIBRARY)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        name == MAIN_LIBRARY || self.libraries.contains_key(name)
+    }
+
+    /// Directory of the library `name`.
+    pub fn dir(&self, name: &str) -> Result<&Path> {
+        match name {
+            MAIN_LIBRARY => Ok(&self.main_dir),
+            _ => self.libraries.get(name).map(PathBuf::as_path).ok_or_else(|| unknown_library(name)),
+        }
+    }
+
+    /// Directory of the library `to`, which the clashes of the library `from`
+    /// are copied or moved to.
+    pub fn target_dir(&self, from: &str, to: &str) -> Result<&Path> {
+        if from == to {
+            return Err(Error::InvalidInput(format!("The clashes are already in the {to} library")))
+        }
+        self.dir(to)
+    }
+
+    /// All libraries (name and directory), the main library first.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Path)> {
+        std::iter::once((MAIN_LIBRARY, self.main_dir.as_path()))
+            .chain(self.libraries.iter().map(|(name, dir)| (name.as_str(), dir.as_path())))
+    }
+}
+
+fn unknown_library(name: &str) -> Error {
+    Error::NotFound(format!("Unknown library {:?} (see `coctus library list`)", name))
+}
+
+/// Path of the file of the clash with `handle` in the library stored in `dir`.
+pub fn clash_file(dir: &Path, handle: &PublicHandle) -> PathBuf {
+    dir.join(format!("{}.json", handle))
+}
+
+/// Handles of the clashes in the library stored in `dir`, sorted.
+pub fn stored_handles(dir: &Path) -> Result<Vec<PublicHandle>> {
+    let mut handles = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("No clashes stored in {:?}", dir))? {
+        let file_name = entry.with_context(|| format!("Unable to read {:?}", dir))?.file_name();
+        let handle = file_name
+            .to_str()
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|name| PublicHandle::from_str(name).ok());
+        handles.extend(handle);
+    }
+    handles.sort_by_key(PublicHandle::to_string);
+    Ok(handles)
+}
+
+/// Copies the clash with `handle` from the library in `from` to the library
+/// in `to`. Fails if `to` already has the clash.
+pub fn copy_clash(from: &Path, to: &Path, handle: &PublicHandle) -> Result<()> {
+    let source = clash_file(from, handle);
+    let target = clash_file(to, handle);
+    if !source.is_file() {
+        return Err(Error::NotFound(format!("Clash {} is not stored in {:?}", handle, from)))
+    }
+    if target.exists() {
+        return Err(Error::InvalidInput(format!("Clash {} is already stored in {:?}", handle, to)))
+    }
+    std::fs::create_dir_all(to).with_context(|| format!("Unable to create {:?}", to))?;
+    std::fs::copy(&source, &target)
+        .with_context(|| format!("Unable to copy {:?} to {:?}", source, target))?;
+    Ok(())
+}
+
+/// Moves the clash with `handle` from the library in `from` to the library
+/// in `to`. Fails if `to` already has the clash.
+pub fn move_clash(from: &Path, to: &Path, handle: &PublicHandle) -> Result<()> {
+    copy_clash(from, to, handle)?;
+    remove_clash(from, handle)
+}
+
+/// Removes the clash with `handle` from the library in `dir`.
+pub fn remove_clash(dir: &Path, handle: &PublicHandle) -> Result<()> {
+    let path = clash_file(dir, handle);
+    std::fs::remove_file(&path).with_context(|| format!("Unable to remove {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn libraries() -> Libraries {
+        Libraries {
+            main_dir: PathBuf::from("main"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_library() {
+        let mut libraries = libraries();
+        assert_eq!(libraries.default_name(), MAIN_LIBRARY);
+        libraries.add("team", PathBuf::from("team")).unwrap();
+        libraries.set_default("team").unwrap();
+        assert_eq!(libraries.default_name(), "team");
+
+        // Forgetting the default library makes main the default again
+        assert_eq!(libraries.forget("team").unwrap(), PathBuf::from("team"));
+        assert_eq!(libraries.default_name(), MAIN_LIBRARY);
+        assert!(libraries.set_default("team").is_err());
+    }
+
+    #[test]
+    fn test_add_and_forget() {
+        let mut libraries = libraries();
+        assert!(libraries.add(MAIN_LIBRARY, PathBuf::from("elsewhere")).is_err());
+        assert!(libraries.add("two words", PathBuf::from("x")).is_err());
+        assert!(libraries.forget(MAIN_LIBRARY).is_err());
+        assert!(libraries.forget("team").is_err());
+
+        libraries.add("team", PathBuf::from("team")).unwrap();
+        assert!(libraries.add("team", PathBuf::from("other")).is_err());
+        let names: Vec<&str> = libraries.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, [MAIN_LIBRARY, "team"]);
+        assert_eq!(libraries.dir("team").unwrap(), Path::new("team"));
+        assert_eq!(libraries.dir(MAIN_LIBRARY).unwrap(), Path::new("main"));
+    }
+
+    #[test]
+    fn test_create_and_target_dir() {
+        let dir = std::env::temp_dir().join(format!("coctus-library-create-{}", std::process::id()));
+        let mut libraries = libraries();
+        assert!(libraries.create("two words", &dir).is_err());
+        assert!(!dir.exists());
+        libraries.create("team", &dir.join("team")).unwrap();
+        assert!(libraries.dir("team").unwrap().is_absolute());
+        assert!(libraries.dir("team").unwrap().is_dir());
+
+        assert!(libraries.target_dir("team", "team").is_err());
+        assert!(libraries.target_dir("team", "nope").is_err());
+        assert_eq!(libraries.target_dir("team", MAIN_LIBRARY).unwrap(), Path::new("main"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stored_handles() {
+        let dir: PathBuf = ["fixtures", "library"].iter().collect();
+        let handles: Vec<String> =
+            stored_handles(&dir).unwrap().iter().map(PublicHandle::to_string).collect();
+        assert_eq!(handles, ["682102420fbce0fce95e0ee56095ea2b9924"]);
+    }
+}
@@ -1,35 +1,107 @@
 mod internal;
 
-use std::io::Read;
+use std::io::{BufRead, Read, Write};
+use std::ops::ControlFlow;
 use std::path::PathBuf;
-use std::process::Command;
 use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use clap::ArgMatches;
-use clashlib::clash::{Clash, PublicHandle, Testcase};
-use clashlib::stub::StubConfig;
-use clashlib::{solution, stub};
+use clashlib::clash::{Clash, HandleQuery, Mode, PublicHandle, PuzzleType, Testcase};
+use clashlib::game::{self, PracticeSession};
+use clashlib::library::{self, Libraries};
+use clashlib::metadata::{self, ClashMetadata};
+use clashlib::selection::{ClashFilter, Strategy};
+use clashlib::solution::saved::{self, SavedSolution};
+use clashlib::solution::CommandOutput;
+use clashlib::workspace::{
+    self, BuildStatus, FileStorage, PracticeSubmission, RunProfile, Storage, TestEvent, TestOptions,
+    Verification, Workspace, WorkspaceFile,
+};
+use clashlib::{leaderboard, stub};
 use directories::ProjectDirs;
-use internal::OutputStyle;
+use internal::jsonrpc::{parse_params, Connection, RpcError};
+use internal::{
+    add_dynamic_completion, completion_target, ColorChoice, CompletionTarget, Config, DiffMode, Doctor,
+    LanguageServer, OutputStyle, SettingSource, TestRun, Tui, TuiExit, COMPLETE_COMMAND, CONFIG_FILE_NAME,
+    SETTINGS,
+};
 use rand::seq::IteratorRandom;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
-fn command_from_argument(cmd_arg: Option<&String>) -> Result<Option<Command>> {
-    let cmd = match cmd_arg {
-        Some(cmd) => cmd,
-        None => return Ok(None),
-    };
+fn timeout_from_argument(timeout_arg: Option<&f64>) -> Result<Duration> {
+    match timeout_arg.copied() {
+        None => Ok(Duration::MAX),
+        Some(secs) if secs.is_nan() => Err(anyhow!("Timeout can't be NaN")),
+        Some(secs) if secs < 0.0 => Err(anyhow!("Timeout can't be negative (use 0 for no timeout)")),
+        Some(0.0) => Ok(Duration::MAX),
+        Some(secs) => Ok(Duration::from_micros((secs * 1e6) as u64)),
+    }
+}
+
+/// Prints an error that does not stop the command.
+fn print_warning(err: clashlib::Error) {
+    eprintln!("WARNING: {:#}", anyhow::Error::from(err));
+}
+
+/// Prints whether the solution was built. `out` is STDOUT unless it is
+/// reserved for something else.
+fn print_build_status(out: &mut dyn Write, status: BuildStatus) -> Result<()> {
+    match status {
+        BuildStatus::NotNeeded => (),
+        BuildStatus::Skipped => writeln!(out, "Build skipped (sources unchanged)")?,
+        BuildStatus::Built(elapsed) => writeln!(out, "Build finished (in {:.2?})", elapsed)?,
+    }
+    Ok(())
+}
 
-    match shlex::split(cmd) {
-        Some(shlexed_cmd) if shlexed_cmd.is_empty() => Ok(None),
-        Some(shlexed_cmd) => {
-            let exe = &shlexed_cmd[0];
-            let exe_args = &shlexed_cmd[1..];
-            let mut cmd = Command::new(exe);
-            cmd.args(exe_args);
-            Ok(Some(cmd))
+/// Prints the ERROR row of a saved solution that could not be verified.
+fn print_solution_error(ostyle: &OutputStyle, handle: &PublicHandle, language: &str, err: clashlib::Error) {
+    println!("{} {handle} ({language})", ostyle.error.paint("ERROR"));
+    println!(" {}", ostyle.stderr.paint(format!("{:#}", anyhow::Error::from(err))));
+}
+
+/// Prints the PASS or FAIL row of a verified saved solution and its failed
+/// testcases (with their results if `verbose`).
+fn print_verification(
+    ostyle: &OutputStyle,
+    saved_solution: &SavedSolution,
+    verification: &Verification,
+    verbose: bool,
+) -> Result<()> {
+    print_build_status(&mut std::io::stdout(), verification.build)?;
+    let status = match verification.all_passed() {
+        true => ostyle.success.paint("PASS"),
+        false => ostyle.failure.paint("FAIL"),
+    };
+    println!(
+        "{status} {} ({}) {}/{} {}",
+        saved_solution.handle,
+        saved_solution.language,
+        verification.passed,
+        verification.total,
+        verification.clash.title()
+    );
+    for (testcase, test_result) in &verification.failures {
+        if verbose {
+            let previous = verification.clash.testcases().get(testcase.index.wrapping_sub(2));
+            ostyle.print_result(testcase, test_result, previous);
+        } else {
+            println!(" {}", ostyle.styled_testcase_title(testcase));
         }
-        _ => Err(anyhow!("Invalid COMMAND")),
+    }
+    Ok(())
+}
+
+/// Formats a number of seconds as a short relative time, e.g. `5m ago`.
+fn format_time_ago(secs: u64) -> String {
+    match secs {
+        0..=59 => String::from("just now"),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
     }
 }
 
@@ -41,19 +113,26 @@ fn cli() -> clap::Command {
         .version(clap::crate_version!())
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .after_help(
+            "Wherever a PUBLIC_HANDLE is expected, a CodinGame contribution URL or a unique prefix \
+            (at least 4 characters) of the handle of a stored clash can be given instead."
+        )
+        .arg(
+            arg!(--"library" <LIBRARY> "library of clashes to use instead of the default one (see `coctus library`)")
+                .global(true)
+        )
         .subcommand(
             Command::new("show")
                 .about("Show clash")
                 .arg(
-                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces")
+                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces (default: true or from config)")
                         // This means show-whitespace=1 also works
                         .value_parser(clap::builder::BoolishValueParser::new())
-                        .default_value("true")
                         .default_missing_value("true")
                 )
                 .arg(
                     arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
-                        .value_parser(value_parser!(PublicHandle))
+                        .value_parser(value_parser!(HandleQuery))
                 )
                 .arg(arg!(-'r' --"reverse" "print the clash in reverse mode"))
         )
@@ -62,64 +141,537 @@ fn cli() -> clap::Command {
                 .about("Select next clash")
                 .arg(
                     arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
-                        .value_parser(value_parser!(PublicHandle))
+                        .value_parser(value_parser!(HandleQuery))
                         .exclusive(true)
                 )
                 .arg(arg!(-'r' --"reverse" "pick a random clash that has reverse mode"))
                 .arg(arg!(-'s' --"shortest" "pick a random clash that has shortest mode"))
                 .arg(arg!(-'f' --"fastest" "pick a random clash that has fastest mode"))
+                .arg(
+                    arg!(-'t' --"type" <TYPE> "only pick puzzles of this type: clash or classic")
+                        .value_parser(value_parser!(PuzzleType))
+                )
+                .arg(
+                    arg!(--"tag" <TAG> "only pick clashes with this tag (can be repeated)")
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(arg!(--"favorite" "only pick favorite clashes"))
+                .arg(
+                    arg!(--"strategy" <STRATEGY> "how to pick the clash: uniform, weighted, unseen, retry-failed or daily (default: uniform or from config)")
+                        .value_parser(value_parser!(Strategy))
+                )
                 .after_help(
                     "Pick a random clash from locally stored clashes when PUBLIC_HANDLE is not given.\
                     \nIf instead flags modes are supplied, it will look for a clash that has at least all of those modes available.\
-                    \nFor example: coctus next --fastest --shortest will return a clash that has BOTH fastest and shortest as options."
+                    \nFor example: coctus next --fastest --shortest will return a clash that has BOTH fastest and shortest as options.\
+                    \nHidden clashes are never picked (see `coctus hide`).\
+                    \nStrategies:\
+                    \n  uniform       every clash is equally likely\
+                    \n  weighted      clashes with a better upvote/downvote ratio are more likely\
+                    \n  unseen        clashes that were never run first, then the ones run the longest time ago\
+                    \n  retry-failed  clashes that were failed, again after 1, 3 and 7 days until passed 4 times in a row\
+                    \n  daily         the same clash for the whole day (given the same stored clashes)"
+                )
+        )
+        .subcommand(
+            Command::new("clash")
+                .about("Start a timed practice clash")
+                .arg(
+                    arg!(-'m' --"mode" <MODE> "game mode: fastest, shortest or reverse (default: random)")
+                        .value_parser(value_parser!(Mode))
+                )
+                .arg(
+                    arg!(--"minutes" <MINUTES>)
+                        .help(format!(
+                            "time limit in minutes (default: {})",
+                            game::DEFAULT_TIME_LIMIT.as_secs() / 60
+                        ))
+                        .value_parser(value_parser!(u64).range(1..))
+                )
+                .arg(arg!(--"stop" "abandon the current practice clash").exclusive(true))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Picks a random clash (that has the requested --mode) and starts a countdown that is shown by `coctus run`.\
+                    \nOnce all testcases pass, the solution is submitted: it is run against all testcases including validators \
+                    and scored like on CodinGame (percentage of passed testcases, then time or code length in shortest mode). \
+                    Code length is measured from the --source files given to `coctus run`.\
+                    \nThe result is stored in the data directory so later attempts can be compared against it."
+                )
+        )
+        .subcommand(
+            Command::new("leaderboard")
+                .about("Show the team leaderboard")
+                .arg(arg!(-'m' --"mode" <MODE> "only show results in this mode").value_parser(value_parser!(Mode)))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "show the ranking of the clash with this handle")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Results of practice clashes are published to the leaderboard-dir set in the config file \
+                    (for example a directory on a shared network filesystem) under the configured player-name. \
+                    Every player only appends to their own file, so no server or locking is needed.\
+                    \nWithout a PUBLIC_HANDLE the number of first places and solved clashes of every player is shown."
+                )
+        )
+        .subcommand(
+            Command::new("hotseat")
+                .about("Compete with several players on the same clash")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("start")
+                        .about("Start a clash for the given players")
+                        .arg(
+                            arg!(-'p' --"player" <NAME> "name of a player (repeat for each player)")
+                                .action(clap::ArgAction::Append)
+                                .required(true)
+                        )
+                        .arg(
+                            arg!(-'m' --"mode" <MODE> "game mode: fastest, shortest or reverse (default: random)")
+                                .value_parser(value_parser!(Mode))
+                        )
+                        .arg(
+                            arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
+                                .value_parser(value_parser!(HandleQuery))
+                        )
+                )
+                .subcommand(
+                    Command::new("submit")
+                        .about("Submit the solution of a player")
+                        .arg(arg!(<PLAYER> "name of the player"))
+                        .arg(arg!(<FILE> "source file of the solution").value_parser(value_parser!(PathBuf)))
+                        .arg(arg!(--"command" <COMMAND> "command that executes the solution").required(true))
+                        .arg(arg!(--"build-command" <COMMAND> "command that compiles the solution"))
+                        .arg(
+                            arg!(--"build-timeout" <SECONDS> "how many seconds before the build is timed out (0 for no timeout, default: 30 or from config)")
+                                .value_parser(value_parser!(f64))
+                        )
+                        .arg(
+                            arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout, default: 5 or from config)")
+                                .value_parser(value_parser!(f64))
+                        )
+                )
+                .subcommand(Command::new("results").about("Show the ranking of the current hot-seat clash"))
+                .after_help(
+                    "Submitted solutions are run against all testcases (including validators) and ranked like on CodinGame: \
+                    by score, then by the number of characters in FILE in shortest mode or by the time since the start otherwise. \
+                    A player can submit again to replace their previous submission.\
+                    \nIMPORTANT: The commands you provide will be executed without any sandboxing. Only run code you trust!"
                 )
         )
         .subcommand(
             Command::new("run")
                 .about("Test a solution against current clash")
                 .arg(arg!(--"build-command" <COMMAND> "command that compiles the solution"))
-                .arg(arg!(--"command" <COMMAND> "command that executes the solution").required(true))
+                .arg(arg!(--"command" <COMMAND> "command that executes the solution (default: from the workspace file)"))
+                .arg(
+                    arg!(--"source" <FILE> "source file of the solution (can be given multiple times)")
+                        .value_parser(value_parser!(PathBuf))
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(
+                    arg!(--"build-timeout" <SECONDS> "how many seconds before the build is timed out (0 for no timeout, default: 30 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(arg!(--"rebuild" "run the --build-command even if the sources have not changed"))
                 .arg(
-                    arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout)")
+                    arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout, default: 5 or from config)")
                         .value_parser(value_parser!(f64))
-                        .default_value("5")
                 )
                 .arg(arg!(--"auto-advance" "automatically move on to next clash if all testcases pass"))
                 .arg(arg!(--"ignore-failures" "run all tests despite failures"))
+                .arg(
+                    arg!(--"spoiler-free" [BOOL] "hide validators like the CodinGame IDE does (default: from config)")
+                        .value_parser(clap::builder::BoolishValueParser::new())
+                        .default_missing_value("true")
+                )
+                .arg(arg!(--"validators" "in spoiler-free mode, also run the validators without revealing them"))
+                .arg(
+                    arg!(--"diff" <MODE> "how to show the difference between expected and actual output")
+                        .value_parser(value_parser!(DiffMode))
+                        .default_value("inline")
+                )
                 .arg(
                     arg!(--"testcases" <TESTCASE_INDICES> "indices of the testcases to run (separated by commas)")
                         .value_parser(value_parser!(u64).range(1..99))
                         .value_delimiter(',')
                 )
                 .arg(
-                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces")
+                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces (default: true or from config)")
                         // This means show-whitespace=1 also works
                         .value_parser(clap::builder::BoolishValueParser::new())
-                        .default_value("true")
                         .default_missing_value("true")
                 )
                 .arg(
                     arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
-                        .value_parser(value_parser!(PublicHandle))
+                        .value_parser(value_parser!(HandleQuery))
                 )
                 .after_help(
                     "If a --build-command is specified, it will be executed once before running any of the testcases. \
-                    The --command is required and will be executed once per testcase.\
+                    When the --source files of the solution are given, the build is skipped if none of them changed since the last successful build and the files it built are still there. \
+                    The --command (or the command of the workspace, see `coctus init`) will be executed once per testcase.\
+                    \nIn spoiler-free mode only the testcases that are visible on CodinGame are run. \
+                    With --validators the validators are run after them, but only whether they passed is shown \
+                    (just like submitting a solution on CodinGame).\
+                    \nIMPORTANT: The commands you provide will be executed without any sandboxing. Only run code you trust!"
+                )
+        )
+        .subcommand(
+            Command::new("tui")
+                .about("Show the statement, testcases and test results of a clash in a full-screen terminal UI")
+                .arg(arg!(--"build-command" <COMMAND> "command that compiles the solution"))
+                .arg(arg!(--"command" <COMMAND> "command that executes the solution (default: from the workspace file)"))
+                .arg(
+                    arg!(--"source" <FILE> "source file of the solution (can be given multiple times)")
+                        .value_parser(value_parser!(PathBuf))
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(
+                    arg!(--"build-timeout" <SECONDS> "how many seconds before the build is timed out (0 for no timeout, default: 30 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(
+                    arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout, default: 5 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(
+                    arg!(--"spoiler-free" [BOOL] "hide validators like the CodinGame IDE does (default: from config)")
+                        .value_parser(clap::builder::BoolishValueParser::new())
+                        .default_missing_value("true")
+                )
+                .arg(
+                    arg!(--"diff" <MODE> "how to show the difference between expected and actual output")
+                        .value_parser(value_parser!(DiffMode))
+                        .default_value("inline")
+                )
+                .arg(
+                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces (default: true or from config)")
+                        .value_parser(clap::builder::BoolishValueParser::new())
+                        .default_missing_value("true")
+                )
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Keys: r runs the tests, n moves on to the next clash, w toggles showing whitespace, \
+                    d switches between the diff modes, tab switches between the panes, arrow keys move and q quits.\
+                    \nThe solution is built and run like with `coctus run` (without skipping unchanged builds).\
                     \nIMPORTANT: The commands you provide will be executed without any sandboxing. Only run code you trust!"
                 )
         )
+        .subcommand(
+            Command::new("verify")
+                .about("Test all saved solutions against their clashes")
+                .arg(arg!(--"lang" <LANGUAGE> "only verify solutions in this language"))
+                .arg(
+                    arg!(--"build-timeout" <SECONDS> "how many seconds before a build is timed out (0 for no timeout, default: 30 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(
+                    arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout, default: 5 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(arg!(-'v' --"verbose" "print input, expected output and diff of failed testcases"))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "only verify solutions to the clash with this handle")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Runs every saved solution against all testcases (including validators) of its clash \
+                    and exits with a non-zero status if any of them fails.\
+                    \nSaved solutions are stored in the data directory as solutions/HANDLE/LANGUAGE/solution.toml \
+                    along with the source file. Their commands are executed inside that directory and \
+                    {file} is replaced with the path of the source file.\
+                    \nIMPORTANT: The commands will be executed without any sandboxing. Only run code you trust!"
+                )
+        )
+        .subcommand(
+            Command::new("save")
+                .about("Save a solution to the current clash")
+                .arg(arg!(<FILE> "source file of the solution").value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--"lang" <LANGUAGE> "programming language of the solution (default: guessed from the file extension)"))
+                .arg(arg!(--"command" <COMMAND> "command that executes the solution"))
+                .arg(arg!(--"build-command" <COMMAND> "command that compiles the solution"))
+                .arg(
+                    arg!(--"build-timeout" <SECONDS> "how many seconds before the build is timed out (0 for no timeout, default: 30 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(
+                    arg!(--"timeout" <SECONDS> "how many seconds before execution is timed out (0 for no timeout, default: 5 or from config)")
+                        .value_parser(value_parser!(f64))
+                )
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Copies FILE into the solutions store of the data directory, replacing the previously saved solution \
+                    to the same clash in the same language. The solution is tested against all testcases (including validators) \
+                    and the result is recorded. If it can not be built, the previously saved solution is kept.\
+                    \nWithout --command, the commands saved before are used, or else the run_command and build_command \
+                    of the language's stub_config.toml.\
+                    \nThe commands are executed inside the directory of the saved solution. \
+                    {file} is replaced with the path of the saved source file."
+                )
+        )
+        .subcommand(
+            Command::new("solutions")
+                .about("List saved solutions")
+                .arg(
+                    arg!([PUBLIC_HANDLE] "only list solutions to the clash with this handle")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+        )
+        .subcommand(
+            Command::new("checkout")
+                .about("Copy a saved solution into the current directory and select its clash")
+                .arg(
+                    arg!(<PUBLIC_HANDLE> "hexadecimal handle of the clash")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .arg(arg!(<LANGUAGE> "programming language of the solution"))
+                .arg(
+                    arg!(-'o' --"output" <FILE> "where to write the solution (default: original file name)")
+                        .value_parser(value_parser!(PathBuf))
+                )
+                .arg(arg!(-'f' --"force" "overwrite the output file if it already exists"))
+        )
+        .subcommand(
+            Command::new("prev")
+                .about("Go back to a previously selected clash")
+                .arg(
+                    arg!([STEPS] "how many clashes to go back (see `coctus history --nav`)")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("1")
+                )
+                .after_help(
+                    "Every clash appears only once in the navigation history, \
+                    so running `coctus prev` twice returns to the clash you started from."
+                )
+        )
+        .subcommand(
+            Command::new("history")
+                .about("Show recently run clashes")
+                .arg(arg!(--"nav" "show previously selected clashes instead"))
+                .arg(
+                    arg!(-'n' --"limit" <NUMBER> "how many entries to show")
+                        .value_parser(value_parser!(usize))
+                        .default_value("20")
+                )
+        )
+        .subcommand(
+            Command::new("bookmark")
+                .about("Bookmark a clash or list bookmarks")
+                .arg(arg!([NAME] "name of the bookmark (default or `list`: list bookmarks)"))
+                .arg(arg!(--"remove" "remove the bookmark").requires("NAME"))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                        .conflicts_with("remove")
+                )
+        )
+        .subcommand(
+            Command::new("goto")
+                .about("Select a bookmarked clash")
+                .arg(arg!(<NAME> "name of the bookmark"))
+        )
+        .subcommand(
+            Command::new("note")
+                .about("Show or edit the notes of a clash")
+                .arg(arg!(-'a' --"add" <TEXT> "add a line to the notes"))
+                .arg(arg!(--"clear" "delete all notes").conflicts_with("add"))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Show or edit the tags of a clash")
+                .arg(arg!(-'a' --"add" <TAG> "add a tag (can be repeated)").action(clap::ArgAction::Append))
+                .arg(arg!(-'r' --"remove" <TAG> "remove a tag (can be repeated)").action(clap::ArgAction::Append))
+                .arg(arg!(--"list" "list all tags with the number of clashes").exclusive(true))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+        )
+        .subcommand(
+            Command::new("favorite")
+                .about("Mark a clash as favorite")
+                .arg(arg!(--"remove" "unmark the clash as favorite"))
+                .arg(arg!(--"list" "list favorite clashes").exclusive(true))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+        )
+        .subcommand(
+            Command::new("hide")
+                .about("Hide a clash so that it is never picked at random")
+                .arg(arg!(--"remove" "unhide the clash"))
+                .arg(arg!(--"list" "list hidden clashes").exclusive(true))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+        )
         .subcommand(
             Command::new("status").about("Show status information")
         )
+        .subcommand(
+            Command::new("serve")
+                .about("Serve clashes, stubs and test runs to editor plugins over JSON-RPC")
+                .arg(arg!(--"stdio" "communicate over STDIN and STDOUT").required(true))
+                .after_help(
+                    "Messages are JSON-RPC 2.0 with a Content-Length header like in the Language Server Protocol. \
+                    Methods: clash/current {spoilerFree}, clash/get {handle, spoilerFree}, clash/list, clash/switch \
+                    {handle, spoilerFree} (random clash without a handle), languages/list, stub/generate {language, \
+                    handle}, tests/run {handle, command, buildCommand, timeout, buildTimeout, rebuild, testcases, \
+                    spoilerFree} and shutdown. All params are optional. \
+                    tests/run sends a tests/result notification for every testcase before it responds.\
+                    \nIMPORTANT: The commands you provide will be executed without any sandboxing. Only run code you trust!"
+                )
+        )
+        .subcommand(
+            Command::new("lsp")
+                .about("Run a language server for stub generator files")
+                .arg(arg!(--"stdio" "communicate over STDIN and STDOUT").required(true))
+                .after_help(
+                    "Reports unknown keywords, variables without a type and join terms that are not declared, \
+                    shows the type and the input comment of a variable on hover and offers code actions that \
+                    preview the stub in every language (coctus.previewStub). Configure your editor to start \
+                    `coctus lsp --stdio` for your stub generator files."
+                )
+        )
+        .subcommand(
+            Command::new("doctor").about("Check the setup and print how to fix the problems found")
+        )
+        .subcommand(
+            Command::new("start")
+                .about("Select a clash and set up the current directory to solve it")
+                .arg(arg!(<PROGRAMMING_LANGUAGE> "programming language of the solution"))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: random clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .arg(arg!(--"current" "use the current clash instead of a random one").conflicts_with("PUBLIC_HANDLE"))
+                .after_help(
+                    "Writes the input stub to solution.EXT (using the source_file_ext of the language's stub_config.toml) \
+                    and the statement to statement.txt. Existing files are never overwritten.\
+                    \nThe current directory becomes a workspace (see `coctus init`) whose run profile uses the \
+                    build_command and run_command of the language, so a bare `coctus run` tests the solution."
+                )
+        )
+        .subcommand(
+            Command::new("library")
+                .about("Manage libraries of clashes")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("List the libraries and their number of clashes"))
+                .subcommand(
+                    Command::new("add")
+                        .about("Register a directory of clashes as a library")
+                        .arg(arg!(<NAME> "name of the library"))
+                        .arg(arg!(<DIR> "directory of the clashes").value_parser(value_parser!(PathBuf)))
+                )
+                .subcommand(
+                    Command::new("forget")
+                        .about("Unregister a library (its clashes are not deleted)")
+                        .arg(arg!(<NAME> "name of the library"))
+                )
+                .subcommand(
+                    Command::new("default")
+                        .about("Make a library the default library")
+                        .arg(arg!(<NAME> "name of the library"))
+                )
+                .subcommand(
+                    Command::new("copy")
+                        .about("Copy clashes from the active library to another library")
+                        .arg(arg!(--"to" <LIBRARY> "library to copy the clashes to").required(true))
+                        .arg(
+                            arg!([PUBLIC_HANDLE]... "hexadecimal handles of the clashes (default: current clash)")
+                                .value_parser(value_parser!(HandleQuery))
+                        )
+                )
+                .subcommand(
+                    Command::new("move")
+                        .about("Move clashes from the active library to another library")
+                        .arg(arg!(--"to" <LIBRARY> "library to move the clashes to").required(true))
+                        .arg(
+                            arg!([PUBLIC_HANDLE]... "hexadecimal handles of the clashes (default: current clash)")
+                                .value_parser(value_parser!(HandleQuery))
+                        )
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Delete clashes from the active library")
+                        .arg(
+                            arg!(<PUBLIC_HANDLE>... "hexadecimal handles of the clashes")
+                                .value_parser(value_parser!(HandleQuery))
+                        )
+                )
+                .after_help(
+                    "A library is a directory of clash JSON files. The main library is stored in the data directory \
+                    and is the default library until another one is made the default.\
+                    \nThe active library is the default library unless another one is selected with --library, \
+                    for example: coctus next --library team\
+                    \nClashes are picked from and fetched to the active library, copy/move/remove work on the active library."
+                )
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect and change the configuration")
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Show all settings and where their values come from"))
+                .subcommand(Command::new("get").about("Print the value of a setting").arg(arg!(<KEY> "name of the setting")))
+                .subcommand(
+                    Command::new("set")
+                        .about("Change a setting in the config file")
+                        .arg(arg!(<KEY> "name of the setting"))
+                        .arg(arg!([VALUE] "new value of the setting").required_unless_present("unset"))
+                        .arg(arg!(--"unset" "remove the setting from the config file").conflicts_with("VALUE"))
+                )
+                .after_help(
+                    "The configuration is read from coctus.toml in the config directory (see `coctus status`).\
+                    \nSome settings can be overridden with environment variables:\
+                    \n  COCTUS_DATA_DIR, COCTUS_TIMEOUT, COCTUS_SHOW_WHITESPACE, COCTUS_COLOR,\
+                    \n  COCTUS_DEFAULT_LANGUAGE and COCTUS_STRATEGY\
+                    \nTables use dotted keys, for example: coctus config set hooks.on-pass 'git commit -am solved'\
+                    \nNOTE: `config set` rewrites the config file, comments in it are not kept."
+                )
+        )
+        .subcommand(
+            Command::new("init")
+                .about("Make the current directory a workspace with its own current clash")
+                .arg(arg!(--"lang" <LANGUAGE> "programming language of the solution"))
+                .arg(arg!(--"solution" <FILE> "source file of the solution").value_parser(value_parser!(PathBuf)))
+                .arg(arg!(--"command" <COMMAND> "command that executes the solution"))
+                .arg(arg!(--"build-command" <COMMAND> "command that compiles the solution"))
+                .arg(
+                    arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash (default: current clash)")
+                        .value_parser(value_parser!(HandleQuery))
+                )
+                .after_help(
+                    "Creates a .coctus.toml file in the current directory. Commands run in this directory \
+                    (or any of its subdirectories) use the clash selected in it instead of the global current clash, \
+                    so several terminals can work on different clashes at the same time.\
+                    \nWhen a command is set, `coctus run` works without --command. The commands are executed in \
+                    the workspace directory and the solution file is used as the --source."
+                )
+        )
         .subcommand(
             Command::new("fetch")
                 .about("Fetch a clash from codingame.com and save it locally")
                 .arg(
                     arg!(<PUBLIC_HANDLE> ... "hexadecimal handle of the clash")
-                        .value_parser(value_parser!(PublicHandle))
+                        .value_parser(value_parser!(HandleQuery))
                 )
                 .after_help(
                     "The PUBLIC_HANDLE of a puzzle is the last part of the URL when viewing it on the contribution section on CodinGame (1).\
+                    \nThe whole URL can be given instead of the PUBLIC_HANDLE.\
                     \nYou can fetch both clash of code and classic (in/out) puzzles.\
                     \n (1) https://www.codingame.com/contribute/community"
                 )
@@ -128,10 +680,14 @@ fn cli() -> clap::Command {
             Command::new("showtests")
                 .about("Print testcases and validators of current clash")
                 .arg(
-                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces")
+                    arg!(--"show-whitespace" [BOOL] "render ⏎ and • in place of newlines and spaces (default: false or from config)")
                         // This means show-whitespace=1 also works
                         .value_parser(clap::builder::BoolishValueParser::new())
-                        .default_value("false")
+                        .default_missing_value("true")
+                )
+                .arg(
+                    arg!(--"spoiler-free" [BOOL] "hide validators like the CodinGame IDE does (default: from config)")
+                        .value_parser(clap::builder::BoolishValueParser::new())
                         .default_missing_value("true")
                 )
                 .arg(arg!(--"in" "only print the testcase input"))
@@ -147,14 +703,14 @@ fn cli() -> clap::Command {
                 .about("Print the raw source JSON of a clash")
                 .arg(
                     arg!([PUBLIC_HANDLE] "hexadecimal handle of the clash")
-                        .value_parser(value_parser!(PublicHandle))
+                        .value_parser(value_parser!(HandleQuery))
                 )
         )
         .subcommand(
             Command::new("generate-stub")
                 .alias("gen")
                 .about("Generate input handling code for a given language")
-                .arg(arg!(<PROGRAMMING_LANGUAGE> "Programming language of the solution stub"))
+                .arg(arg!([PROGRAMMING_LANGUAGE] "Programming language of the solution stub (default: from config)"))
                 .arg(
                     arg!(--"from-file" <STUBFILE> "Generate stub from a stub generator file instead of the current clash")
                         .value_parser(clap::value_parser!(PathBuf))
@@ -179,87 +735,171 @@ fn cli() -> clap::Command {
                     \n  $ coctus generate-shell-completion fish > ~/.config/fish/completions/coctus.fish\
                     \n  $ coctus generate-shell-completion bash >> ~/.config/bash_completion\
                     \n  $ coctus generate-shell-completion powershell >> $PROFILE.CurrentUserCurrentHost\
-                    \nNOTE: (powershell) You may need to move the using statements to the top of the script."
+                    \nNOTE: (powershell) You may need to move the using statements to the top of the script.\
+                    \nThe bash, zsh and fish completions also suggest the handles of stored clashes and the available languages."
                 )
         )
+        .subcommand(
+            Command::new(COMPLETE_COMMAND)
+                .about("Print completion candidates for the last word (used by the shell completions)")
+                .hide(true)
+                .arg(arg!([WORDS] ... "words of the command line after the program name").allow_hyphen_values(true))
+        )
+}
+
+/// Params of the `coctus serve` methods that take a clash.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct HandleParams {
+    /// Handle, handle prefix or contribution URL (default: current clash).
+    handle: Option<String>,
+    /// Leaves out the validators (default: from the config).
+    spoiler_free: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct StubParams {
+    /// Default: from the config.
+    language: Option<String>,
+    handle: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RunTestsParams {
+    handle: Option<String>,
+    /// Default: from the workspace file.
+    command: Option<String>,
+    build_command: Option<String>,
+    /// Seconds (default: from the config).
+    timeout: Option<f64>,
+    /// Seconds (default: from the config).
+    build_timeout: Option<f64>,
+    /// Build even if the sources have not changed since the last build
+    /// (default: false).
+    rebuild: Option<bool>,
+    /// Indices of the testcases to run, starting from 1 (default: all).
+    testcases: Option<Vec<usize>>,
+    /// Default: from the config.
+    spoiler_free: Option<bool>,
 }
 
 struct App {
-    clash_dir: PathBuf,
-    current_clash_file: PathBuf,
-    stub_templates_dir: PathBuf,
+    /// Current clash, stored clashes and stub templates, with the active
+    /// library as the library that clashes are picked from and fetched to.
+    /// The workspace file of the current directory (if any) applies.
+    core: Workspace<FileStorage>,
+    /// Name of the active library.
+    library: String,
+    libraries: Libraries,
+    libraries_file: PathBuf,
+    /// Why the libraries file could not be read, if only the main library is
+    /// available because of that.
+    libraries_error: Option<anyhow::Error>,
+    data_dir: PathBuf,
+    config_file: PathBuf,
+    config: Config,
 }
 
 impl App {
-    fn new(data_dir: &std::path::Path, config_dir: &std::path::Path) -> App {
-        App {
-            clash_dir: data_dir.join("clashes"),
-            current_clash_file: data_dir.join("current"),
-            stub_templates_dir: config_dir.join("stub_templates"),
-        }
+    fn new(
+        data_dir: &std::path::Path,
+        config_dir: &std::path::Path,
+        config: Config,
+        library: Option<&str>,
+        tolerate_broken_libraries: bool,
+        command_output: CommandOutput,
+    ) -> Result<App> {
+        let libraries_file = data_dir.join("libraries.json");
+        let main_dir = data_dir.join("clashes");
+        let (libraries, libraries_error) = match Libraries::read_from_file(&libraries_file, &main_dir) {
+            Ok(libraries) => (libraries, None),
+            Err(err) if tolerate_broken_libraries => (Libraries::new(&main_dir), Some(err.into())),
+            Err(err) => return Err(err.into()),
+        };
+        let library = match libraries_error {
+            Some(_) => library::MAIN_LIBRARY.to_string(),
+            None => library.unwrap_or(libraries.default_name()).to_string(),
+        };
+        let storage = FileStorage::new(data_dir, &config_dir.join("stub_templates"), &libraries, &library)?;
+        let core = Workspace::new(storage)
+            .with_hooks(config.hooks.clone())
+            .with_dir(std::env::current_dir()?)
+            .with_command_output(command_output);
+        Ok(App {
+            core,
+            library,
+            libraries,
+            libraries_file,
+            libraries_error,
+            data_dir: data_dir.to_path_buf(),
+            config_file: config_dir.join(CONFIG_FILE_NAME),
+            config,
+        })
     }
 
-    // This may fail the very first time we call `show` if `next` was never run.
-    fn current_handle(&self) -> Result<PublicHandle> {
-        let content = std::fs::read_to_string(&self.current_clash_file)
-            .with_context(|| format!("Unable to read {:?}", &self.current_clash_file))?;
-        PublicHandle::from_str(&content)
+    /// The `--timeout` argument, falling back to the configured timeout.
+    fn timeout(&self, args: &ArgMatches) -> Result<Duration> {
+        let timeout = args.get_one::<f64>("timeout").copied().unwrap_or(self.config.timeout());
+        timeout_from_argument(Some(&timeout))
     }
 
-    fn clashes(&self) -> Result<std::fs::ReadDir> {
-        std::fs::read_dir(&self.clash_dir).with_context(|| "No clashes stored")
+    /// The `--build-timeout` argument, falling back to the configured build
+    /// timeout.
+    fn build_timeout(&self, args: &ArgMatches) -> Result<Duration> {
+        let timeout = args.get_one::<f64>("build-timeout").copied().unwrap_or(self.config.build_timeout());
+        timeout_from_argument(Some(&timeout))
     }
 
-    fn random_handle(&self) -> Result<PublicHandle> {
-        let mut rng = rand::thread_rng();
-        if let Ok(entry) = self.clashes()?.choose(&mut rng).expect("No clashes to choose from!") {
-            let filename =
-                entry.file_name().into_string().expect("unable to convert OsString to String (?!?)");
-            PublicHandle::from_str(match filename.strip_suffix(".json") {
-                Some(handle) => handle,
-                None => &filename,
-            })
-        } else {
-            Err(anyhow!("Unable to randomize next clash"))
-        }
+    /// The `--show-whitespace` argument, falling back to the configured value
+    /// and then to `default` (which differs between subcommands).
+    fn show_whitespace(&self, args: &ArgMatches, default: bool) -> bool {
+        args.get_one::<bool>("show-whitespace")
+            .copied()
+            .or(self.config.show_whitespace)
+            .unwrap_or(default)
     }
 
-    fn random_handle_with_modes(&self, fastest: bool, shortest: bool, reverse: bool) -> Result<PublicHandle> {
-        let max_attemps = 100;
-        for _ in 0..max_attemps {
-            let handle = self.random_handle()?;
-            let clash = self.read_clash(&handle)?;
-            if (!reverse || clash.is_reverse())
-                && (!fastest || clash.is_fastest())
-                && (!shortest || clash.is_shortest())
-            {
-                return Ok(handle)
-            }
+    /// The configured strategy for picking a random clash.
+    fn strategy(&self) -> Strategy {
+        self.config.strategy.unwrap_or_default()
+    }
+
+    fn output_style(&self, show_whitespace: bool) -> OutputStyle {
+        match self.config.color {
+            ColorChoice::Auto => OutputStyle::from_env(show_whitespace),
+            ColorChoice::Always => OutputStyle::default().with_whitespace(show_whitespace),
+            ColorChoice::Never => OutputStyle::plain().with_whitespace(show_whitespace),
         }
-        Err(anyhow!(format!(
-            "Failed to find a clash with the required modes after {} attempts.",
-            max_attemps
-        )))
     }
 
-    fn read_clash(&self, handle: &PublicHandle) -> Result<Clash> {
-        let clash_file = self.clash_dir.join(format!("{}.json", handle));
-        let contents = std::fs::read_to_string(&clash_file)
-            .with_context(|| format!("Unable to find clash with handle {}", handle))?;
-        let clash: Clash = serde_json::from_str(&contents)
-            .with_context(|| format!("Unable to deserialize clash from {:?}", &clash_file))?;
-        Ok(clash)
+    /// The `PROGRAMMING_LANGUAGE` argument, falling back to the configured
+    /// default language.
+    fn language_arg<'a>(&'a self, args: &'a ArgMatches) -> Result<&'a String> {
+        args.get_one::<String>("PROGRAMMING_LANGUAGE")
+            .or(self.config.default_language.as_ref())
+            .context("No programming language given (pass one or set default-language in the config)")
+    }
+
+    // This may fail the very first time we call `show` if `next` was never run.
+    fn current_handle(&self) -> Result<PublicHandle> {
+        Ok(self.core.current_handle()?)
+    }
+
+    /// Where the clashes and the data of the active library are stored.
+    fn files(&self) -> &FileStorage {
+        self.core.storage()
     }
 
     fn show(&self, args: &ArgMatches) -> Result<()> {
-        let handle = match args.get_one::<PublicHandle>("PUBLIC_HANDLE") {
-            Some(h) => h.to_owned(),
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
             None => self.current_handle()?,
         };
-        let clash = self.read_clash(&handle)?;
+        let clash = self.core.read_clash(&handle)?;
 
-        let show_whitespace = *args.get_one::<bool>("show-whitespace").unwrap_or(&false);
-        let ostyle = OutputStyle::from_env(show_whitespace);
+        let ostyle = self.output_style(self.show_whitespace(args, true));
 
         // --reverse flag
         if args.get_flag("reverse") {
@@ -279,128 +919,1043 @@ impl App {
             ostyle.print_statement(&clash);
         }
 
+        let metadata = self.core.clash_metadata(&handle)?;
+        if !metadata.is_empty() {
+            println!();
+            ostyle.print_metadata(&metadata);
+        }
+
         Ok(())
     }
 
     fn next(&self, args: &ArgMatches) -> Result<()> {
-        let next_handle = match args.get_one::<PublicHandle>("PUBLIC_HANDLE") {
-            Some(h) => h.to_owned(),
+        let next_handle = match self.handle_arg(args)? {
+            Some(h) => h,
             None => {
-                let fastest = args.get_flag("fastest");
-                let shortest = args.get_flag("shortest");
-                let reverse = args.get_flag("reverse");
-                if reverse || fastest || shortest {
-                    self.random_handle_with_modes(fastest, shortest, reverse)?
-                } else {
-                    self.random_handle()?
-                }
+                let modes = [
+                    ("fastest", Mode::Fastest),
+                    ("shortest", Mode::Shortest),
+                    ("reverse", Mode::Reverse),
+                ]
+                .into_iter()
+                .filter(|(flag, _)| args.get_flag(flag))
+                .map(|(_, mode)| mode)
+                .collect();
+                let filter = ClashFilter {
+                    modes,
+                    puzzle_type: args.get_one::<PuzzleType>("type").copied(),
+                    tags: args.get_many::<String>("tag").unwrap_or_default().cloned().collect(),
+                    favorites_only: args.get_flag("favorite"),
+                };
+                let strategy = args.get_one::<Strategy>("strategy").copied().unwrap_or(self.strategy());
+                self.core.pick_handle(&filter, strategy)?
             }
         };
         println!(" Changed clash to https://codingame.com/contribute/view/{}", next_handle);
-        println!(" Local file: {}/{}.json", &self.clash_dir.to_str().unwrap(), next_handle);
-        std::fs::write(&self.current_clash_file, next_handle.to_string())?;
+        if let Some(clash_file) = self.files().clash_file(&next_handle) {
+            println!(" Local file: {}", clash_file.display());
+        }
+        if self.libraries.iter().count() > 1 {
+            println!(" Library: {}", self.library);
+        }
+        self.change_current_handle(&next_handle)
+    }
+
+    /// Makes `handle` the current clash and runs the on-next hook.
+    fn change_current_handle(&self, handle: &PublicHandle) -> Result<()> {
+        self.core.set_current_handle(handle)?.into_iter().for_each(print_warning);
         Ok(())
     }
 
     fn status(&self, _args: &ArgMatches) -> Result<()> {
-        println!("Current clash file: {}", self.current_clash_file.display());
+        match self.core.workspace_file()? {
+            Some(workspace) => println!("Workspace file: {}", workspace.path().display()),
+            None => println!("Current clash file: {}", self.files().current_clash_file().display()),
+        }
         match self.current_handle() {
             Ok(handle) => println!("Current clash: {}", handle),
             Err(_) => println!("Current clash: -"),
         }
-        println!("Clash dir: {}", self.clash_dir.display());
-        let num_clashes = match self.clashes() {
-            Ok(clashes) => clashes.count(),
-            Err(_) => 0,
-        };
+        println!("Config file: {}", self.config_file.display());
+        match self.library == self.libraries.default_name() {
+            true => println!("Library: {} (default)", self.library),
+            false => println!("Library: {} (default: {})", self.library, self.libraries.default_name()),
+        }
+        println!("Clash dir: {}", self.files().clash_dir().display());
+        let num_clashes = self.core.stored_handles().map_or(0, |handles| handles.len());
         println!("Number of clashes: {}", num_clashes);
+        if let Some(session) = self.core.practice_session() {
+            let remaining = game::format_duration(session.remaining());
+            println!("Practice clash: {} ({} mode, {} left)", session.handle, session.mode, remaining);
+        }
         Ok(())
     }
 
-    fn run(&self, args: &ArgMatches) -> Result<()> {
-        let handle = match args.get_one::<PublicHandle>("PUBLIC_HANDLE") {
-            Some(h) => h.to_owned(),
-            None => self.current_handle()?,
-        };
-
-        if let Some(mut build_command) = command_from_argument(args.get_one::<String>("build-command"))? {
-            let build = build_command.output()?;
+    fn doctor(&self, _args: &ArgMatches) -> Result<()> {
+        let mut doctor = Doctor::new(self.output_style(false));
 
-            if !build.status.success() {
-                if !build.stderr.is_empty() {
-                    println!("Build command STDERR:\n{}", String::from_utf8(build.stderr)?);
-                }
-                if !build.stdout.is_empty() {
-                    println!("Build command STDOUT:\n{}", String::from_utf8(build.stdout)?);
-                }
-                return Err(anyhow!("Build failed"))
+        doctor.section("Directories");
+        match self.data_dir.is_dir() {
+            true => doctor.ok(format!("Data directory: {}", self.data_dir.display())),
+            false => doctor.warning(
+                format!("Data directory {} does not exist yet", self.data_dir.display()),
+                "It is created when fetching the first clash with `coctus fetch PUBLIC_HANDLE`",
+            ),
+        }
+        match Config::load(&self.config_file) {
+            Ok(_) if self.config_file.is_file() => {
+                doctor.ok(format!("Config file: {}", self.config_file.display()))
             }
+            Ok(_) => {
+                doctor.ok(format!("No config file at {} (using the defaults)", self.config_file.display()))
+            }
+            Err(err) => doctor.error(
+                format!("{:#}", err),
+                "Fix the config file or the COCTUS_* environment variables (see `coctus config list`)",
+            ),
+        }
+        if let Some(finding) = self.core.check_workspace_file() {
+            doctor.report(finding);
         }
 
-        let mut run_command = command_from_argument(args.get_one::<String>("command"))?
-            .expect("clap should ensure `run` can't be executed without a --command");
-
-        let timeout = match *args.get_one::<f64>("timeout").unwrap_or(&5.0) {
-            secs if secs.is_nan() => return Err(anyhow!("Timeout can't be NaN")),
-            secs if secs < 0.0 => return Err(anyhow!("Timeout can't be negative (use 0 for no timeout)")),
-            secs if secs == 0.0 => std::time::Duration::MAX,
-            secs => std::time::Duration::from_micros((secs * 1e6) as u64),
-        };
-
-        let all_testcases = self.read_clash(&handle)?.testcases().to_owned();
+        doctor.section("Current clash");
+        doctor.report(self.core.check_current_clash());
 
-        let testcases: Vec<&Testcase> = if let Some(testcase_indices) = args.get_many::<u64>("testcases") {
-            testcase_indices.map(|idx| &all_testcases[(idx - 1) as usize]).collect()
-        } else {
-            all_testcases.iter().collect()
-        };
+        doctor.section("Libraries");
+        if let Some(err) = &self.libraries_error {
+            doctor.error(
+                format!("{:#}", err),
+                format!("Fix or delete {} (only the main library is checked)", self.libraries_file.display()),
+            );
+        }
+        for (name, dir) in self.libraries.iter() {
+            workspace::check_library(name, dir)
+                .into_iter()
+                .for_each(|finding| doctor.report(finding));
+        }
 
-        let num_tests = testcases.len();
-        let suite_run = solution::lazy_run(testcases, &mut run_command, &timeout);
+        doctor.section("Stub templates");
+        self.core
+            .check_stub_templates()
+            .into_iter()
+            .for_each(|finding| doctor.report(finding));
 
-        let ignore_failures = args.get_flag("ignore-failures");
-        let show_whitespace = *args.get_one::<bool>("show-whitespace").unwrap_or(&false);
-        let ostyle = OutputStyle::from_env(show_whitespace);
+        doctor.section("Commands");
+        self.core.check_commands()?.into_iter().for_each(|finding| doctor.report(finding));
 
-        let mut num_passed = 0;
+        println!();
+        match (doctor.errors, doctor.warnings) {
+            (0, 0) => println!("{}", self.output_style(false).success.paint("No problems found")),
+            (errors, warnings) => println!("Found {} error(s) and {} warning(s)", errors, warnings),
+        }
+        match doctor.errors {
+            0 => Ok(()),
+            errors => Err(anyhow!("{} problem(s) need to be fixed", errors)),
+        }
+    }
 
-        for (testcase, test_result) in suite_run {
-            ostyle.print_result(testcase, &test_result);
+    /// The `PUBLIC_HANDLE` argument (if given), with a handle prefix resolved
+    /// to the stored clash it refers to.
+    fn handle_arg(&self, args: &ArgMatches) -> Result<Option<PublicHandle>> {
+        match args.get_one::<HandleQuery>("PUBLIC_HANDLE") {
+            Some(query) => Ok(Some(self.core.resolve(query)?)),
+            None => Ok(None),
+        }
+    }
 
-            if test_result.is_success() {
-                num_passed += 1;
-            } else if !ignore_failures {
-                break
+    /// The `PUBLIC_HANDLE` arguments, or the current clash if none are given.
+    fn handles_arg(&self, args: &ArgMatches) -> Result<Vec<PublicHandle>> {
+        match args.get_many::<HandleQuery>("PUBLIC_HANDLE") {
+            Some(queries) => {
+                Ok(queries.map(|query| self.core.resolve(query)).collect::<clashlib::Result<_>>()?)
             }
+            None => Ok(vec![self.current_handle()?]),
+        }
+    }
+
+    /// Name and directory of the library given with `--to`.
+    fn target_library<'a>(&'a self, args: &'a ArgMatches) -> Result<(&'a str, &'a std::path::Path)> {
+        let name = args.get_one::<String>("to").expect("clap should ensure --to is given");
+        Ok((name, self.libraries.target_dir(&self.library, name)?))
+    }
+
+    fn library(&self, args: &ArgMatches) -> Result<()> {
+        let mut libraries = self.libraries.clone();
+        match args.subcommand() {
+            Some(("list", _)) => {
+                for (name, dir) in libraries.iter() {
+                    let num_clashes = library::stored_handles(dir).map_or(0, |handles| handles.len());
+                    let mut labels = Vec::new();
+                    if name == libraries.default_name() {
+                        labels.push("default");
+                    }
+                    if name == self.library {
+                        labels.push("active");
+                    }
+                    let labels = match labels.is_empty() {
+                        true => String::new(),
+                        false => format!(" ({})", labels.join(", ")),
+                    };
+                    println!("{name}{labels}: {num_clashes} clashes in {}", dir.display());
+                }
+                return Ok(())
+            }
+            Some(("add", args)) => {
+                let name = args.get_one::<String>("NAME").expect("clap should ensure NAME is given");
+                let dir = args.get_one::<PathBuf>("DIR").expect("clap should ensure DIR is given");
+                libraries.create(name, dir)?;
+                println!("Added library {name}");
+            }
+            Some(("forget", args)) => {
+                let name = args.get_one::<String>("NAME").expect("clap should ensure NAME is given");
+                let dir = libraries.forget(name)?;
+                println!("Removed library {name} (its clashes are still stored in {})", dir.display());
+            }
+            Some(("default", args)) => {
+                let name = args.get_one::<String>("NAME").expect("clap should ensure NAME is given");
+                libraries.set_default(name)?;
+                println!("The default library is now {name}");
+            }
+            Some(("copy", args)) => {
+                let (target, target_dir) = self.target_library(args)?;
+                for handle in self.handles_arg(args)? {
+                    library::copy_clash(self.files().clash_dir(), target_dir, &handle)?;
+                    println!("Copied {handle} from {} to {target}", self.library);
+                }
+                return Ok(())
+            }
+            Some(("move", args)) => {
+                let (target, target_dir) = self.target_library(args)?;
+                for handle in self.handles_arg(args)? {
+                    library::move_clash(self.files().clash_dir(), target_dir, &handle)?;
+                    println!("Moved {handle} from {} to {target}", self.library);
+                }
+                return Ok(())
+            }
+            Some(("remove", args)) => {
+                for handle in self.handles_arg(args)? {
+                    library::remove_clash(self.files().clash_dir(), &handle)?;
+                    println!("Removed {handle} from {}", self.library);
+                }
+                return Ok(())
+            }
+            _ => unreachable!("clap should ensure a subcommand is given"),
+        }
+        Ok(libraries.write_to_file(&self.libraries_file)?)
+    }
+
+    fn config(&self, args: &ArgMatches) -> Result<()> {
+        match args.subcommand() {
+            Some(("list", _)) => {
+                println!("Config file: {}", self.config_file.display());
+                let ostyle = self.output_style(false);
+                for (setting, value, source) in Config::settings_with_sources(&self.config_file)? {
+                    let value = value.map_or(String::from("-"), |value| value.to_string());
+                    let source = match source {
+                        SettingSource::Default => String::new(),
+                        SettingSource::ConfigFile => String::from(" (config file)"),
+                        SettingSource::Environment(var) => format!(" (from {var})"),
+                    };
+                    let description = ostyle.dim_color.paint(format!("# {}", setting.description));
+                    println!("{:<18} {:<24} {description}{source}", setting.key, value);
+                }
+                if let Err(err) = Config::load(&self.config_file) {
+                    println!();
+                    println!("{}", ostyle.error.paint(format!("{:#}", err)));
+                }
+            }
+            Some(("get", args)) => {
+                let key = args.get_one::<String>("KEY").expect("clap should ensure KEY is given");
+                let (_, value, _) = Config::settings_with_sources(&self.config_file)?
+                    .into_iter()
+                    .find(|(setting, _, _)| setting.key == key)
+                    .with_context(|| format!("Unknown setting {:?}", key))?;
+                match value.with_context(|| format!("{key} is not set"))? {
+                    toml::Value::String(text) => println!("{text}"),
+                    value => println!("{value}"),
+                }
+            }
+            Some(("set", args)) => {
+                let key = args.get_one::<String>("KEY").expect("clap should ensure KEY is given");
+                let value = args.get_one::<String>("VALUE");
+                Config::set_in_file(&self.config_file, key, value.map(String::as_str))?;
+                if let Err(err) = Config::load(&self.config_file) {
+                    eprintln!("WARNING: the configuration is still invalid: {:#}", err);
+                }
+                if let Some(var) = SETTINGS.iter().find(|setting| setting.key == key).and_then(|s| s.env_var)
+                {
+                    if std::env::var_os(var).is_some() {
+                        eprintln!("WARNING: {var} is set and overrides {key}");
+                    }
+                }
+            }
+            _ => unreachable!("clap should ensure a subcommand is given"),
+        }
+        Ok(())
+    }
+
+    fn run(&self, args: &ArgMatches) -> Result<()> {
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+
+        let practice_session = self.core.practice_session().filter(|session| session.handle == handle);
+        if let Some(session) = &practice_session {
+            let remaining = match session.remaining() {
+                remaining if remaining.is_zero() => String::from("time is up!"),
+                remaining => format!("{} left", game::format_duration(remaining)),
+            };
+            println!("Practice clash ({} mode): {}", session.mode, remaining);
+        }
+
+        let profile = self.run_profile(args)?;
+        let mut options = self.test_options(args)?;
+        options.rebuild = args.get_flag("rebuild");
+        options.testcases = args
+            .get_many::<u64>("testcases")
+            .map(|indices| indices.map(|&index| index as usize).collect());
+        options.validators = args.get_flag("validators");
+        options.fail_fast = !args.get_flag("ignore-failures");
+
+        let mut ostyle = self.output_style(self.show_whitespace(args, true));
+        ostyle.diff_mode = args.get_one::<DiffMode>("diff").copied().unwrap_or_default();
+
+        let mut previous_testcase: Option<Testcase> = None;
+        let mut output_error = None;
+        let summary = self.core.run_tests(&handle, &profile, &options, |event| {
+            match event {
+                TestEvent::Build(status) => {
+                    if let Err(err) = print_build_status(&mut std::io::stdout(), status) {
+                        output_error = Some(err);
+                        return ControlFlow::Break(())
+                    }
+                }
+                TestEvent::Result {
+                    testcase,
+                    result,
+                    hidden: false,
+                } => {
+                    ostyle.print_result(testcase, &result, previous_testcase.as_ref());
+                    previous_testcase = Some(testcase.clone());
+                }
+                TestEvent::Result {
+                    testcase,
+                    result,
+                    hidden: true,
+                } => ostyle.print_hidden_result(testcase, &result),
+            }
+            ControlFlow::Continue(())
+        })?;
+        if let Some(err) = output_error {
+            return Err(err)
+        }
+
+        println!("{}/{} tests passed (in {:.2?})", summary.passed, summary.total, summary.elapsed);
+        if summary.validators > 0 {
+            println!("{}/{} validators passed", summary.validators_passed, summary.validators);
+        }
+        let all_passed = summary.all_passed();
+        summary.warnings.into_iter().for_each(print_warning);
+
+        if let Some(session) = practice_session.filter(|_| all_passed && options.testcases.is_none()) {
+            self.submit_practice_solution(&session, &profile, options.timeout)?;
         }
-        println!("{num_passed}/{num_tests} tests passed");
 
         // Move on to next clash if --auto-advance is set
-        if num_passed == num_tests && args.get_flag("auto-advance") {
-            let next_handle = self.random_handle()?;
-            std::fs::write(&self.current_clash_file, next_handle.to_string())?;
+        if all_passed && args.get_flag("auto-advance") {
+            let next_handle = self.core.pick_handle(&ClashFilter::default(), self.strategy())?;
             println!("Moving on to next clash...");
+            self.change_current_handle(&next_handle)?;
+        }
+
+        Ok(())
+    }
+
+    /// How to run the tests: the time limits and spoiler-free mode from the
+    /// arguments of `run` or `tui`.
+    fn test_options(&self, args: &ArgMatches) -> Result<TestOptions> {
+        let mut options = TestOptions::new(self.timeout(args)?, self.build_timeout(args)?);
+        options.spoiler_free =
+            args.get_one::<bool>("spoiler-free").copied().unwrap_or(self.config.spoiler_free);
+        Ok(options)
+    }
+
+    fn tui(&self, args: &ArgMatches) -> Result<()> {
+        let mut handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+        let test_run = match self.run_profile(args) {
+            Ok(profile) => Some(TestRun {
+                workspace: self.core.clone().with_command_output(CommandOutput::Discard),
+                profile,
+                options: self.test_options(args)?,
+            }),
+            Err(_) => None,
+        };
+        let spoiler_free = args.get_one::<bool>("spoiler-free").copied().unwrap_or(self.config.spoiler_free);
+        let mut show_whitespace = self.show_whitespace(args, true);
+        let mut diff_mode = args.get_one::<DiffMode>("diff").copied().unwrap_or_default();
+
+        loop {
+            let clash = self.core.read_clash(&handle)?;
+            let mut tui = Tui::new(&handle, &clash, test_run.clone(), self.output_style(true), spoiler_free);
+            tui.show_whitespace = show_whitespace;
+            tui.diff_mode = diff_mode;
+            let exit = tui.run()?;
+            (show_whitespace, diff_mode) = (tui.show_whitespace, tui.diff_mode);
+            match exit {
+                TuiExit::Quit => return Ok(()),
+                TuiExit::NextClash => {
+                    handle = self.core.pick_handle(&ClashFilter::default(), self.strategy())?;
+                    self.change_current_handle(&handle)?;
+                }
+            }
+        }
+    }
+
+    /// How to build and run the solution: from the arguments of `run`,
+    /// falling back to the run profile of the workspace.
+    fn run_profile(&self, args: &ArgMatches) -> Result<RunProfile> {
+        Ok(self.core.run_profile(
+            args.get_one::<String>("command").cloned(),
+            args.get_one::<String>("build-command").cloned(),
+            args.get_many::<PathBuf>("source").map(|sources| sources.cloned().collect()),
+        )?)
+    }
+
+    fn start(&self, args: &ArgMatches) -> Result<()> {
+        let lang = args
+            .get_one::<String>("PROGRAMMING_LANGUAGE")
+            .expect("clap should ensure LANG is given");
+        let stub_config = self.core.stub_config(lang)?;
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None if args.get_flag("current") => self.current_handle()?,
+            None => self.core.pick_handle(&ClashFilter::default(), self.strategy())?,
+        };
+        let clash = self.core.read_clash(&handle)?;
+
+        let dir = std::env::current_dir()?;
+        let solution_file = format!("solution.{}", stub_config.source_file_ext());
+        let statement_file = "statement.txt";
+        for file in [solution_file.as_str(), statement_file] {
+            if dir.join(file).exists() {
+                return Err(anyhow!("{file} already exists, refusing to overwrite it (use another directory)"))
+            }
+        }
+
+        let run_command = stub_config.run_command(&solution_file);
+        let build_command = stub_config.build_command(&solution_file);
+        let stub = match clash.stub_generator() {
+            Some(generator) => stub::generate_from_config(stub_config, generator)?,
+            None => {
+                eprintln!(
+                    "WARNING: the clash provides no input stub generator, {solution_file} will be empty"
+                );
+                String::new()
+            }
+        };
+
+        let ostyle = OutputStyle::plain();
+        let statement = ostyle.format_headers(&clash) + &ostyle.format_statement(&clash);
+        std::fs::write(dir.join(&solution_file), stub + "\n")?;
+        std::fs::write(dir.join(statement_file), statement)?;
+
+        let existing_workspace = self.core.workspace_file()?.filter(|workspace| workspace.dir == dir);
+        let profile = WorkspaceFile {
+            language: Some(lang.clone()),
+            solution: Some(PathBuf::from(&solution_file)),
+            command: run_command.clone(),
+            build_command,
+            ..existing_workspace.clone().unwrap_or_default()
+        };
+        let workspace = match existing_workspace {
+            Some(_) => {
+                profile.save()?;
+                profile
+            }
+            None => WorkspaceFile::create(&dir, profile)?,
+        };
+        self.change_current_handle(&handle)?;
+
+        println!("Started {} ({handle})", clash.title());
+        println!("Wrote {solution_file} and {statement_file}");
+        match run_command {
+            Some(_) => println!("Test your solution with `coctus run`"),
+            None => println!(
+                "No run command is known for {lang}, set `command` in {} to use `coctus run`",
+                workspace.path().display()
+            ),
+        }
+        Ok(())
+    }
+
+    fn init(&self, args: &ArgMatches) -> Result<()> {
+        let dir = std::env::current_dir()?;
+        let handle = match self.handle_arg(args)? {
+            Some(h) => Some(h),
+            None => self.current_handle().ok(),
+        };
+        let workspace = WorkspaceFile::create(
+            &dir,
+            WorkspaceFile {
+                handle,
+                language: args.get_one::<String>("lang").cloned(),
+                solution: args.get_one::<PathBuf>("solution").cloned(),
+                command: args.get_one::<String>("command").cloned(),
+                build_command: args.get_one::<String>("build-command").cloned(),
+                ..Default::default()
+            },
+        )?;
+        println!("Created {}", workspace.path().display());
+        Ok(())
+    }
+
+    fn clash(&self, args: &ArgMatches) -> Result<()> {
+        if args.get_flag("stop") {
+            self.core.stop_practice()?;
+            println!("Practice clash abandoned");
+            return Ok(())
+        }
+
+        let (handle, clash, mode) = self.pick_clash_with_mode(args)?;
+
+        let time_limit = match args.get_one::<u64>("minutes") {
+            Some(minutes) => Duration::from_secs(minutes * 60),
+            None => game::DEFAULT_TIME_LIMIT,
+        };
+        self.core.start_practice(handle.clone(), mode, time_limit)?;
+        self.change_current_handle(&handle)?;
+
+        let ostyle = self.output_style(true);
+        ostyle.print_headers(&clash);
+        println!("Started a {} minute {mode} clash. Good luck!", time_limit.as_secs() / 60);
+        println!("Use `coctus show` to read the statement and `coctus run` to test your solution.");
+        Ok(())
+    }
+
+    /// Selects the clash and game mode given by the PUBLIC_HANDLE and --mode
+    /// arguments. Picks a random clash (with that mode) and a random mode (of
+    /// that clash) for the ones that are missing.
+    fn pick_clash_with_mode(&self, args: &ArgMatches) -> Result<(PublicHandle, Clash, Mode)> {
+        let mode = args.get_one::<Mode>("mode").copied();
+        let handle = match (self.handle_arg(args)?, mode) {
+            (Some(handle), _) => handle,
+            (None, mode) => self
+                .core
+                .pick_handle(&ClashFilter::with_modes(mode.into_iter().collect()), self.strategy())?,
+        };
+        let clash = self.core.read_clash(&handle)?;
+
+        let mode = match mode {
+            Some(mode) if !clash.has_mode(mode) => {
+                return Err(anyhow!("Clash {} does not have {} mode", handle, mode))
+            }
+            Some(mode) => mode,
+            None => clash.modes().into_iter().choose(&mut rand::thread_rng()).unwrap_or(Mode::Fastest),
+        };
+        Ok((handle, clash, mode))
+    }
+
+    /// Scores a solution that passed all tests in a practice clash and stores
+    /// the result.
+    fn submit_practice_solution(
+        &self,
+        session: &PracticeSession,
+        profile: &RunProfile,
+        timeout: Duration,
+    ) -> Result<()> {
+        if !session.is_time_up() {
+            println!("Submitting solution...");
+        }
+        let (result, previous_best) = match self.core.submit_practice(session, profile, timeout)? {
+            PracticeSubmission::TimeUp => {
+                println!("Time is up! The result of the practice clash was not recorded.");
+                return Ok(())
+            }
+            PracticeSubmission::Submitted {
+                result,
+                previous_best,
+            } => (result, previous_best),
+        };
+        if result.length.is_none() && result.mode == Mode::Shortest {
+            println!("Code length was not measured (use --source to specify the source files)");
+        }
+
+        println!("Clash finished! Score: {}", result.summary());
+        if let Some(leaderboard_dir) = &self.config.leaderboard_dir {
+            let published = self
+                .config
+                .player_name()
+                .context("Unable to determine player name (set player-name in the config file)")
+                .and_then(|player| Ok(leaderboard::publish(leaderboard_dir, &player, &result)?));
+            if let Err(err) = published {
+                eprintln!("Unable to publish result to the leaderboard: {err:#}");
+            }
+        }
+        match previous_best {
+            Some(best) if result.is_better_than(&best) => {
+                println!("New personal best! (previous: {})", best.summary())
+            }
+            Some(best) => println!("Personal best: {}", best.summary()),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn leaderboard(&self, args: &ArgMatches) -> Result<()> {
+        let leaderboard_dir = self
+            .config
+            .leaderboard_dir
+            .as_ref()
+            .context("No leaderboard directory configured (set leaderboard-dir in the config file)")?;
+        let mut entries = leaderboard::read_entries(leaderboard_dir)?;
+        if let Some(mode) = args.get_one::<Mode>("mode") {
+            entries.retain(|entry| &entry.result.mode == mode);
+        }
+        let ostyle = self.output_style(true);
+
+        let Some(handle) = self.handle_arg(args)? else {
+            let stats = leaderboard::player_stats(&leaderboard::best_entries(entries));
+            if stats.is_empty() {
+                println!("No results published yet");
+                return Ok(())
+            }
+            let mut stats: Vec<_> = stats.into_iter().collect();
+            stats.sort_by_key(|(player, s)| (std::cmp::Reverse((s.first_places, s.solved)), player.clone()));
+            let name_width = stats.iter().map(|(player, _)| player.chars().count()).max().unwrap_or(0).max(6);
+            println!("{:<name_width$}  {:>6}  {:>6}  {:>6}", "PLAYER", "FIRST", "SOLVED", "PLAYED");
+            for (player, s) in stats {
+                println!("{player:<name_width$}  {:>6}  {:>6}  {:>6}", s.first_places, s.solved, s.played);
+            }
+            return Ok(())
+        };
+
+        let best = leaderboard::best_entries(leaderboard::entries_for_handle(entries, &handle));
+        if best.is_empty() {
+            println!("No results published for {handle}");
+            return Ok(())
+        }
+        match self.core.read_clash(&handle) {
+            Ok(clash) => println!("{} ({handle})", ostyle.title.paint(clash.title())),
+            Err(_) => println!("{handle}"),
+        }
+        let name_width = best.iter().map(|entry| entry.player.chars().count()).max().unwrap_or(0);
+        for mode in Mode::ALL {
+            let ranking: Vec<_> = best.iter().filter(|entry| entry.result.mode == mode).collect();
+            if ranking.is_empty() {
+                continue
+            }
+            println!("{}", ostyle.secondary_title.paint(format!("{mode}:")));
+            for entry in &ranking {
+                let rank =
+                    1 + ranking.iter().filter(|other| other.result.is_better_than(&entry.result)).count();
+                println!("{rank:>2}. {:<name_width$}  {}", entry.player, entry.result.summary());
+            }
+        }
+        Ok(())
+    }
+
+    fn hotseat(&self, args: &ArgMatches) -> Result<()> {
+        match args.subcommand() {
+            Some(("start", args)) => self.hotseat_start(args),
+            Some(("submit", args)) => self.hotseat_submit(args),
+            Some(("results", _)) => self.hotseat_results(),
+            _ => Err(anyhow!("unimplemented subcommand")),
+        }
+    }
+
+    fn hotseat_start(&self, args: &ArgMatches) -> Result<()> {
+        let players: Vec<String> = args.get_many::<String>("player").unwrap_or_default().cloned().collect();
+        let (handle, clash, mode) = self.pick_clash_with_mode(args)?;
+        let hotseat = self.core.start_hotseat(handle.clone(), mode, players)?;
+        self.change_current_handle(&handle)?;
+
+        let ostyle = self.output_style(true);
+        ostyle.print_headers(&clash);
+        println!("Started a {mode} clash for {}. Good luck!", hotseat.players.join(", "));
+        println!("Use `coctus hotseat submit PLAYER FILE --command COMMAND` to submit a solution.");
+        Ok(())
+    }
+
+    fn hotseat_submit(&self, args: &ArgMatches) -> Result<()> {
+        let player = args.get_one::<String>("PLAYER").expect("clap should ensure PLAYER is given");
+        self.core.hotseat()?.check_player(player)?;
+        let source_file = args.get_one::<PathBuf>("FILE").expect("clap should ensure FILE is given");
+        let profile = RunProfile {
+            command: args
+                .get_one::<String>("command")
+                .cloned()
+                .expect("clap should ensure --command is given"),
+            build_command: args.get_one::<String>("build-command").cloned(),
+            ..Default::default()
+        };
+        let status = self.core.build(&profile, self.build_timeout(args)?, true)?;
+        print_build_status(&mut std::io::stdout(), status)?;
+
+        println!("Submitting solution of {player}...");
+        let (hotseat, result) =
+            self.core.submit_hotseat(player, source_file, &profile, self.timeout(args)?)?;
+        println!("{player}: {}", result.summary());
+
+        match hotseat.waiting_for().as_slice() {
+            [] => self.hotseat_results(),
+            waiting => {
+                println!("Waiting for {}", waiting.join(", "));
+                Ok(())
+            }
+        }
+    }
+
+    fn hotseat_results(&self) -> Result<()> {
+        let hotseat = self.core.hotseat()?;
+        let clash = self.core.read_clash(&hotseat.handle)?;
+        let ostyle = self.output_style(true);
+
+        println!("{} ({} mode, {})", ostyle.title.paint(clash.title()), hotseat.mode, hotseat.handle);
+        let name_width = hotseat.players.iter().map(|p| p.chars().count()).max().unwrap_or(0);
+        for (rank, player, result) in hotseat.ranking() {
+            println!("{rank:>2}. {player:<name_width$}  {}", result.summary());
+        }
+        for player in hotseat.waiting_for() {
+            println!("  - {player:<name_width$}  {}", ostyle.dim_color.paint("(no submission)"));
+        }
+        Ok(())
+    }
+
+    fn verify(&self, args: &ArgMatches) -> Result<()> {
+        let handle = self.handle_arg(args)?;
+        let language = args.get_one::<String>("lang").map(String::as_str);
+        let solutions = self.core.saved_solutions(handle.as_ref(), language)?;
+
+        if solutions.is_empty() {
+            println!("No saved solutions found in {}", self.core.solutions_dir()?.display());
+            return Ok(())
+        }
+
+        let options = TestOptions::new(self.timeout(args)?, self.build_timeout(args)?);
+        let verbose = args.get_flag("verbose");
+        let ostyle = self.output_style(false);
+
+        let num_solutions = solutions.len();
+        let mut num_failed = 0;
+
+        for stored_solution in solutions {
+            let mut saved_solution = match stored_solution {
+                Ok(saved_solution) => saved_solution,
+                Err(broken) => {
+                    print_solution_error(&ostyle, &broken.handle, &broken.language, broken.error);
+                    num_failed += 1;
+                    continue
+                }
+            };
+            match self.core.verify_solution(&mut saved_solution, &options) {
+                Ok(verification) => {
+                    if !verification.all_passed() {
+                        num_failed += 1;
+                    }
+                    print_verification(&ostyle, &saved_solution, &verification, verbose)?;
+                }
+                Err(err) => {
+                    print_solution_error(&ostyle, &saved_solution.handle, &saved_solution.language, err);
+                    num_failed += 1;
+                }
+            }
+        }
+
+        println!("{}/{num_solutions} saved solutions passed", num_solutions - num_failed);
+
+        if num_failed > 0 {
+            Err(anyhow!("{num_failed} saved solutions failed verification"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn save(&self, args: &ArgMatches) -> Result<()> {
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+        let source_file = args.get_one::<PathBuf>("FILE").expect("clap should ensure FILE is given");
+        let language = match args.get_one::<String>("lang") {
+            Some(lang) => lang.to_owned(),
+            None => self.language_of_file(source_file).with_context(|| {
+                format!("Unable to guess the language of {:?} (use --lang to specify it)", source_file)
+            })?,
+        };
+        let command = args.get_one::<String>("command").cloned();
+        let build_command = args.get_one::<String>("build-command").cloned();
+
+        let options = TestOptions::new(self.timeout(args)?, self.build_timeout(args)?);
+        let (saved_solution, verification) =
+            self.core
+                .save_solution(&handle, &language, source_file, command, build_command, &options)?;
+        print_verification(&self.output_style(false), &saved_solution, &verification, false)?;
+
+        println!(
+            "Saved {} solution ({} chars) as {}",
+            saved_solution.language,
+            saved_solution.meta.chars,
+            saved_solution.source_path().display()
+        );
+        Ok(())
+    }
+
+    fn solutions(&self, args: &ArgMatches) -> Result<()> {
+        let solutions = self.core.saved_solutions(self.handle_arg(args)?.as_ref(), None)?;
+
+        if solutions.is_empty() {
+            println!("No saved solutions found in {}", self.core.solutions_dir()?.display());
+            return Ok(())
+        }
+
+        let ostyle = self.output_style(false);
+        for stored_solution in solutions {
+            let saved_solution = match stored_solution {
+                Ok(saved_solution) => saved_solution,
+                Err(broken) => {
+                    let error = anyhow::Error::from(broken.error);
+                    println!(
+                        "{} {} {:<10} {}",
+                        ostyle.error.paint("ERROR"),
+                        broken.handle,
+                        broken.language,
+                        ostyle.stderr.paint(format!("{error:#}"))
+                    );
+                    continue
+                }
+            };
+            let status = match saved_solution.meta.passed {
+                Some(true) => ostyle.success.paint("PASS"),
+                Some(false) => ostyle.failure.paint("FAIL"),
+                None => ostyle.dim_color.paint("----"),
+            };
+            let title = match self.core.read_clash(&saved_solution.handle) {
+                Ok(clash) => clash.title().to_string(),
+                Err(_) => String::from("(clash not found)"),
+            };
+            println!(
+                "{status} {} {:<10} {:>6} chars  {title}",
+                saved_solution.handle, saved_solution.language, saved_solution.meta.chars
+            );
+        }
+        Ok(())
+    }
+
+    fn checkout(&self, args: &ArgMatches) -> Result<()> {
+        let handle = &self.handle_arg(args)?.expect("clap should ensure handle is given");
+        let language = args.get_one::<String>("LANGUAGE").expect("clap should ensure language is given");
+        let output = args.get_one::<PathBuf>("output").map(PathBuf::as_path);
+        let (saved_solution, output) =
+            self.core.checkout_solution(handle, language, output, args.get_flag("force"))?;
+        self.change_current_handle(handle)?;
+
+        let output_str = output.to_string_lossy();
+        println!("Wrote {} solution to {}", language, output.display());
+        println!(" Changed clash to https://codingame.com/contribute/view/{}", handle);
+        if let Some(build_command) = &saved_solution.meta.build_command {
+            println!(
+                " Build command: {}",
+                build_command.replace(saved::SOURCE_FILE_PLACEHOLDER, &output_str)
+            );
+        }
+        println!(
+            " Command: {}",
+            saved_solution.meta.command.replace(saved::SOURCE_FILE_PLACEHOLDER, &output_str)
+        );
+        Ok(())
+    }
+
+    fn note(&self, args: &ArgMatches) -> Result<()> {
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+        let metadata = match args.get_one::<String>("add") {
+            Some(text) => self.core.update_metadata(&handle, |metadata| metadata.add_note(text))?,
+            None if args.get_flag("clear") => {
+                self.core.update_metadata(&handle, |metadata| metadata.notes.clear())?
+            }
+            None => self.core.clash_metadata(&handle)?,
+        };
+        if metadata.notes.is_empty() {
+            println!("No notes for {handle}");
+        } else {
+            println!("{}", metadata.notes);
+        }
+        Ok(())
+    }
+
+    fn tag(&self, args: &ArgMatches) -> Result<()> {
+        if args.get_flag("list") {
+            let metadata_store = self.core.metadata()?;
+            for (tag, count) in metadata_store.tag_counts() {
+                println!("{tag} ({count})");
+            }
+            return Ok(())
         }
 
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+        let added: Vec<&String> = args.get_many::<String>("add").unwrap_or_default().collect();
+        let removed: Vec<&String> = args.get_many::<String>("remove").unwrap_or_default().collect();
+        for tag in &added {
+            metadata::validate_tag(tag)?;
+        }
+
+        let metadata = self.core.update_metadata(&handle, |metadata| {
+            metadata.tags.extend(added.into_iter().cloned());
+            metadata.tags.retain(|tag| !removed.contains(&tag));
+        })?;
+        if metadata.tags.is_empty() {
+            println!("No tags for {handle}");
+        } else {
+            println!("{}", metadata.tags.iter().cloned().collect::<Vec<_>>().join(" "));
+        }
         Ok(())
     }
 
+    fn favorite(&self, args: &ArgMatches) -> Result<()> {
+        self.set_flag(
+            args,
+            "favorite",
+            |metadata| metadata.favorite,
+            |metadata, value| metadata.favorite = value,
+        )
+    }
+
+    fn hide(&self, args: &ArgMatches) -> Result<()> {
+        self.set_flag(args, "hidden", |metadata| metadata.hidden, |metadata, value| metadata.hidden = value)
+    }
+
+    /// Sets (or with --remove unsets) a flag in the metadata of a clash, or
+    /// lists the clashes that have it with --list.
+    fn set_flag(
+        &self,
+        args: &ArgMatches,
+        flag_name: &str,
+        get: fn(&ClashMetadata) -> bool,
+        set: fn(&mut ClashMetadata, bool),
+    ) -> Result<()> {
+        if args.get_flag("list") {
+            let metadata_store = self.core.metadata()?;
+            for handle in metadata_store.handles_where(get) {
+                match self.core.read_clash(&handle) {
+                    Ok(clash) => println!("{handle}  {}", clash.title()),
+                    Err(_) => println!("{handle}"),
+                }
+            }
+            return Ok(())
+        }
+
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
+            None => self.current_handle()?,
+        };
+        let value = !args.get_flag("remove");
+        self.core.update_metadata(&handle, |metadata| set(metadata, value))?;
+        if value {
+            println!("Marked {handle} as {flag_name}");
+        } else {
+            println!("Unmarked {handle} as {flag_name}");
+        }
+        Ok(())
+    }
+
+    fn prev(&self, args: &ArgMatches) -> Result<()> {
+        let steps = *args.get_one::<u64>("STEPS").unwrap_or(&1) as usize;
+        let handle = self.core.previous_handle(steps)?;
+        println!(" Changed clash to https://codingame.com/contribute/view/{}", handle);
+        self.change_current_handle(&handle)
+    }
+
+    fn history(&self, args: &ArgMatches) -> Result<()> {
+        let limit = *args.get_one::<usize>("limit").unwrap_or(&20);
+        let ostyle = self.output_style(false);
+        let title_of = |handle: &PublicHandle| match self.core.read_clash(handle) {
+            Ok(clash) => clash.title().to_string(),
+            Err(_) => String::from("?"),
+        };
+
+        if args.get_flag("nav") {
+            let navigation = self.core.navigation()?;
+            if let Ok(handle) = self.current_handle() {
+                println!("{}  {handle}  {}", ostyle.bold.paint(" *"), title_of(&handle));
+            }
+            for (i, handle) in navigation.recent().iter().take(limit).enumerate() {
+                println!("{:>2}  {handle}  {}", i + 1, title_of(handle));
+            }
+            return Ok(())
+        }
+
+        let history = self.core.history();
+        if history.is_empty() {
+            println!("No runs yet");
+            return Ok(())
+        }
+        let now = game::unix_time_now();
+        for record in history.iter().rev().take(limit) {
+            let status = if record.passed {
+                ostyle.success.paint("PASS")
+            } else {
+                ostyle.failure.paint("FAIL")
+            };
+            let ago = format_time_ago(now.saturating_sub(record.timestamp));
+            println!("{status} {:>8}  {}  {}", ago, record.handle, title_of(&record.handle));
+        }
+        Ok(())
+    }
+
+    fn bookmark(&self, args: &ArgMatches) -> Result<()> {
+        // `coctus bookmark list` lists the bookmarks too ("list" is reserved)
+        let is_list = |name: &&String| {
+            *name == "list" && !args.get_flag("remove") && !args.contains_id("PUBLIC_HANDLE")
+        };
+        let Some(name) = args.get_one::<String>("NAME").filter(|name| !is_list(name)) else {
+            let navigation = self.core.navigation()?;
+            if navigation.bookmarks().is_empty() {
+                println!("No bookmarks");
+            }
+            for (name, handle) in navigation.bookmarks() {
+                println!("{name}  {handle}");
+            }
+            return Ok(())
+        };
+
+        if args.get_flag("remove") {
+            self.core.remove_bookmark(name)?;
+            println!("Removed bookmark {name}");
+        } else {
+            let handle = match self.handle_arg(args)? {
+                Some(h) => h,
+                None => self.current_handle()?,
+            };
+            self.core.add_bookmark(name, handle.clone())?;
+            println!("Bookmarked {handle} as {name}");
+        }
+        Ok(())
+    }
+
+    fn goto(&self, args: &ArgMatches) -> Result<()> {
+        let name = args.get_one::<String>("NAME").expect("clap should ensure NAME is given");
+        let handle = self.core.bookmark(name)?;
+        println!(" Changed clash to https://codingame.com/contribute/view/{}", handle);
+        self.change_current_handle(&handle)
+    }
+
     fn fetch(&self, args: &ArgMatches) -> Result<()> {
-        std::fs::create_dir_all(&self.clash_dir)?;
         let handles = args
-            .get_many::<PublicHandle>("PUBLIC_HANDLE")
+            .get_many::<HandleQuery>("PUBLIC_HANDLE")
             .with_context(|| "Should have many handles")?;
         for handle in handles {
-            let req = ureq::post("https://www.codingame.com/services/Contribution/findContribution")
-                .set("Content-Type", "application/json");
-            let content = match req.send_string(&format!(r#"["{}", true]"#, handle)) {
-                Err(ureq::Error::Status(status, res)) => {
-                    return Err(anyhow!("HTTP {} {} from {}", status, res.status_text(), res.get_url()))
-                }
-                res => res?.into_string()?,
-            };
-            let clash_file_path = self.clash_dir.join(format!("{}.json", handle));
-            std::fs::write(&clash_file_path, &content)?;
+            let handle = self.core.resolve(handle)?;
+            self.core.fetch(&handle)?;
+            let clash_file_path = library::clash_file(self.files().clash_dir(), &handle);
             println!("Saved clash {} as {}", &handle, &clash_file_path.display());
         }
         Ok(())
@@ -408,15 +1963,19 @@ impl App {
 
     fn showtests(&self, args: &ArgMatches) -> Result<()> {
         let handle = self.current_handle()?;
-        let clash = self.read_clash(&handle)?;
+        let clash = self.core.read_clash(&handle)?;
         let all_testcases = clash.testcases();
 
-        let show_whitespace = *args.get_one::<bool>("show-whitespace").unwrap_or(&false);
-        let ostyle = OutputStyle::from_env(show_whitespace);
+        let ostyle = self.output_style(self.show_whitespace(args, false));
+
+        let spoiler_free = args.get_one::<bool>("spoiler-free").copied().unwrap_or(self.config.spoiler_free);
 
         let num_testcases = all_testcases.len();
         let testcase_indices: Vec<u64> = match args.get_many::<u64>("TESTCASE") {
             Some(nums) => nums.cloned().collect(),
+            None if spoiler_free => {
+                all_testcases.iter().filter(|t| !t.is_validator).map(|t| t.index as u64).collect()
+            }
             None => (1u64..=num_testcases as u64).collect(),
         };
 
@@ -432,6 +1991,11 @@ impl App {
                     ))
                 }
             };
+            if spoiler_free && testcase.is_validator {
+                return Err(anyhow!(
+                    "Testcase {idx} is a validator (validators are hidden in spoiler-free mode)"
+                ))
+            }
 
             if !(only_in || only_out) {
                 let styled_title = ostyle.title.paint(format!("#{} {}", idx, testcase.title));
@@ -453,9 +2017,7 @@ impl App {
     }
 
     fn generate_stub(&self, args: &ArgMatches) -> Result<()> {
-        let lang_arg = args
-            .get_one::<String>("PROGRAMMING_LANGUAGE")
-            .context("Should have a programming language")?;
+        let lang_arg = self.language_arg(args)?;
 
         let stub_generator = match args.get_one::<PathBuf>("from-file") {
             Some(fname) if fname.to_str() == Some("-") => {
@@ -467,36 +2029,41 @@ impl App {
             None if args.get_flag("from-reference") => stub::SIMPLE_REFERENCE_STUB.to_owned(),
             None => {
                 let handle = self.current_handle()?;
-                self.read_clash(&handle)?
+                self.core
+                    .read_clash(&handle)?
                     .stub_generator()
                     .with_context(|| "Current clash provides no input stub generator")?
                     .to_owned()
             }
         };
 
-        // Language config files are stored in: (ordered by precedence)
-        // 1. The user config dir, where {CONF} is the OS dependent config folder:
-        //    `{CONF}/stub_templates/LANG/stub_config.toml`
-        // 2. This repo, embedded into the binary:
-        //    `config/stub_templates/LANG/stub_config.toml`
-        let lang_template_dir = self.stub_templates_dir.join(lang_arg);
-        let stub_string = if lang_template_dir.is_dir() {
-            let stub_config = StubConfig::read_from_dir(lang_template_dir)?;
-            stub::generate_from_config(stub_config, &stub_generator)?
-        } else {
-            stub::generate(lang_arg, &stub_generator)?
-        };
+        let stub_config = self.core.stub_config(lang_arg)?;
+        let stub_string = stub::generate_from_config(stub_config, &stub_generator)?;
         println!("{stub_string}");
         Ok(())
     }
 
+    /// Guess the programming language of a source file from its extension
+    /// using the `source_file_ext` of the stub templates.
+    fn language_of_file(&self, path: &std::path::Path) -> Option<String> {
+        let ext = path.extension()?.to_str()?;
+        self.core
+            .available_languages()
+            .into_iter()
+            .find(|lang| match self.core.stub_config(lang) {
+                Ok(stub_config) => stub_config.source_file_ext() == ext,
+                Err(_) => false,
+            })
+    }
+
     fn json(&self, args: &ArgMatches) -> Result<()> {
-        let handle = match args.get_one::<PublicHandle>("PUBLIC_HANDLE") {
-            Some(h) => h.to_owned(),
+        let handle = match self.handle_arg(args)? {
+            Some(h) => h,
             None => self.current_handle()?,
         };
-        let clash_file = self.clash_dir.join(format!("{}.json", handle));
-        let contents = std::fs::read_to_string(clash_file)
+        let contents = self
+            .files()
+            .read_clash(&handle)?
             .with_context(|| format!("Unable to find clash with handle {}", handle))?;
 
         println!("{}", &contents);
@@ -511,7 +2078,221 @@ impl App {
         let mut cmd = cli();
         let name = String::from(cmd.get_name());
         eprintln!("Generating {generator} completions...");
-        clap_complete::generate(generator, &mut cmd, name, &mut std::io::stdout());
+        let mut script = Vec::new();
+        clap_complete::generate(generator, &mut cmd, &name, &mut script);
+        let script = String::from_utf8(script).context("Generated completion is not valid UTF-8")?;
+        print!("{}", add_dynamic_completion(generator, script, &name));
+        Ok(())
+    }
+
+    fn serve(&self, _args: &ArgMatches) -> Result<()> {
+        let mut connection = Connection::stdio();
+        while let Some(request) = connection.receive()? {
+            let Some(id) = request.id else {
+                // Notifications from the client can be ignored, except for exit
+                if request.method == "exit" {
+                    break
+                }
+                continue
+            };
+            if request.method == "shutdown" {
+                connection.respond(id, Ok(Value::Null))?;
+                break
+            }
+            let result = self.handle_request(&mut connection, &id, &request.method, request.params);
+            connection.respond(id, result)?;
+        }
+        Ok(())
+    }
+
+    fn handle_request<R: BufRead, W: Write>(
+        &self,
+        connection: &mut Connection<R, W>,
+        id: &Value,
+        method: &str,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        match method {
+            "clash/current" => {
+                let params: HandleParams = parse_params(params)?;
+                Ok(self.clash_json(&self.served_handle(None)?, params.spoiler_free)?)
+            }
+            "clash/get" => {
+                let params: HandleParams = parse_params(params)?;
+                Ok(self.clash_json(&self.served_handle(params.handle.as_deref())?, params.spoiler_free)?)
+            }
+            "clash/list" => {
+                let clashes: Vec<Value> = self
+                    .core
+                    .stored_handles()?
+                    .iter()
+                    .map(|handle| {
+                        let title = self.core.read_clash(handle).map(|clash| clash.title().to_string()).ok();
+                        json!({ "handle": handle, "title": title })
+                    })
+                    .collect();
+                Ok(json!(clashes))
+            }
+            "clash/switch" => {
+                let params: HandleParams = parse_params(params)?;
+                let handle = match params.handle {
+                    Some(query) => self.core.resolve(&HandleQuery::from_str(&query)?)?,
+                    None => self.core.pick_handle(&ClashFilter::default(), self.strategy())?,
+                };
+                self.change_current_handle(&handle)?;
+                Ok(self.clash_json(&handle, params.spoiler_free)?)
+            }
+            "languages/list" => Ok(json!(self.core.available_languages())),
+            "stub/generate" => {
+                let params: StubParams = parse_params(params)?;
+                Ok(self.stub_json(params)?)
+            }
+            "tests/run" => self.serve_run_tests(connection, id, parse_params(params)?),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    fn lsp(&self, _args: &ArgMatches) -> Result<()> {
+        let stub_config = |language: &str| Ok(self.core.stub_config(language)?);
+        let preview_dir = self.data_dir.join("stub_preview");
+        let mut server = LanguageServer::new(self.core.available_languages(), &stub_config, preview_dir);
+        server.run(&mut Connection::stdio())
+    }
+
+    /// The clash that a request is about. The current clash is read again for
+    /// every request because it may have been changed by another coctus in
+    /// the meantime.
+    fn served_handle(&self, query: Option<&str>) -> Result<PublicHandle> {
+        if let Some(query) = query {
+            return Ok(self.core.resolve(&HandleQuery::from_str(query)?)?)
+        }
+        Ok(self.core.current_handle()?)
+    }
+
+    fn clash_json(&self, handle: &PublicHandle, spoiler_free: Option<bool>) -> Result<Value> {
+        let clash = self.core.read_clash(handle)?;
+        let spoiler_free = spoiler_free.unwrap_or(self.config.spoiler_free);
+        let testcases = clash
+            .testcases()
+            .iter()
+            .filter(|testcase| !(spoiler_free && testcase.is_validator))
+            .map(|testcase| {
+                let mut json = serde_json::to_value(testcase)?;
+                json["index"] = json!(testcase.index);
+                Ok(json)
+            })
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(json!({
+            "handle": handle,
+            "title": clash.title(),
+            "link": clash.codingame_link(),
+            "puzzleType": clash.puzzle_type().to_string(),
+            "modes": clash.modes(),
+            "statement": clash.statement(),
+            "inputDescription": clash.input_description(),
+            "outputDescription": clash.output_description(),
+            "constraints": clash.constraints(),
+            "stubGenerator": clash.stub_generator(),
+            "formattedStatement": OutputStyle::plain().format_statement(&clash),
+            "testcases": testcases,
+        }))
+    }
+
+    fn stub_json(&self, params: StubParams) -> Result<Value> {
+        let lang = params
+            .language
+            .or(self.config.default_language.clone())
+            .context("No language given (pass one or set default-language in the config)")?;
+        let handle = self.served_handle(params.handle.as_deref())?;
+        let clash = self.core.read_clash(&handle)?;
+        let generator = clash.stub_generator().context("The clash provides no input stub generator")?;
+        let stub_config = self.core.stub_config(&lang)?;
+        let source_file_ext = stub_config.source_file_ext().to_string();
+        let source_file = format!("solution.{source_file_ext}");
+        let run_command = stub_config.run_command(&source_file);
+        let build_command = stub_config.build_command(&source_file);
+        Ok(json!({
+            "language": lang,
+            "stub": stub::generate_from_config(stub_config, generator)?,
+            "sourceFileExt": source_file_ext,
+            "runCommand": run_command,
+            "buildCommand": build_command,
+        }))
+    }
+
+    /// Runs the tests like `coctus run`, sending the result of every testcase
+    /// as a `tests/result` notification before responding with the totals.
+    fn serve_run_tests<R: BufRead, W: Write>(
+        &self,
+        connection: &mut Connection<R, W>,
+        id: &Value,
+        params: RunTestsParams,
+    ) -> Result<Value, RpcError> {
+        let handle = self.served_handle(params.handle.as_deref())?;
+        let profile = self.core.run_profile(params.command, params.build_command, None)?;
+        let mut options = TestOptions::new(
+            timeout_from_argument(Some(&params.timeout.unwrap_or(self.config.timeout())))?,
+            timeout_from_argument(Some(&params.build_timeout.unwrap_or(self.config.build_timeout())))?,
+        );
+        options.rebuild = params.rebuild.unwrap_or(false);
+        options.testcases = params.testcases;
+        options.spoiler_free = params.spoiler_free.unwrap_or(self.config.spoiler_free);
+
+        let mut send_error = None;
+        let summary = self.core.run_tests(&handle, &profile, &options, |event| {
+            let sent = match event {
+                // STDOUT is reserved for the messages
+                TestEvent::Build(status) => print_build_status(&mut std::io::stderr(), status),
+                TestEvent::Result { testcase, result, .. } => {
+                    let notification = json!({
+                        "requestId": id,
+                        "index": testcase.index,
+                        "title": testcase.title,
+                        "result": result,
+                    });
+                    connection.notify("tests/result", notification)
+                }
+            };
+            match sent {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(err) => {
+                    send_error = Some(err);
+                    ControlFlow::Break(())
+                }
+            }
+        })?;
+        if let Some(err) = send_error {
+            return Err(err.into())
+        }
+        summary.warnings.into_iter().for_each(print_warning);
+
+        Ok(json!({ "passed": summary.passed, "total": summary.total }))
+    }
+
+    fn complete(&self, args: &ArgMatches) -> Result<()> {
+        let words: Vec<String> = args.get_many::<String>("WORDS").unwrap_or_default().cloned().collect();
+        let current = words.last().map_or("", String::as_str);
+        match completion_target(cli(), &words) {
+            Some(CompletionTarget::Handle) => {
+                let mut handles = self.files().known_handles();
+                handles.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                handles.dedup();
+                for handle in handles.iter().filter(|handle| handle.as_str().starts_with(current)) {
+                    let title = self
+                        .core
+                        .read_clash(handle)
+                        .map(|clash| clash.title().to_string())
+                        .unwrap_or_default();
+                    println!("{handle}\t{title}");
+                }
+            }
+            Some(CompletionTarget::Language) => {
+                for lang in self.core.available_languages().iter().filter(|lang| lang.starts_with(current)) {
+                    println!("{lang}");
+                }
+            }
+            None => (),
+        }
         Ok(())
     }
 }
@@ -520,18 +2301,58 @@ fn main() -> Result<()> {
     // We look for the locally stored clashes here:
     let project_dirs = ProjectDirs::from("", "CoCtus", "coctus").expect("Unable to find project directory");
 
-    let app = App::new(project_dirs.data_dir(), project_dirs.config_dir());
+    let matches = cli().get_matches();
+    // The doctor reports a broken config or workspace file instead of failing
+    let is_doctor = matches.subcommand_name() == Some("doctor");
+    // A broken config file can be fixed with `coctus config`
+    let is_config = matches.subcommand_name() == Some("config");
+    let config = match Config::load(&project_dirs.config_dir().join(CONFIG_FILE_NAME)) {
+        Err(_) if is_doctor || is_config => Config::default(),
+        config => config?,
+    };
+    // STDOUT is reserved for the messages of `coctus serve`
+    let command_output = match matches.subcommand_name() {
+        Some("serve") => CommandOutput::Stderr,
+        _ => CommandOutput::Inherit,
+    };
+    let data_dir = config.data_dir.clone().unwrap_or_else(|| project_dirs.data_dir().to_path_buf());
+    let library = matches.get_one::<String>("library").map(String::as_str);
+    let app = App::new(&data_dir, project_dirs.config_dir(), config, library, is_doctor, command_output)?;
 
-    match cli().get_matches().subcommand() {
+    match matches.subcommand() {
         Some(("show", args)) => app.show(args),
         Some(("next", args)) => app.next(args),
         Some(("status", args)) => app.status(args),
+        Some(("doctor", args)) => app.doctor(args),
+        Some(("serve", args)) => app.serve(args),
+        Some(("lsp", args)) => app.lsp(args),
+        Some(("library", args)) => app.library(args),
+        Some(("config", args)) => app.config(args),
+        Some(("init", args)) => app.init(args),
+        Some(("start", args)) => app.start(args),
+        Some(("clash", args)) => app.clash(args),
+        Some(("hotseat", args)) => app.hotseat(args),
+        Some(("leaderboard", args)) => app.leaderboard(args),
         Some(("run", args)) => app.run(args),
+        Some(("tui", args)) => app.tui(args),
+        Some(("verify", args)) => app.verify(args),
+        Some(("save", args)) => app.save(args),
+        Some(("solutions", args)) => app.solutions(args),
+        Some(("checkout", args)) => app.checkout(args),
+        Some(("prev", args)) => app.prev(args),
+        Some(("history", args)) => app.history(args),
+        Some(("bookmark", args)) => app.bookmark(args),
+        Some(("goto", args)) => app.goto(args),
+        Some(("note", args)) => app.note(args),
+        Some(("tag", args)) => app.tag(args),
+        Some(("favorite", args)) => app.favorite(args),
+        Some(("hide", args)) => app.hide(args),
         Some(("fetch", args)) => app.fetch(args),
         Some(("showtests", args)) => app.showtests(args),
         Some(("json", args)) => app.json(args),
         Some(("generate-stub", args)) => app.generate_stub(args),
         Some(("generate-shell-completion", args)) => app.generate_completions(args),
+        Some((COMPLETE_COMMAND, args)) => app.complete(args),
         _ => Err(anyhow!("unimplemented subcommand")),
     }
 }
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// `ClashMetadata` is what the user wrote down about a clash.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClashMetadata {
+    /// Free-text notes.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// Hidden clashes are never picked at random.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hidden: bool,
+}
+
+impl ClashMetadata {
+    pub fn is_empty(&self) -> bool {
+        self == &ClashMetadata::default()
+    }
+
+    /// Adds `text` as a new line of the notes.
+    pub fn add_note(&mut self, text: &str) {
+        if !self.notes.is_empty() {
+            self.notes.push('\n');
+        }
+        self.notes.push_str(text.trim_end());
+    }
+}
+
+/// Checks that `tag` is a single word that can be given on the command line
+/// without quoting.
+pub fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.chars().any(|c| c.is_whitespace() || c == ',') {
+        return Err(Error::InvalidInput(format!(
+            "Invalid tag {:?} (tags can not be empty or contain spaces or commas)",
+            tag
+        )))
+    }
+    Ok(())
+}
+
+/// `MetadataStore` holds the metadata of all clashes. It is stored as a JSON
+/// object with the handles as keys, clashes without metadata are left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MetadataStore {
+    entries: BTreeMap<String, ClashMetadata>,
+}
+
+impl MetadataStore {
+    /// Reads the store from `path`. A missing file is an empty store.
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).with_context(|| format!("Unable to deserialize {:?}", path))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(MetadataStore::default()),
+            Err(err) => Err(err).with_context(|| format!("Unable to read {:?}", path)),
+        }
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self).map_err(|err| Error::serialize("metadata", err))?;
+        std::fs::write(path, contents).with_context(|| format!("Unable to write {:?}", path))
+    }
+
+    /// The metadata of the clash with `handle` (empty if there is none).
+    pub fn get(&self, handle: &PublicHandle) -> ClashMetadata {
+        self.entries.get(&handle.to_string()).cloned().unwrap_or_default()
+    }
+
+    /// Changes the metadata of the clash with `handle` with `f`.
+    pub fn update<F: FnOnce(&mut ClashMetadata)>(&mut self, handle: &PublicHandle, f: F) {
+        let key = handle.to_string();
+        let metadata = self.entries.entry(key.clone()).or_default();
+        f(metadata);
+        if metadata.is_empty() {
+            self.entries.remove(&key);
+        }
+    }
+
+    /// Handles of the clashes whose metadata satisfies `predicate`, sorted.
+    pub fn handles_where<P: Fn(&ClashMetadata) -> bool>(&self, predicate: P) -> Vec<PublicHandle> {
+        self.entries
+            .iter()
+            .filter(|(_, metadata)| predicate(metadata))
+            .filter_map(|(handle, _)| PublicHandle::from_str(handle).ok())
+            .collect()
+    }
+
+    /// Number of clashes with each tag.
+    pub fn tag_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for tag in self.entries.values().flat_map(|metadata| &metadata.tags) {
+            *counts.entry(tag.as_str()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handle() -> PublicHandle {
+        PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap()
+    }
+
+    #[test]
+    fn test_update_removes_empty_entries() {
+        let mut store = MetadataStore::default();
+        store.update(&handle(), |metadata| metadata.hidden = true);
+        assert!(store.get(&handle()).hidden);
+        assert_eq!(store.handles_where(|metadata| metadata.hidden).len(), 1);
+
+        store.update(&handle(), |metadata| metadata.hidden = false);
+        assert!(store.entries.is_empty());
+    }
+
+    #[test]
+    fn test_serialization_skips_defaults() {
+        let mut store = MetadataStore::default();
+        store.update(&handle(), |metadata| {
+            metadata.add_note("Good for teaching regexes");
+            metadata.add_note("Watch out for the empty line");
+            metadata.tags.insert(String::from("regex"));
+        });
+        let json = serde_json::to_string(&store).unwrap();
+        assert_eq!(
+            json,
+            r#"{"90435e82d1d5e3fe5f9d3dd813770f0d5a7d2":{"notes":"Good for teaching regexes\nWatch out for the empty line","tags":["regex"]}}"#
+        );
+        let deserialized: MetadataStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.get(&handle()), store.get(&handle()));
+    }
+
+    #[test]
+    fn test_tag_counts() {
+        let mut store = MetadataStore::default();
+        let other = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap();
+        store.update(&handle(), |metadata| metadata.tags.extend(["math", "strings"].map(String::from)));
+        store.update(&other, |metadata| {
+            metadata.tags.insert(String::from("math"));
+        });
+        assert_eq!(store.tag_counts(), BTreeMap::from([("math", 2), ("strings", 1)]));
+    }
+
+    #[test]
+    fn test_validate_tag() {
+        assert!(validate_tag("dynamic-programming").is_ok());
+        assert!(validate_tag("").is_err());
+        assert!(validate_tag("two words").is_err());
+        assert!(validate_tag("a,b").is_err());
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// Maximum number of previously current clashes that are remembered.
+pub const MAX_RECENT: usize = 100;
+
+/// Names that can not be used for bookmarks because `coctus bookmark NAME`
+/// treats them as commands.
+pub const RESERVED_BOOKMARK_NAMES: [&str; 1] = ["list"];
+
+/// `Navigation` remembers which clashes were current before (most recent
+/// first) and the bookmarked clashes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Navigation {
+    recent: Vec<PublicHandle>,
+    bookmarks: BTreeMap<String, PublicHandle>,
+}
+
+impl Navigation {
+    /// Reads the navigation state from `path`. A missing file is an empty
+    /// state.
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).with_context(|| format!("Unable to deserialize {:?}", path))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Navigation::default()),
+            Err(err) => Err(err).with_context(|| format!("Unable to read {:?}", path)),
+        }
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).map_err(|err| Error::serialize("navigation", err))?;
+        std::fs::write(path, contents).with_context(|| format!("Unable to write {:?}", path))
+    }
+
+    /// Records that the current clash changed from `previous` to `next`.
+    /// Every clash appears at most once in the recent clashes, so going back
+    /// to the previous clash twice returns to where we started.
+    pub fn visit(&mut self, previous: Option<&PublicHandle>, next: &PublicHandle) {
+        self.recent.retain(|handle| handle != next && Some(handle) != previous);
+        if let Some(previous) = previous.filter(|previous| *previous != next) {
+            self.recent.insert(0, previous.clone());
+        }
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    /// Previously current clashes, most recent first (without the current
+    /// clash).
+    pub fn recent(&self) -> &[PublicHandle] {
+        &self.recent
+    }
+
+    /// Bookmarks `handle` as `name`, replacing the bookmark with the same
+    /// name (if any).
+    pub fn add_bookmark(&mut self, name: &str, handle: PublicHandle) -> Result<()> {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(Error::InvalidInput(format!(
+                "Invalid bookmark name {:?} (names can not be empty or contain spaces)",
+                name
+            )))
+        }
+        if RESERVED_BOOKMARK_NAMES.contains(&name) {
+            return Err(Error::InvalidInput(format!("{:?} can not be used as a bookmark name", name)))
+        }
+        self.bookmarks.insert(name.to_string(), handle);
+        Ok(())
+    }
+
+    pub fn remove_bookmark(&mut self, name: &str) -> Option<PublicHandle> {
+        self.bookmarks.remove(name)
+    }
+
+    pub fn bookmark(&self, name: &str) -> Option<&PublicHandle> {
+        self.bookmarks.get(name)
+    }
+
+    pub fn bookmarks(&self) -> &BTreeMap<String, PublicHandle> {
+        &self.bookmarks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn handles() -> [PublicHandle; 3] {
+        [
+            "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2",
+            "682102420fbce0fce95e0ee56095ea2b9924",
+            "1222536cec20519e1a630ecc8ada367dd708b",
+        ]
+        .map(|h| PublicHandle::from_str(h).unwrap())
+    }
+
+    #[test]
+    fn test_visit_keeps_most_recent_first() {
+        let [a, b, c] = handles();
+        let mut navigation = Navigation::default();
+        navigation.visit(None, &a);
+        navigation.visit(Some(&a), &b);
+        navigation.visit(Some(&b), &c);
+        assert_eq!(navigation.recent(), [b.clone(), a.clone()]);
+
+        // Going back to `a` makes `c` the most recent clash
+        navigation.visit(Some(&c), &a);
+        assert_eq!(navigation.recent(), [c.clone(), b.clone()]);
+    }
+
+    #[test]
+    fn test_visit_bounces_between_two_clashes() {
+        let [a, b, _] = handles();
+        let mut navigation = Navigation::default();
+        navigation.visit(Some(&a), &b);
+        navigation.visit(Some(&b), &a);
+        assert_eq!(navigation.recent(), std::slice::from_ref(&b));
+        navigation.visit(Some(&a), &b);
+        assert_eq!(navigation.recent(), std::slice::from_ref(&a));
+    }
+
+    #[test]
+    fn test_visit_same_clash() {
+        let [a, _, _] = handles();
+        let mut navigation = Navigation::default();
+        navigation.visit(Some(&a), &a);
+        assert!(navigation.recent().is_empty());
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let [a, b, _] = handles();
+        let mut navigation = Navigation::default();
+        navigation.add_bookmark("teaching", a).unwrap();
+        navigation.add_bookmark("teaching", b.clone()).unwrap();
+        assert!(navigation.add_bookmark("two words", b.clone()).is_err());
+        assert!(navigation.add_bookmark("", b.clone()).is_err());
+        assert!(navigation.add_bookmark("list", b.clone()).is_err());
+        assert_eq!(navigation.bookmark("teaching"), Some(&b));
+        assert_eq!(navigation.remove_bookmark("teaching"), Some(b));
+        assert!(navigation.bookmarks().is_empty());
+    }
+}
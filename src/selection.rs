@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::clash::{Clash, Mode, PublicHandle, PuzzleType};
+use crate::history::RunRecord;
+use crate::metadata::ClashMetadata;
+use crate::Error;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// `Strategy` decides which clash `coctus next` picks among the candidates.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::selection::Strategy;
+/// use std::str::FromStr;
+///
+/// assert_eq!(Strategy::from_str("retry-failed").unwrap(), Strategy::RetryFailed);
+/// assert_eq!(Strategy::Daily.to_string(), "daily");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Every clash is equally likely.
+    #[default]
+    Uniform,
+    /// Clashes with a better upvote/downvote ratio are more likely.
+    Weighted,
+    /// Clashes that were never run are picked first, then the ones that were
+    /// run the longest time ago.
+    Unseen,
+    /// Clashes that were failed come back after increasing intervals until
+    /// they are passed a few times in a row (spaced repetition).
+    RetryFailed,
+    /// The same clash for everyone during a day (UTC), as long as they have
+    /// the same clashes stored.
+    Daily,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 5] = [
+        Strategy::Uniform,
+        Strategy::Weighted,
+        Strategy::Unseen,
+        Strategy::RetryFailed,
+        Strategy::Daily,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Strategy::Uniform => "uniform",
+            Strategy::Weighted => "weighted",
+            Strategy::Unseen => "unseen",
+            Strategy::RetryFailed => "retry-failed",
+            Strategy::Daily => "daily",
+        }
+    }
+
+    /// Picks one of the `candidates`. `history` is the run history (oldest
+    /// first) and `now` the current time in seconds since the Unix epoch.
+    pub fn choose<'a>(
+        &self,
+        candidates: &'a [Candidate],
+        history: &[RunRecord],
+        now: u64,
+        rng: &mut impl Rng,
+    ) -> Option<&'a Candidate> {
+        match self {
+            Strategy::Uniform => candidates.choose(rng),
+            Strategy::Weighted => {
+                let weights = candidates.iter().map(Candidate::weight);
+                let index = WeightedIndex::new(weights).ok()?.sample(rng);
+                candidates.get(index)
+            }
+            Strategy::Unseen => {
+                let last_runs = last_runs(history);
+                let unseen: Vec<&Candidate> =
+                    candidates.iter().filter(|c| !last_runs.contains_key(&c.handle)).collect();
+                match unseen.choose(rng) {
+                    Some(candidate) => Some(candidate),
+                    None => candidates.iter().min_by_key(|c| last_runs.get(&c.handle)),
+                }
+            }
+            Strategy::RetryFailed => {
+                let due_dates = retry_due_dates(history);
+                candidates
+                    .iter()
+                    .filter_map(|c| due_dates.get(&c.handle).map(|due| (c, *due)))
+                    .filter(|(_, due)| *due <= now)
+                    .min_by_key(|(_, due)| *due)
+                    .map(|(candidate, _)| candidate)
+            }
+            Strategy::Daily => {
+                let mut sorted: Vec<&Candidate> = candidates.iter().collect();
+                sorted.sort_by_key(|c| c.handle.to_string());
+                let mut daily_rng = StdRng::seed_from_u64(now / SECONDS_PER_DAY);
+                sorted.choose(&mut daily_rng).copied()
+            }
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Strategy::ALL.into_iter().find(|strategy| strategy.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = Strategy::ALL.iter().map(Strategy::name).collect();
+            Error::InvalidInput(format!("Invalid strategy {:?} (expected one of: {})", s, names.join(", ")))
+        })
+    }
+}
+
+impl std::fmt::Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// `Candidate` is a clash that a [`Strategy`] can pick.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub handle: PublicHandle,
+    pub upvotes: i32,
+    pub downvotes: i32,
+}
+
+impl Candidate {
+    /// Share of upvotes with add-one smoothing, so that clashes without
+    /// votes get a weight of 0.5.
+    fn weight(&self) -> f64 {
+        let upvotes = self.upvotes.max(0) as f64;
+        let downvotes = self.downvotes.max(0) as f64;
+        (upvotes + 1.0) / (upvotes + downvotes + 2.0)
+    }
+}
+
+/// Criteria that a clash has to meet to be picked at random.
+#[derive(Debug, Clone, Default)]
+pub struct ClashFilter {
+    /// The clash has to have all of these modes.
+    pub modes: Vec<Mode>,
+    pub puzzle_type: Option<PuzzleType>,
+    /// The clash has to have all of these tags.
+    pub tags: Vec<String>,
+    pub favorites_only: bool,
+}
+
+impl ClashFilter {
+    pub fn with_modes(modes: Vec<Mode>) -> Self {
+        ClashFilter {
+            modes,
+            ..Default::default()
+        }
+    }
+
+    /// Returns true if clashes have to be read to check the filter.
+    pub fn needs_clash(&self) -> bool {
+        !self.modes.is_empty() || self.puzzle_type.is_some()
+    }
+
+    pub fn matches_metadata(&self, metadata: &ClashMetadata) -> bool {
+        !metadata.hidden
+            && (!self.favorites_only || metadata.favorite)
+            && self.tags.iter().all(|tag| metadata.tags.contains(tag))
+    }
+
+    pub fn matches(&self, clash: &Clash) -> bool {
+        self.modes.iter().all(|mode| clash.has_mode(*mode))
+            && self.puzzle_type.is_none_or(|puzzle_type| clash.puzzle_type() == puzzle_type)
+    }
+}
+
+/// Time of the latest run of every clash in `history`.
+fn last_runs(history: &[RunRecord]) -> HashMap<PublicHandle, u64> {
+    let mut last_runs = HashMap::new();
+    for record in history {
+        let last_run = last_runs.entry(record.handle.clone()).or_insert(record.timestamp);
+        *last_run = record.timestamp.max(*last_run);
+    }
+    last_runs
+}
+
+/// When each clash that was failed at some point is due for a retry. A
+/// failed clash is due right away, after passing it the interval grows (1, 3
+/// and 7 days) and after passing it 4 times in a row it is not retried
+/// anymore.
+fn retry_due_dates(history: &[RunRecord]) -> HashMap<PublicHandle, u64> {
+    const INTERVALS_IN_DAYS: [u64; 4] = [0, 1, 3, 7];
+
+    // (passes since the last failure, time of the last run)
+    let mut progress: HashMap<PublicHandle, (usize, u64)> = HashMap::new();
+    for record in history {
+        if !record.passed {
+            progress.insert(record.handle.clone(), (0, record.timestamp));
+        } else if let Some((passes, last_run)) = progress.get_mut(&record.handle) {
+            *passes += 1;
+            *last_run = record.timestamp;
+        }
+    }
+
+    progress
+        .into_iter()
+        .filter_map(|(handle, (passes, last_run))| {
+            let interval = INTERVALS_IN_DAYS.get(passes)?;
+            Some((handle, last_run + interval * SECONDS_PER_DAY))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HANDLES: [&str; 3] = [
+        "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2",
+        "682102420fbce0fce95e0ee56095ea2b9924",
+        "1222536cec20519e1a630ecc8ada367dd708b",
+    ];
+
+    fn handle(i: usize) -> PublicHandle {
+        PublicHandle::from_str(HANDLES[i]).unwrap()
+    }
+
+    fn candidates() -> Vec<Candidate> {
+        (0..HANDLES.len())
+            .map(|i| Candidate {
+                handle: handle(i),
+                upvotes: 0,
+                downvotes: 0,
+            })
+            .collect()
+    }
+
+    fn record(i: usize, passed: bool, timestamp: u64) -> RunRecord {
+        RunRecord {
+            handle: handle(i),
+            passed,
+            timestamp,
+        }
+    }
+
+    fn choose(
+        strategy: Strategy,
+        candidates: &[Candidate],
+        history: &[RunRecord],
+        now: u64,
+    ) -> Option<String> {
+        let mut rng = StdRng::seed_from_u64(0);
+        strategy.choose(candidates, history, now, &mut rng).map(|c| c.handle.to_string())
+    }
+
+    #[test]
+    fn test_weighted_prefers_upvoted() {
+        let mut candidates = candidates();
+        candidates[0].upvotes = 1000;
+        candidates[1].downvotes = 1_000_000_000;
+        candidates[2].downvotes = 1_000_000_000;
+        assert_eq!(choose(Strategy::Weighted, &candidates, &[], 0), Some(HANDLES[0].to_string()));
+    }
+
+    #[test]
+    fn test_unseen_first() {
+        let history = [record(0, true, 10), record(2, false, 20)];
+        assert_eq!(choose(Strategy::Unseen, &candidates(), &history, 30), Some(HANDLES[1].to_string()));
+    }
+
+    #[test]
+    fn test_unseen_falls_back_to_least_recent() {
+        let history = [
+            record(0, true, 10),
+            record(1, true, 5),
+            record(2, false, 20),
+            record(0, true, 30),
+        ];
+        assert_eq!(choose(Strategy::Unseen, &candidates(), &history, 40), Some(HANDLES[1].to_string()));
+    }
+
+    #[test]
+    fn test_retry_failed_spacing() {
+        let day = SECONDS_PER_DAY;
+        let history = [record(0, false, 0), record(1, true, 0), record(0, true, 10)];
+        assert_eq!(choose(Strategy::RetryFailed, &candidates(), &history, 20), None);
+        let due = Some(HANDLES[0].to_string());
+        assert_eq!(choose(Strategy::RetryFailed, &candidates(), &history, day + 10), due);
+
+        let history = [record(0, false, 0), record(2, false, 5)];
+        assert_eq!(
+            choose(Strategy::RetryFailed, &candidates(), &history, 10),
+            Some(HANDLES[0].to_string())
+        );
+    }
+
+    #[test]
+    fn test_retry_failed_stops_after_passing_repeatedly() {
+        let day = SECONDS_PER_DAY;
+        let mut history = vec![record(0, false, 0)];
+        history.extend((1..=4).map(|i| record(0, true, i * 10 * day)));
+        assert_eq!(choose(Strategy::RetryFailed, &candidates(), &history, 100 * day), None);
+    }
+
+    #[test]
+    fn test_daily_is_deterministic() {
+        let day = SECONDS_PER_DAY;
+        let mut reversed = candidates();
+        reversed.reverse();
+        let first = choose(Strategy::Daily, &candidates(), &[], 5 * day);
+        assert_eq!(first, choose(Strategy::Daily, &reversed, &[], 5 * day + 100));
+        let mut rng = rand::thread_rng();
+        let other = Strategy::Daily
+            .choose(&candidates(), &[], 5 * day, &mut rng)
+            .map(|c| c.handle.to_string());
+        assert_eq!(first, other);
+    }
+
+    #[test]
+    fn test_empty_candidates() {
+        for strategy in Strategy::ALL {
+            assert!(choose(strategy, &[], &[], 0).is_none());
+        }
+    }
+}
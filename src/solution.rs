@@ -1,7 +1,11 @@
+pub mod build;
+pub mod hint;
+pub mod saved;
 mod test_result;
 
 use std::io::Write;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::Duration;
 
 use test_result::CommandExit;
@@ -9,6 +13,83 @@ pub use test_result::TestResult;
 use wait_timeout::ChildExt;
 
 use crate::clash::Testcase;
+use crate::{Error, Result};
+
+/// Where the output of the commands that are not solutions (builds and
+/// hooks) goes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommandOutput {
+    /// To STDOUT and STDERR.
+    #[default]
+    Inherit,
+    /// All of it to STDERR, for when STDOUT is used for something else (like
+    /// the messages of a JSON-RPC server).
+    Stderr,
+    /// Nowhere, for when it would draw over a terminal UI.
+    Discard,
+}
+
+impl CommandOutput {
+    pub fn apply(&self, command: &mut Command) {
+        match self {
+            CommandOutput::Inherit => (),
+            CommandOutput::Stderr => {
+                command.stdout(std::io::stderr());
+            }
+            CommandOutput::Discard => {
+                command.stdout(Stdio::null()).stderr(Stdio::null());
+            }
+        }
+    }
+}
+
+/// Splits `command` into the program and its arguments like a shell would.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::solution::parse_command;
+///
+/// let command = parse_command("python3 'my solution.py'").unwrap();
+/// assert_eq!(command.get_program(), "python3");
+/// assert_eq!(command.get_args().collect::<Vec<_>>(), ["my solution.py"]);
+/// assert!(parse_command("").is_err());
+/// ```
+pub fn parse_command(command: &str) -> Result<Command> {
+    let words =
+        shlex::split(command).ok_or_else(|| Error::InvalidInput(format!("Invalid command {:?}", command)))?;
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| Error::InvalidInput(String::from("Commands can not be empty")))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    Ok(cmd)
+}
+
+/// The program of `command` if it can not be found. Programs with a path are
+/// looked up relative to `dir`, others in the `PATH`.
+pub fn missing_program(command: &str, dir: &Path) -> Option<String> {
+    let words = shlex::split(command)?;
+    let program = words.first()?;
+    let found = if program.contains(std::path::MAIN_SEPARATOR) || program.contains('/') {
+        dir.join(program).is_file()
+    } else {
+        let paths = std::env::var_os("PATH").unwrap_or_default();
+        std::env::split_paths(&paths).any(|path| is_program(&path, program))
+    };
+    match found {
+        true => None,
+        false => Some(program.clone()),
+    }
+}
+
+fn is_program(dir: &Path, program: &str) -> bool {
+    let candidates: [PathBuf; 2] = [
+        dir.join(program),
+        dir.join(format!("{program}{}", std::env::consts::EXE_SUFFIX)),
+    ];
+    candidates.iter().any(|candidate| candidate.is_file())
+}
 
 /// Run a command against testcases one at a time.
 ///
@@ -55,29 +136,38 @@ pub fn run_testcase(testcase: &Testcase, run_command: &mut Command, timeout: &Du
         .spawn()
     {
         Ok(run) => run,
-        Err(error) => {
-            let program = run_command.get_program().to_str().unwrap_or("Unable to run command");
-            let error_msg = format!("{}: {}", program, error);
-            return TestResult::UnableToRun { error_msg }
-        }
+        Err(error) => return unable_to_run(run_command, error),
     };
 
-    run.stdin
-        .as_mut()
-        .expect("STDIN of child process should be captured")
-        .write_all(testcase.test_in.as_bytes())
-        .expect("STDIN of child process should be writable");
+    if let Some(mut stdin) = run.stdin.take() {
+        match stdin.write_all(testcase.test_in.as_bytes()) {
+            // The solution exited (or closed STDIN) without reading all of the input
+            Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => (),
+            Err(error) => {
+                let _ = run.kill();
+                return unable_to_run(run_command, error)
+            }
+            Ok(()) => (),
+        }
+    }
 
-    let timed_out = run
-        .wait_timeout(*timeout)
-        .expect("Process should be able to wait for execution")
-        .is_none();
+    let timed_out = match run.wait_timeout(*timeout) {
+        Ok(status) => status.is_none(),
+        Err(error) => {
+            let _ = run.kill();
+            return unable_to_run(run_command, error)
+        }
+    };
 
     if timed_out {
-        run.kill().expect("Process should have been killed");
+        // Killing only fails if the process has exited already
+        let _ = run.kill();
     }
 
-    let output = run.wait_with_output().expect("Process should allow waiting for its execution");
+    let output = match run.wait_with_output() {
+        Ok(output) => output,
+        Err(error) => return unable_to_run(run_command, error),
+    };
 
     let exit_status = if timed_out {
         CommandExit::Timeout
@@ -89,6 +179,12 @@ pub fn run_testcase(testcase: &Testcase, run_command: &mut Command, timeout: &Du
     TestResult::from_output(&testcase.test_out, output.stdout, output.stderr, exit_status)
 }
 
+fn unable_to_run(run_command: &Command, error: std::io::Error) -> TestResult {
+    let program = run_command.get_program().to_str().unwrap_or("Unable to run command");
+    let error_msg = format!("{}: {}", program, error);
+    TestResult::UnableToRun { error_msg }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +210,31 @@ mod tests {
             .into_iter()
             .all(|(_, test_result)| !test_result.is_success()))
     }
+
+    #[test]
+    fn solution_that_ignores_input() {
+        // Bigger than a pipe buffer so that writing STDIN fails with a broken pipe
+        let testcase = Testcase {
+            index: 1,
+            title: String::from("Test #1"),
+            test_in: "x".repeat(1 << 20),
+            test_out: String::from("ok"),
+            is_validator: false,
+        };
+        let mut run_cmd = Command::new("true");
+        let result = run_testcase(&testcase, &mut run_cmd, &Duration::from_secs(5));
+        assert!(matches!(result, TestResult::WrongOutput { .. }), "{:?}", result);
+    }
+
+    #[test]
+    fn programs_are_found_in_path_and_dir() {
+        let dir: PathBuf = ["fixtures", "workspace"].iter().collect();
+        assert_eq!(missing_program("sh -c 'echo hi'", &dir), None);
+        assert_eq!(missing_program("./sol.sh", &dir), None);
+        assert_eq!(missing_program("./solution", &dir), Some(String::from("./solution")));
+        assert_eq!(
+            missing_program("coctus-no-such-compiler -O2 sol.c", &dir),
+            Some(String::from("coctus-no-such-compiler"))
+        );
+    }
 }
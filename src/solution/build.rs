@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use wait_timeout::ChildExt;
+
+/// Represents the outcome of running a build command.
+#[derive(Debug, Clone)]
+pub enum BuildResult {
+    /// Build command exited successfully.
+    Success,
+    /// Build command could not be started. This may happen for example if the
+    /// executable does not exist.
+    UnableToRun { error_msg: String },
+    /// Build command exited with a non-zero exit code (`None` if it was
+    /// terminated by a signal).
+    Failure { exit_code: Option<i32> },
+    /// Build command did not finish before the timeout and was killed.
+    Timeout,
+}
+
+impl BuildResult {
+    /// Returns true if the build command exited successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, BuildResult::Success)
+    }
+
+    /// Turns a build that did not succeed into an error. `elapsed` is how
+    /// long the build ran.
+    pub fn into_result(self, elapsed: Duration) -> Result<(), BuildError> {
+        match self {
+            BuildResult::Success => Ok(()),
+            BuildResult::UnableToRun { error_msg } => Err(BuildError::UnableToRun(error_msg)),
+            BuildResult::Failure { exit_code } => Err(BuildError::Failure(exit_code)),
+            BuildResult::Timeout => Err(BuildError::Timeout(elapsed)),
+        }
+    }
+}
+
+/// Why a build did not succeed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BuildError {
+    #[error("Unable to run build command: {0}")]
+    UnableToRun(String),
+    /// The exit code is `None` if the build was terminated by a signal.
+    #[error("Build failed{}", .0.map(|code| format!(" with exit code {code}")).unwrap_or_default())]
+    Failure(Option<i32>),
+    #[error("Build timed out after {0:.2?}")]
+    Timeout(Duration),
+}
+
+/// Run a build command. The command inherits STDOUT and STDERR so compiler
+/// diagnostics are shown live while the build is in progress.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::solution::build::{run_build, BuildResult};
+///
+/// let mut command = std::process::Command::new("true");
+/// let timeout = std::time::Duration::from_secs(5);
+///
+/// assert!(run_build(&mut command, &timeout).is_success());
+/// ```
+pub fn run_build(build_command: &mut Command, timeout: &Duration) -> BuildResult {
+    let mut build = match build_command.stdin(std::process::Stdio::null()).spawn() {
+        Ok(build) => build,
+        Err(error) => return unable_to_run(build_command, error),
+    };
+
+    match build.wait_timeout(*timeout) {
+        Ok(Some(status)) if status.success() => BuildResult::Success,
+        Ok(Some(status)) => BuildResult::Failure {
+            exit_code: status.code(),
+        },
+        Ok(None) => {
+            // Killing only fails if the process has exited already
+            let _ = build.kill();
+            let _ = build.wait();
+            BuildResult::Timeout
+        }
+        Err(error) => {
+            let _ = build.kill();
+            unable_to_run(build_command, error)
+        }
+    }
+}
+
+fn unable_to_run(build_command: &Command, error: std::io::Error) -> BuildResult {
+    let program = build_command.get_program().to_str().unwrap_or("Unable to run command");
+    let error_msg = format!("{}: {}", program, error);
+    BuildResult::UnableToRun { error_msg }
+}
+
+/// Compute a fingerprint of a build from the build command and the contents
+/// of the source files it depends on. If the fingerprint of a build matches
+/// the one of the previous successful build, building again can be skipped.
+///
+/// The fingerprint is stored between runs, so it is computed with FNV-1a
+/// which (unlike the hashers of the standard library) always gives the same
+/// result.
+pub fn fingerprint(build_command: &str, sources: &[PathBuf]) -> std::io::Result<String> {
+    let mut hasher = Fnv1a::new();
+    hasher.write(build_command.as_bytes());
+    for source in sources {
+        hasher.write(source.to_string_lossy().as_bytes());
+        hasher.write(&std::fs::read(source)?);
+    }
+    Ok(format!("{:016x}", hasher.0))
+}
+
+/// 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Hashes `bytes` prefixed with their length, so that the boundaries
+    /// between the written values are part of the hash.
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// A successful build, remembered so that building again can be skipped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedBuild {
+    pub fingerprint: String,
+    /// Files that the build created or updated, with their modification
+    /// times.
+    pub artifacts: BTreeMap<PathBuf, SystemTime>,
+}
+
+impl CachedBuild {
+    /// A build with `fingerprint` that started at `start_time` in `dir`. The
+    /// files in `dir` that were modified since are taken as its artifacts.
+    pub fn new(fingerprint: String, dir: &Path, start_time: SystemTime) -> Self {
+        // File systems may store modification times with a precision as low
+        // as 2 seconds
+        let since = start_time.checked_sub(Duration::from_secs(2)).unwrap_or(start_time);
+        let artifacts = match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    let modified = modified_time(&path)?;
+                    (modified >= since).then_some((path, modified))
+                })
+                .collect(),
+            Err(_) => BTreeMap::new(),
+        };
+        CachedBuild {
+            fingerprint,
+            artifacts,
+        }
+    }
+
+    /// Returns true if the build with `fingerprint` does not need to run
+    /// again: the sources are unchanged and the artifacts were not deleted or
+    /// modified since.
+    pub fn is_up_to_date(&self, fingerprint: &str) -> bool {
+        self.fingerprint == fingerprint
+            && self.artifacts.iter().all(|(path, modified)| modified_time(path) == Some(*modified))
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_failure() {
+        let timeout = Duration::from_secs(1);
+        let result = run_build(&mut Command::new("false"), &timeout);
+        assert!(matches!(result, BuildResult::Failure { exit_code: Some(1) }));
+    }
+
+    #[test]
+    fn test_build_timeout() {
+        let timeout = Duration::from_millis(100);
+        let mut build_cmd = Command::new("sleep");
+        build_cmd.arg("5");
+        assert!(matches!(run_build(&mut build_cmd, &timeout), BuildResult::Timeout));
+    }
+
+    #[test]
+    fn test_build_unable_to_run() {
+        let timeout = Duration::from_secs(1);
+        let result = run_build(&mut Command::new("./this-command-does-not-exist"), &timeout);
+        assert!(matches!(result, BuildResult::UnableToRun { .. }));
+    }
+
+    #[test]
+    fn test_fingerprint_depends_on_command_and_sources() {
+        let source: PathBuf = ["fixtures", "puzzles", "stub_and_solution_tester.json"].iter().collect();
+        let sources = [source];
+        let fp = fingerprint("gcc sol.c", &sources).unwrap();
+        assert_eq!(fp, fingerprint("gcc sol.c", &sources).unwrap());
+        assert_ne!(fp, fingerprint("gcc -O2 sol.c", &sources).unwrap());
+        assert_ne!(fp, fingerprint("gcc sol.c", &[]).unwrap());
+    }
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint("gcc sol.c", &[]).unwrap(), "db6c739c69117cd2");
+    }
+
+    #[test]
+    fn test_cached_build_checks_artifacts() {
+        let dir = std::env::temp_dir().join(format!("coctus-build-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let start_time = SystemTime::now();
+        std::fs::write(dir.join("a.out"), "binary").unwrap();
+
+        let cached_build = CachedBuild::new(String::from("fp"), &dir, start_time);
+        assert_eq!(cached_build.artifacts.len(), 1);
+        assert!(cached_build.is_up_to_date("fp"));
+        assert!(!cached_build.is_up_to_date("other"));
+
+        std::fs::remove_file(dir.join("a.out")).unwrap();
+        assert!(!cached_build.is_up_to_date("fp"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fingerprint_missing_source() {
+        assert!(fingerprint("gcc sol.c", &["this-file-does-not-exist.c".into()]).is_err());
+    }
+}
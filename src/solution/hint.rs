@@ -0,0 +1,169 @@
+use itertools::Itertools;
+
+/// Explanation for a common reason why the output of a solution did not match
+/// the expected output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hint {
+    /// The output is the expected output of the previous testcase.
+    OutputOfPreviousTestcase,
+    /// The output only differs in letter case.
+    WrongCase,
+    /// The output only differs in whitespace at the end of lines.
+    TrailingWhitespace,
+    /// The output only differs in blank lines.
+    BlankLines,
+    /// All numbers have the correct value but are formatted differently.
+    NumberFormatting { expected: String, actual: String },
+    /// The output has the correct lines in the wrong order.
+    LineOrder,
+}
+
+impl std::fmt::Display for Hint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Hint::OutputOfPreviousTestcase => {
+                write!(f, "the output is the expected output of the previous testcase")
+            }
+            Hint::WrongCase => write!(f, "the output only differs in letter case"),
+            Hint::TrailingWhitespace => write!(f, "the output only differs in trailing whitespace"),
+            Hint::BlankLines => write!(f, "the output has extra or missing blank lines"),
+            Hint::NumberFormatting { expected, actual } => write!(
+                f,
+                "numbers have the right value but are formatted differently (expected {expected} but got {actual})"
+            ),
+            Hint::LineOrder => write!(f, "the output has the right lines in the wrong order"),
+        }
+    }
+}
+
+/// Looks for a simple explanation of why `actual` output did not match the
+/// `expected` output. `previous_expected` is the expected output of the
+/// testcase that was run before this one (if any).
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::solution::hint::{explain_wrong_output, Hint};
+///
+/// assert_eq!(explain_wrong_output("Hello", "HELLO", None), Some(Hint::WrongCase));
+/// assert_eq!(explain_wrong_output("Hello", "Goodbye", None), None);
+/// ```
+pub fn explain_wrong_output(expected: &str, actual: &str, previous_expected: Option<&str>) -> Option<Hint> {
+    let expected = expected.trim_end();
+    let actual = actual.trim_end();
+
+    if expected == actual || actual.is_empty() {
+        return None
+    }
+
+    if previous_expected.is_some_and(|prev| prev.trim_end() == actual) {
+        return Some(Hint::OutputOfPreviousTestcase)
+    }
+
+    if expected.to_lowercase() == actual.to_lowercase() {
+        return Some(Hint::WrongCase)
+    }
+
+    if expected.lines().map(str::trim_end).eq(actual.lines().map(str::trim_end)) {
+        return Some(Hint::TrailingWhitespace)
+    }
+
+    let non_blank_lines =
+        |s: &str| s.lines().filter(|line| !line.trim().is_empty()).map(String::from).collect_vec();
+    if non_blank_lines(expected) == non_blank_lines(actual) {
+        return Some(Hint::BlankLines)
+    }
+
+    if let Some(hint) = explain_number_formatting(expected, actual) {
+        return Some(hint)
+    }
+
+    if expected.lines().count() > 1 && expected.lines().sorted().eq(actual.lines().sorted()) {
+        return Some(Hint::LineOrder)
+    }
+
+    None
+}
+
+fn explain_number_formatting(expected: &str, actual: &str) -> Option<Hint> {
+    let expected_tokens = expected.split_whitespace().collect_vec();
+    let actual_tokens = actual.split_whitespace().collect_vec();
+
+    if expected_tokens.len() != actual_tokens.len() {
+        return None
+    }
+
+    let mut first_difference = None;
+    for (exp, act) in expected_tokens.into_iter().zip(actual_tokens) {
+        if exp == act {
+            continue
+        }
+        match (exp.parse::<f64>(), act.parse::<f64>()) {
+            (Ok(x), Ok(y)) if x == y => {
+                first_difference.get_or_insert((exp, act));
+            }
+            _ => return None,
+        }
+    }
+
+    first_difference.map(|(expected, actual)| Hint::NumberFormatting {
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hint_output_of_previous_testcase() {
+        let hint = explain_wrong_output("42", "17", Some("17\n"));
+        assert_eq!(hint, Some(Hint::OutputOfPreviousTestcase));
+    }
+
+    #[test]
+    fn hint_wrong_case() {
+        assert_eq!(explain_wrong_output("True\nFalse", "true\nfalse", None), Some(Hint::WrongCase));
+    }
+
+    #[test]
+    fn hint_trailing_whitespace() {
+        let hint = explain_wrong_output("a b\nc", "a b \nc", None);
+        assert_eq!(hint, Some(Hint::TrailingWhitespace));
+    }
+
+    #[test]
+    fn hint_blank_lines() {
+        assert_eq!(explain_wrong_output("a\nb", "a\n\nb", None), Some(Hint::BlankLines));
+        assert_eq!(explain_wrong_output("a\n\nb", "a\nb", None), Some(Hint::BlankLines));
+    }
+
+    #[test]
+    fn hint_number_formatting() {
+        let hint = explain_wrong_output("1 2.50\n3", "1 2.5\n3.0", None);
+        assert_eq!(
+            hint,
+            Some(Hint::NumberFormatting {
+                expected: String::from("2.50"),
+                actual: String::from("2.5")
+            })
+        );
+    }
+
+    #[test]
+    fn hint_number_formatting_requires_equal_values() {
+        assert_eq!(explain_wrong_output("2.50", "2.51", None), None);
+    }
+
+    #[test]
+    fn hint_line_order() {
+        assert_eq!(explain_wrong_output("a\nb\nc", "c\na\nb", None), Some(Hint::LineOrder));
+    }
+
+    #[test]
+    fn no_hint_for_unrelated_output() {
+        assert_eq!(explain_wrong_output("a\nb\nc", "x\ny", Some("z")), None);
+        assert_eq!(explain_wrong_output("abc", "", None), None);
+    }
+}
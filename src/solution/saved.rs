@@ -0,0 +1,349 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// Name of the file that describes a saved solution.
+pub const SOLUTION_META_FILE: &str = "solution.toml";
+
+/// Placeholder that gets replaced with the path to the source file of a saved
+/// solution in its commands.
+pub const SOURCE_FILE_PLACEHOLDER: &str = "{file}";
+
+/// `SolutionMeta` describes how a saved solution is built and run. It is
+/// stored as `solution.toml` next to the source file of the solution.
+///
+/// # Examples
+///
+/// ```toml
+/// file = "sol.c"
+/// command = "./sol"
+/// build_command = "gcc -o sol {file}"
+/// passed = true
+/// chars = 123
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolutionMeta {
+    /// `file` is the name of the source file (relative to the solution
+    /// directory).
+    pub file: String,
+    /// `command` executes the solution.
+    pub command: String,
+    /// `build_command` compiles the solution (if needed).
+    pub build_command: Option<String>,
+    /// `passed` is true if the saved version passed all testcases (including
+    /// validators) the last time it was tested, `None` if it was never tested.
+    #[serde(default)]
+    pub passed: Option<bool>,
+    /// `chars` is the number of characters in the source file (this is how
+    /// code length is measured in shortest mode on CodinGame).
+    #[serde(default)]
+    pub chars: usize,
+}
+
+/// `SavedSolution` is a solution to a clash in a specific programming
+/// language. Saved solutions are stored in the solutions directory as
+/// `{HANDLE}/{LANGUAGE}/solution.toml` along with the source file.
+#[derive(Debug, Clone)]
+pub struct SavedSolution {
+    pub handle: PublicHandle,
+    pub language: String,
+    pub dir: PathBuf,
+    pub meta: SolutionMeta,
+}
+
+impl SavedSolution {
+    /// Read the solution stored in `dir`.
+    pub fn read_from_dir(handle: PublicHandle, language: &str, dir: PathBuf) -> Result<Self> {
+        let meta_file = dir.join(SOLUTION_META_FILE);
+        let contents = std::fs::read_to_string(&meta_file)
+            .with_context(|| format!("Unable to read {:?}", &meta_file))?;
+        let meta: SolutionMeta =
+            toml::from_str(&contents).with_context(|| format!("Unable to deserialize {:?}", &meta_file))?;
+        Ok(Self {
+            handle,
+            language: language.to_string(),
+            dir,
+            meta,
+        })
+    }
+
+    /// Store a copy of `source_file` in `dir` as the solution to the clash
+    /// with `handle` in `language`. `dir` is created if needed, and files
+    /// that it already contains are left alone.
+    pub fn write_to_dir(
+        dir: PathBuf,
+        handle: PublicHandle,
+        language: &str,
+        source_file: &Path,
+        command: String,
+        build_command: Option<String>,
+    ) -> Result<Self> {
+        if command.trim().is_empty() {
+            return Err(Error::InvalidInput(String::from("The command of a saved solution can't be empty")))
+        }
+        let source = std::fs::read_to_string(source_file)
+            .with_context(|| format!("Unable to read {:?}", source_file))?;
+        let file = source_file
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| Error::InvalidInput(format!("Invalid solution file name {:?}", source_file)))?
+            .to_string();
+
+        std::fs::create_dir_all(&dir).with_context(|| format!("Unable to create {:?}", &dir))?;
+        let target = dir.join(&file);
+        std::fs::write(&target, &source).with_context(|| format!("Unable to write {:?}", &target))?;
+
+        let saved_solution = Self {
+            handle,
+            language: language.to_string(),
+            dir,
+            meta: SolutionMeta {
+                file,
+                command,
+                build_command,
+                passed: None,
+                chars: source.chars().count(),
+            },
+        };
+        saved_solution.write_meta()?;
+        Ok(saved_solution)
+    }
+
+    /// Move the solution (with everything else in its directory) to `dir`,
+    /// replacing what was stored there.
+    pub fn move_to(&mut self, dir: PathBuf) -> Result<()> {
+        match std::fs::remove_dir_all(&dir) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            result => result.with_context(|| format!("Unable to remove {:?}", &dir))?,
+        }
+        if let Some(parent) = dir.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Unable to create {:?}", parent))?;
+        }
+        std::fs::rename(&self.dir, &dir)
+            .with_context(|| format!("Unable to move {:?} to {:?}", &self.dir, &dir))?;
+        self.dir = dir;
+        Ok(())
+    }
+
+    /// Write `solution.toml` of the saved solution.
+    pub fn write_meta(&self) -> Result<()> {
+        let meta_file = self.dir.join(SOLUTION_META_FILE);
+        let contents = toml::to_string(&self.meta).map_err(|err| Error::serialize("solution meta", err))?;
+        std::fs::write(&meta_file, contents).with_context(|| format!("Unable to write {:?}", &meta_file))
+    }
+
+    pub fn source_path(&self) -> PathBuf {
+        self.dir.join(&self.meta.file)
+    }
+
+    /// The command that executes the solution with `{file}` replaced by the
+    /// path to the source file.
+    pub fn command(&self) -> String {
+        self.expand_placeholder(&self.meta.command)
+    }
+
+    /// The command that compiles the solution with `{file}` replaced by the
+    /// path to the source file.
+    pub fn build_command(&self) -> Option<String> {
+        self.meta.build_command.as_ref().map(|cmd| self.expand_placeholder(cmd))
+    }
+
+    fn expand_placeholder(&self, cmd: &str) -> String {
+        let source_path = self.source_path();
+        let source_path = source_path.canonicalize().unwrap_or(source_path);
+        let source_path = source_path.to_string_lossy();
+        let quoted_path = shlex::try_quote(&source_path).unwrap_or(source_path.clone());
+        cmd.replace(SOURCE_FILE_PLACEHOLDER, &quoted_path)
+    }
+}
+
+/// `BrokenSolution` is a solution in the solutions store whose
+/// `solution.toml` could not be read.
+#[derive(Debug)]
+pub struct BrokenSolution {
+    pub handle: PublicHandle,
+    pub language: String,
+    pub dir: PathBuf,
+    pub error: Error,
+}
+
+/// A solution in the solutions store, which may be broken.
+pub type StoredSolution = std::result::Result<SavedSolution, BrokenSolution>;
+
+/// Lists all solutions stored in `solutions_dir`, sorted by handle and
+/// language. Directories that do not look like saved solutions are skipped.
+/// A solution that can not be read is listed as a [BrokenSolution] so that
+/// it doesn't hide the other ones.
+pub fn saved_solutions(solutions_dir: &Path) -> Result<Vec<StoredSolution>> {
+    let mut solutions = Vec::new();
+
+    if !solutions_dir.is_dir() {
+        return Ok(solutions)
+    }
+
+    let read_error = || format!("Unable to read {:?}", solutions_dir);
+    for handle_entry in std::fs::read_dir(solutions_dir).with_context(read_error)? {
+        let handle_entry = handle_entry.with_context(read_error)?;
+        let Some(handle) = handle_entry.file_name().to_str().and_then(|s| PublicHandle::from_str(s).ok())
+        else {
+            continue
+        };
+        if !handle_entry.path().is_dir() {
+            continue
+        }
+        solutions.extend(saved_solutions_for_handle(solutions_dir, &handle)?);
+    }
+
+    solutions.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+    Ok(solutions)
+}
+
+/// Directory of the solution to the clash with `handle` in `language` in
+/// `solutions_dir`.
+pub fn solution_dir(solutions_dir: &Path, handle: &PublicHandle, language: &str) -> PathBuf {
+    solutions_dir.join(handle.as_str()).join(language)
+}
+
+/// The language of a stored solution, whether it is broken or not.
+pub fn stored_language(solution: &StoredSolution) -> &str {
+    match solution {
+        Ok(solution) => &solution.language,
+        Err(broken) => &broken.language,
+    }
+}
+
+fn sort_key(solution: &StoredSolution) -> (&str, &str) {
+    match solution {
+        Ok(solution) => (solution.handle.as_str(), &solution.language),
+        Err(broken) => (broken.handle.as_str(), &broken.language),
+    }
+}
+
+/// Lists the solutions to the clash with `handle` stored in `solutions_dir`,
+/// sorted by language.
+pub fn saved_solutions_for_handle(
+    solutions_dir: &Path,
+    handle: &PublicHandle,
+) -> Result<Vec<StoredSolution>> {
+    let handle_dir = solutions_dir.join(handle.to_string());
+    let mut solutions = Vec::new();
+
+    if !handle_dir.is_dir() {
+        return Ok(solutions)
+    }
+
+    let read_error = || format!("Unable to read {:?}", &handle_dir);
+    for lang_entry in std::fs::read_dir(&handle_dir).with_context(read_error)? {
+        let lang_dir = lang_entry.with_context(read_error)?.path();
+        let Some(language) = lang_dir.file_name().and_then(|s| s.to_str()).map(String::from) else {
+            continue
+        };
+        if lang_dir.join(SOLUTION_META_FILE).is_file() {
+            let solution =
+                SavedSolution::read_from_dir(handle.clone(), &language, lang_dir.clone()).map_err(|error| {
+                    BrokenSolution {
+                        handle: handle.clone(),
+                        language,
+                        dir: lang_dir,
+                        error,
+                    }
+                });
+            solutions.push(solution);
+        }
+    }
+
+    solutions.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
+    Ok(solutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solutions_dir() -> PathBuf {
+        ["fixtures", "solutions"].iter().collect()
+    }
+
+    #[test]
+    fn test_saved_solutions() {
+        let solutions: Vec<SavedSolution> =
+            saved_solutions(&solutions_dir()).unwrap().into_iter().map(|s| s.unwrap()).collect();
+        let languages: Vec<&str> = solutions.iter().map(|s| s.language.as_str()).collect();
+        assert_eq!(languages, vec!["bash", "python"]);
+        assert!(solutions
+            .iter()
+            .all(|s| s.handle.to_string() == "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2"));
+    }
+
+    #[test]
+    fn test_saved_solution_commands_expand_placeholder() {
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let solutions = saved_solutions_for_handle(&solutions_dir(), &handle).unwrap();
+        let bash = solutions[0].as_ref().unwrap();
+        let expected_path = solutions_dir().join(handle.to_string()).join("bash").join("sol.sh");
+        assert_eq!(bash.source_path(), expected_path);
+        let absolute_path = expected_path.canonicalize().unwrap();
+        assert_eq!(bash.command(), format!("bash {}", absolute_path.display()));
+        assert_eq!(bash.build_command(), None);
+    }
+
+    #[test]
+    fn test_saved_solution_meta_defaults() {
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let solutions = saved_solutions_for_handle(&solutions_dir(), &handle).unwrap();
+        let python = solutions[1].as_ref().unwrap();
+        assert_eq!(python.meta.passed, Some(true));
+        assert_eq!(python.meta.chars, 33);
+        let bash = solutions[0].as_ref().unwrap();
+        assert_eq!(bash.meta.passed, None);
+        assert_eq!(bash.meta.chars, 0);
+    }
+
+    #[test]
+    fn test_broken_solutions_are_listed() {
+        let dir = std::env::temp_dir().join(format!("coctus-saved-solutions-{}", std::process::id()));
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let solution_dir = dir.join(handle.as_str());
+        for language in ["bash", "ruby"] {
+            std::fs::create_dir_all(solution_dir.join(language)).unwrap();
+        }
+        std::fs::write(solution_dir.join("bash").join(SOLUTION_META_FILE), "file = \"sol.sh\"").unwrap();
+        std::fs::write(solution_dir.join("ruby").join("sol.rb"), "puts 1").unwrap();
+        SavedSolution::write_to_dir(
+            solution_dir.join("ruby"),
+            handle.clone(),
+            "ruby",
+            &solution_dir.join("ruby").join("sol.rb"),
+            String::from("ruby {file}"),
+            None,
+        )
+        .unwrap();
+
+        let solutions = saved_solutions(&dir).unwrap();
+        assert_eq!(solutions.len(), 2);
+        assert_eq!(solutions[0].as_ref().unwrap_err().language, "bash");
+        assert_eq!(solutions[1].as_ref().unwrap().language, "ruby");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_requires_command() {
+        let handle = PublicHandle::from_str("90435e82d1d5e3fe5f9d3dd813770f0d5a7d2").unwrap();
+        let source = solution_dir(&solutions_dir(), &handle, "bash").join("sol.sh");
+        let dir = PathBuf::from("this-dir-does-not-exist");
+        let command = String::from(" ");
+        assert!(SavedSolution::write_to_dir(dir.clone(), handle, "bash", &source, command, None).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_saved_solutions_missing_dir() {
+        let solutions = saved_solutions(Path::new("this-dir-does-not-exist")).unwrap();
+        assert!(solutions.is_empty());
+    }
+}
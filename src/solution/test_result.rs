@@ -1,3 +1,5 @@
+use serde::Serialize;
+
 pub enum CommandExit {
     Ok,
     Error,
@@ -7,7 +9,8 @@ pub enum CommandExit {
 /// Represents the outcome of running a testcase. [TestResult::Success] means
 /// the output of a solution command matched the `test_out` field of the
 /// [Testcase](crate::clash::Testcase).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase", rename_all_fields = "camelCase")]
 pub enum TestResult {
     /// Solution command produced the expected output. A test run is considered
     /// a success even if it runs into a runtime error or times out if its
@@ -132,4 +135,15 @@ mod tests {
             other => panic!("expected TestResult::RuntimeError but found {:?}", other),
         }
     }
+
+    #[test]
+    fn test_testresult_serializes_with_status_tag() {
+        let result = TestResult::UnableToRun {
+            error_msg: String::from("nope"),
+        };
+        let json = serde_json::to_value(result).unwrap();
+        assert_eq!(json, serde_json::json!({"status": "unableToRun", "errorMsg": "nope"}));
+        let json = serde_json::to_value(TestResult::Success).unwrap();
+        assert_eq!(json, serde_json::json!({"status": "success"}));
+    }
 }
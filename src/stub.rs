@@ -1,15 +1,18 @@
+pub mod analysis;
 mod language;
 mod parser;
 mod preprocessor;
 mod renderer;
 mod stub_config;
 
-use anyhow::Result;
 use indoc::indoc;
 use language::Language;
+pub use parser::{ParseError, ParseErrorKind};
 use preprocessor::Renderable;
 use serde::Serialize;
-pub use stub_config::StubConfig;
+pub use stub_config::{embedded_languages, ConfigError, StubConfig};
+
+use crate::Result;
 
 pub fn generate_from_config(config: StubConfig, generator: &str) -> Result<String> {
     let mut stub = parser::parse_generator_stub(generator)?;
@@ -19,11 +22,18 @@ pub fn generate_from_config(config: StubConfig, generator: &str) -> Result<Strin
     }
 
     let renderer = renderer::Renderer::new(config, stub)?;
-    let output_str = renderer.render();
+    let output_str = renderer.render()?;
 
     Ok(output_str.as_str().trim().to_string())
 }
 
+/// Checks that `config` can generate a stub for the reference stub
+/// generator, which uses every kind of stub command.
+pub fn check_stub_config(config: StubConfig) -> Result<()> {
+    generate_from_config(config, SIMPLE_REFERENCE_STUB)?;
+    Ok(())
+}
+
 /// Generate a stub string from a (supported) language and a generator.
 ///
 /// # Examples
@@ -68,21 +78,21 @@ enum VarType {
 }
 
 impl<'a> VarType {
-    fn new_unsized(value: &'a str) -> Self {
+    fn new_unsized(value: &'a str) -> Option<Self> {
         match value {
-            "int" => VarType::Int,
-            "float" => VarType::Float,
-            "long" => VarType::Long,
-            "bool" => VarType::Bool,
-            other => panic!("No unsized variable type: {other}"),
+            "int" => Some(VarType::Int),
+            "float" => Some(VarType::Float),
+            "long" => Some(VarType::Long),
+            "bool" => Some(VarType::Bool),
+            _ => None,
         }
     }
 
-    fn new_sized(value: &'a str) -> Self {
+    fn new_sized(value: &'a str) -> Option<Self> {
         match value {
-            "word" => VarType::Word,
-            "string" => VarType::String,
-            other => panic!("No sized variable type: {other}"),
+            "word" => Some(VarType::Word),
+            "string" => Some(VarType::String),
+            _ => None,
         }
     }
 }
@@ -174,6 +184,7 @@ pub const SIMPLE_REFERENCE_STUB: &str = indoc! {r##"
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Error;
 
     const COMPLEX_REFERENCE_STUB: &str = indoc! {r##"
         write many  spaces   here
@@ -322,4 +333,59 @@ mod tests {
     fn test_reference_stub_cpp() {
         generate("cpp", COMPLEX_REFERENCE_STUB).unwrap();
     }
+
+    #[test]
+    fn invalid_generators_are_errors() {
+        for generator in [
+            "read a:enum",
+            "write join(a)",
+            "loop",
+            "loop n\nfoo",
+            "gameloop",
+            "unknown",
+        ] {
+            let result = generate("python", generator);
+            assert!(matches!(result, Err(Error::StubParse(_))), "{:?}: {:?}", generator, result);
+        }
+
+        let unknown_type = generate("python", "read n:int\nread a:enum").unwrap_err();
+        match unknown_type {
+            Error::StubParse(err) => {
+                assert_eq!(err.kind, ParseErrorKind::UnknownType(String::from("enum")));
+                assert_eq!(err.span.line, 1);
+            }
+            err => panic!("expected a parse error, got {:?}", err),
+        }
+        let too_deep = format!("{}read x:int", "loop n ".repeat(20));
+        assert!(matches!(
+            generate("python", &too_deep),
+            Err(Error::TooDeeplyNested { max_depth: 18 })
+        ));
+    }
+
+    #[test]
+    fn unknown_preprocessor_is_a_config_error() {
+        let python_config = include_str!("../config/stub_templates/python/stub_config.toml");
+        let toml_str = format!("preprocessor = \"lisp-like\"\n{python_config}");
+        assert!(Language::from_toml(&toml_str, "stub_config.toml").unwrap().preprocessor.is_some());
+
+        let toml_str = format!("preprocessor = \"lisp\"\n{python_config}");
+        assert!(matches!(
+            Language::from_toml(&toml_str, "stub_config.toml"),
+            Err(Error::Config(ConfigError::UnknownPreprocessor(name))) if name == "lisp"
+        ));
+    }
+
+    #[test]
+    fn unknown_language_is_not_found() {
+        assert!(matches!(generate("brainfuck", "read a:int"), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn embedded_stub_configs_render_the_reference_stub() {
+        for lang in embedded_languages() {
+            let config = StubConfig::read_from_embedded(lang).unwrap();
+            assert!(check_stub_config(config).is_ok(), "{}", lang);
+        }
+    }
 }
@@ -0,0 +1,226 @@
+//! Diagnostics for stub generators and where their variables are declared
+//! and used, for the language server.
+//!
+//! Everything is collected by the same parser that [generate](super::generate)
+//! uses, so the generators without errors are exactly the ones that
+//! [generate](super::generate) accepts.
+
+use super::parser::{self, ParseError};
+
+/// Position of some text in a stub generator. Lines and columns (counted in
+/// characters) start from 0, `end` is exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        self.line == line && self.start <= column && column < self.end
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The stub can not be generated.
+    Error,
+    /// The stub can be generated but is probably not what was intended.
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Self {
+        Diagnostic {
+            span: err.span,
+            severity: Severity::Error,
+            message: err.kind.to_string(),
+        }
+    }
+}
+
+/// A variable that is read by a `read` or `loopline` command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// Type as written in the generator, like `int` or `word(50)`.
+    pub var_type: String,
+    /// Comment from an `INPUT` block (if any).
+    pub input_comment: Option<String>,
+    /// Where the variable is declared.
+    pub span: Span,
+}
+
+/// Result of [analyze].
+#[derive(Debug, Clone, Default)]
+pub struct Analysis {
+    pub diagnostics: Vec<Diagnostic>,
+    pub variables: Vec<Variable>,
+    /// Places where variables are declared or used (the index of the
+    /// variable in `variables`).
+    pub(super) references: Vec<(Span, usize)>,
+}
+
+impl Analysis {
+    /// Returns true if the stub can be generated.
+    pub fn is_valid(&self) -> bool {
+        self.diagnostics.iter().all(|diagnostic| diagnostic.severity != Severity::Error)
+    }
+
+    /// The variable that is declared or used at `line` and `column`.
+    pub fn variable_at(&self, line: usize, column: usize) -> Option<&Variable> {
+        self.references
+            .iter()
+            .find(|(span, _)| span.contains(line, column))
+            .map(|(_, index)| &self.variables[*index])
+    }
+}
+
+/// Checks a stub generator. Unlike [generate](super::generate), parsing goes
+/// on after an error (on the next line) to report all of them.
+///
+/// # Examples
+///
+/// ```
+/// use clashlib::stub::analysis::{analyze, Severity};
+///
+/// let analysis = analyze("read n:int\nwrite join(n, m)\nreed x:int");
+/// let messages: Vec<&str> = analysis.diagnostics.iter().map(|d| d.message.as_str()).collect();
+/// assert_eq!(
+///     messages,
+///     [
+///         "Join term m is not declared (read it before writing it)",
+///         "Unknown keyword reed (expected read, write, loop, loopline, OUTPUT, INPUT or STATEMENT)",
+///     ]
+/// );
+/// assert!(!analysis.is_valid());
+/// assert_eq!(analysis.variable_at(1, 11).unwrap().var_type, "int");
+/// ```
+pub fn analyze(generator: &str) -> Analysis {
+    parser::analyze_generator_stub(generator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stub::{ParseErrorKind, SIMPLE_REFERENCE_STUB};
+
+    fn messages(generator: &str) -> Vec<(usize, String)> {
+        analyze(generator)
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.span.line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn reference_stub_is_valid() {
+        let analysis = analyze(SIMPLE_REFERENCE_STUB);
+        assert_eq!(analysis.diagnostics, []);
+        assert_eq!(analysis.variables.len(), 22);
+    }
+
+    #[test]
+    fn puzzle_stub_is_valid() {
+        let clash = crate::test_helper::sample_puzzle("stub_and_solution_tester").unwrap();
+        let analysis = analyze(clash.stub_generator().unwrap());
+        assert_eq!(analysis.diagnostics, []);
+    }
+
+    #[test]
+    fn untyped_and_mistyped_variables() {
+        let generator =
+            "read x y:int\nread a:integer\nread b:word\nread c:int(3)\nloopline n\nread s:string()";
+        assert_eq!(
+            messages(generator),
+            [
+                (0, String::from("Variable x has no type (write it like x:int)")),
+                (1, ParseErrorKind::UnknownType(String::from("integer")).to_string()),
+                (2, String::from("Type word needs a length, like word(50)")),
+                (3, String::from("Type int does not take a length")),
+                (4, String::from("loopline needs at least one variable")),
+            ]
+        );
+    }
+
+    #[test]
+    fn generate_accepts_exactly_the_valid_generators() {
+        for generator in [
+            "read :int",
+            "read s:string()",
+            "read   x:int  ",
+            "write join(",
+            "read n:int\nloop n loopline n x:int",
+            "read x:enum",
+            "loop n\nfoo",
+            "write join(a)",
+            "gameloop",
+            "unknown",
+        ] {
+            let result = crate::stub::generate("python", generator);
+            assert_eq!(analyze(generator).is_valid(), result.is_ok(), "{:?}: {:?}", generator, result);
+        }
+    }
+
+    #[test]
+    fn join_terms_must_be_declared_before() {
+        let generator = "write join(a, \"b\")\nread a:int\nwrite join() join(a,  c)";
+        let analysis = analyze(generator);
+        let spans: Vec<Span> = analysis.diagnostics.iter().map(|diagnostic| diagnostic.span).collect();
+        assert_eq!(
+            spans,
+            [
+                Span {
+                    line: 0,
+                    start: 11,
+                    end: 12
+                },
+                Span {
+                    line: 2,
+                    start: 22,
+                    end: 23
+                },
+            ]
+        );
+        assert_eq!(analysis.variable_at(2, 18).unwrap().name, "a");
+        // Empty terms make the join plain text
+        assert_eq!(messages("write join(x,,y)"), []);
+    }
+
+    #[test]
+    fn write_and_text_blocks_end_at_empty_lines() {
+        let generator = "write hello\nthis is text\n\nSTATEMENT\nanything goes\n\nnope";
+        assert_eq!(messages(generator)[0].0, 6);
+        assert_eq!(messages(generator).len(), 1);
+    }
+
+    #[test]
+    fn loops_need_a_count_and_a_command() {
+        assert_eq!(
+            messages("loop n foo\nread x:int"),
+            [(0, String::from("Expected read, write, loop or loopline to loop, got foo"))]
+        );
+        assert_eq!(messages("loop")[0].1, "loop needs a count");
+    }
+
+    #[test]
+    fn input_comments_are_shown_on_hover() {
+        let generator = "read n:int\nloop n read x:word(n)\n\nINPUT\nn: the count\nm: nothing";
+        let analysis = analyze(generator);
+        let variable = analysis.variable_at(1, 5).unwrap();
+        assert_eq!((variable.name.as_str(), variable.var_type.as_str()), ("n", "int"));
+        assert_eq!(variable.input_comment.as_deref(), Some("the count"));
+        assert_eq!(analysis.variable_at(1, 19).unwrap().name, "n");
+        assert_eq!(analysis.variable_at(1, 12).unwrap().var_type, "word(n)");
+        assert_eq!(analysis.diagnostics[0].severity, Severity::Warning);
+        assert!(analysis.is_valid());
+    }
+}
@@ -1,10 +1,12 @@
-use serde::de::Error;
 use serde::{Deserialize, Serialize};
 
 mod variable_name_options;
 use variable_name_options::VariableNameOptions;
 
 use super::preprocessor::{self, Preprocessor};
+use super::ConfigError;
+use crate::error::Context;
+use crate::Result;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "PascalCase")]
@@ -21,18 +23,32 @@ pub(super) struct TypeTokens {
 pub(super) struct Language {
     pub variable_name_options: VariableNameOptions,
     pub source_file_ext: String,
+    /// Command that compiles a solution, `{file}` is replaced with the source
+    /// file.
+    #[serde(default)]
+    pub build_command: Option<String>,
+    /// Command that executes a solution, `{file}` is replaced with the source
+    /// file.
+    #[serde(default)]
+    pub run_command: Option<String>,
     pub type_tokens: TypeTokens,
-    #[serde(deserialize_with = "deser_preprocessor", default)]
+    /// Name of the preprocessor, looked up by [Language::from_toml].
+    #[serde(rename = "preprocessor", default)]
+    preprocessor_name: Option<String>,
+    #[serde(skip)]
     pub preprocessor: Option<Preprocessor>,
 }
 
-fn deser_preprocessor<'de, D>(deserializer: D) -> Result<Option<Preprocessor>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let preprocessor: String = Deserialize::deserialize(deserializer)?;
-    match preprocessor.as_str() {
-        "lisp-like" => Ok(Some(preprocessor::lisp_like::transform)),
-        _ => Err(D::Error::custom(format!("preprocessor {preprocessor} not found."))),
+impl Language {
+    /// Reads the contents of a stub_config.toml. `file` names it in errors.
+    pub fn from_toml(toml_str: &str, file: &str) -> Result<Self> {
+        let mut language: Language =
+            toml::from_str(toml_str).with_context(|| format!("Unable to deserialize {file}"))?;
+        language.preprocessor = match language.preprocessor_name.as_deref() {
+            None => None,
+            Some("lisp-like") => Some(preprocessor::lisp_like::transform),
+            Some(name) => return Err(ConfigError::UnknownPreprocessor(name.to_string()).into()),
+        };
+        Ok(language)
     }
 }
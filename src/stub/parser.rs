@@ -1,187 +1,389 @@
-use std::iter;
-
-use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
 
+use super::analysis::{Analysis, Diagnostic, Severity, Span, Variable};
 use super::{Cmd, JoinTerm, Stub, VarType, VariableCommand};
+use crate::{Error, Result};
 
 pub fn parse_generator_stub(generator: &str) -> Result<Stub> {
-    Parser::new(generator).parse()
+    let mut parser = Parser::new(generator);
+    let stub = parser.parse();
+    match parser.errors.into_iter().next() {
+        Some(err) => Err(Error::StubParse(err)),
+        None => Ok(stub),
+    }
+}
+
+/// Parses the whole generator, collecting every error instead of stopping at
+/// the first one (see [analyze](super::analysis::analyze)).
+pub fn analyze_generator_stub(generator: &str) -> Analysis {
+    let mut parser = Parser::new(generator);
+    parser.parse();
+    let mut analysis = parser.analysis;
+    analysis.diagnostics.extend(parser.errors.iter().map(Diagnostic::from));
+    analysis
+        .diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.start));
+    analysis
+}
+
+/// Why a stub generator can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    #[error("Unknown keyword {0} (expected read, write, loop, loopline, OUTPUT, INPUT or STATEMENT)")]
+    UnknownKeyword(String),
+    #[error("The gameloop command is not supported")]
+    Gameloop,
+    /// A `read` or `loopline` (the keyword) without variables.
+    #[error("{0} needs at least one variable")]
+    NoVariables(String),
+    #[error("Variable {0} has no type (write it like {0}:int)")]
+    UntypedVariable(String),
+    #[error("Unknown type {0} (expected int, float, long, bool, word(LENGTH) or string(LENGTH))")]
+    UnknownType(String),
+    #[error("Type {0} does not take a length")]
+    UnexpectedLength(String),
+    #[error("Type {0} needs a length, like {0}(50)")]
+    MissingLength(String),
+    #[error("Join term {0} is not declared (read it before writing it)")]
+    UndeclaredJoinTerm(String),
+    /// A `loop` or `loopline` (the keyword) without a count.
+    #[error("{0} needs a count")]
+    MissingCount(String),
+    #[error("Expected read, write, loop or loopline to loop, got {0}")]
+    NotLoopable(String),
+    #[error("Expected read, write, loop or loopline to loop")]
+    NothingToLoop,
+}
+
+/// A [ParseErrorKind] and where it happened in the stub generator.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{kind} (line {line})", line = .span.line + 1)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    fn new(kind: ParseErrorKind, span: Span) -> Self {
+        ParseError { kind, span }
+    }
+}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Token<'a> {
+    /// A word or "\n" at the end of every line. Consecutive spaces result in
+    /// empty words.
+    text: &'a str,
+    span: Span,
+}
+
+fn tokenize(generator: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for (line_no, line) in generator.lines().enumerate() {
+        let mut column = 0;
+        for text in line.split(' ') {
+            let width = text.chars().count();
+            tokens.push(Token {
+                text,
+                span: Span {
+                    line: line_no,
+                    start: column,
+                    end: column + width,
+                },
+            });
+            column += width + 1;
+        }
+        let end_of_line = Span {
+            line: line_no,
+            start: column.saturating_sub(1),
+            end: column,
+        };
+        tokens.push(Token {
+            text: "\n",
+            span: end_of_line,
+        });
+    }
+    tokens
 }
 
-/// A wrapper around an iterator of tokens in the CG stub. Contains all of the
-/// stub parsing logic.
-///
-/// Exists solely to be consumed with `.parse()`
+/// A cursor over the tokens of the CG stub. Contains all of the stub parsing
+/// logic.
 struct Parser<'a> {
-    token_stream: Box<dyn Iterator<Item = &'a str> + 'a>,
-    read_pairings: std::collections::BTreeMap<String, VarType>,
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    lines: Vec<&'a str>,
+    read_pairings: BTreeMap<String, VarType>,
+    /// Errors of the commands that could not be parsed. Parsing continues on
+    /// the next line after an error.
+    errors: Vec<ParseError>,
+    /// The variables (with the places they are used) and the warnings, for
+    /// the language server.
+    analysis: Analysis,
 }
 
 impl<'a> Parser<'a> {
     fn new(stub: &'a str) -> Self {
-        // .chain just adds an iterator to the end of another one,
-        // iter::once creates an iterator out of a single element.
-        // Essentially this puts a "\n" at the end of each line so the parser can tell
-        // where the lines end. Unfortunately I cannot concat &strs which would
-        // have made this much simpler.
-        let token_stream = stub.lines().flat_map(|line| line.split(' ').chain(iter::once("\n")));
         Self {
-            token_stream: Box::new(token_stream),
-            read_pairings: std::collections::BTreeMap::new(),
+            tokens: tokenize(stub),
+            position: 0,
+            lines: stub.lines().collect(),
+            read_pairings: BTreeMap::new(),
+            errors: Vec::new(),
+            analysis: Analysis::default(),
         }
     }
 
-    #[rustfmt::skip]
-    fn parse(mut self) -> Result<Stub> {
+    fn parse(&mut self) -> Stub {
         let mut stub = Stub::default();
 
         while let Some(token) = self.next_token() {
-            match token {
-                "read"      => stub.commands.push(self.parse_read()),
-                "write"     => stub.commands.push(self.parse_write()),
-                "loop"      => stub.commands.push(self.parse_loop()),
-                "loopline"  => stub.commands.push(self.parse_loopline()),
-                "OUTPUT"    => self.parse_output_comment(&mut stub.commands),
-                "INPUT"     => self.parse_input_comment(&mut stub.commands),
-                "STATEMENT" => stub.statement = self.parse_text_block(),
-                "gameloop"  => return Err(anyhow!("Stub generator does not currently support the 'gameloop' command")),
-                "\n" | ""   => continue,
-                thing => panic!("Unknown token stub generator: '{}'", thing),
+            let command = match token.text {
+                "read" => self.parse_read(),
+                "write" => self.parse_write(),
+                "loop" => self.parse_loop(),
+                "loopline" => self.parse_loopline(),
+                "OUTPUT" => {
+                    self.parse_output_comment(&mut stub.commands);
+                    continue
+                }
+                "INPUT" => {
+                    self.parse_input_comment(&mut stub.commands);
+                    continue
+                }
+                "STATEMENT" => {
+                    stub.statement = self.parse_text_block();
+                    continue
+                }
+                "gameloop" => Err(ParseError::new(ParseErrorKind::Gameloop, token.span)),
+                "\n" | "" => continue,
+                thing => Err(ParseError::new(ParseErrorKind::UnknownKeyword(thing.to_string()), token.span)),
             };
+            match command {
+                Ok(command) => stub.commands.push(command),
+                Err(err) => {
+                    self.errors.push(err);
+                    if self.last_token().text != "\n" {
+                        self.skip_line();
+                    }
+                }
+            }
         }
 
-        Ok(stub)
+        stub
     }
 
-    fn parse_read(&mut self) -> Cmd {
-        Cmd::Read(self.parse_variables())
+    fn parse_read(&mut self) -> ParseResult<Cmd> {
+        let keyword = self.last_token();
+        Ok(Cmd::Read(self.parse_variables(keyword)?))
     }
 
-    fn parse_write(&mut self) -> Cmd {
+    fn parse_write(&mut self) -> ParseResult<Cmd> {
         let mut lines = Vec::new();
 
-        while let Some(line) = self.rest_of_line() {
+        loop {
+            let (line_no, column) = self.current_position();
+            let Some(line) = self.rest_of_line() else { break };
             // NOTE: A join could be present on the first line
             if lines.is_empty() {
-                if let Some(write) = self.check_for_write_join(&line) {
-                    return write
+                if let Some(write) = self.check_for_write_join(&line, line_no, column)? {
+                    return Ok(write)
                 }
             }
 
             lines.push(line)
         }
 
-        Cmd::Write {
+        Ok(Cmd::Write {
             lines,
             output_comment: Vec::new(),
-        }
+        })
     }
 
-    fn check_for_write_join(&self, line: &str) -> Option<Cmd> {
+    /// `line` is the text of the line `line_no` from `column` on, trimmed.
+    fn check_for_write_join(
+        &mut self,
+        line: &str,
+        line_no: usize,
+        column: usize,
+    ) -> ParseResult<Option<Cmd>> {
+        let original_line = self.lines[line_no];
+        let line_start = original_line.char_indices().nth(column).map_or(original_line.len(), |(i, _)| i);
         // NOTE: write•join()•rest⏎, with NOTHING inside the parens,
         //       gets parsed as a write and not as a write_join
-        match line.replace("join()", "").split_once("join(") {
-            Some((_, join_arg)) if join_arg.contains(')') => {
-                let terms_string = join_arg.split_once(')').expect("Already checked existence").0;
-
+        match find_join_terms(&original_line[line_start..]) {
+            Some((terms_start, terms_string)) => {
                 if terms_string.split(',').any(|t| t.trim().is_empty()) {
                     // write•join("hi",,,•"Jim")⏎ should be rendered as a Write Cmd
                     // (I guess the CG parser fails due to consecutive commas)
-                    Some(Cmd::Write {
+                    Ok(Some(Cmd::Write {
                         lines: vec![line.to_string()],
                         output_comment: Vec::new(),
-                    })
+                    }))
                 } else {
                     // NOTE: write•join("a")⏎ is a valid join
-                    Some(self.parse_write_join(terms_string))
+                    Ok(Some(self.parse_write_join(terms_string, line_no, line_start + terms_start)?))
                 }
             }
             // NOTE: write•join(⏎ gets parsed as a raw string
             //       and write parsing resumes
-            _ => None,
+            None => Ok(None),
         }
     }
 
-    fn parse_write_join(&self, terms_string: &str) -> Cmd {
-        let join_terms = terms_string
-            .split(',')
-            .map(|term| {
-                if term.contains('"') {
-                    let ident = term.trim_matches(|c| c != '"').trim_matches('"').to_string();
-                    JoinTerm::new(ident, None)
-                } else {
-                    let ident = term.trim().to_string();
-                    match self.read_pairings.get(&ident) {
-                        Some(var_type) => JoinTerm::new(ident, Some(*var_type)),
-                        None => panic!("The JoinTerm '{}' was not previously initialized.", &ident),
-                    }
-                }
-            })
-            .collect();
+    /// `terms_start` is the byte offset of `terms_string` in the line.
+    fn parse_write_join(
+        &mut self,
+        terms_string: &str,
+        line_no: usize,
+        terms_start: usize,
+    ) -> ParseResult<Cmd> {
+        let line = self.lines[line_no];
+        let mut join_terms = Vec::new();
+        let mut term_start = terms_start;
+        for term in terms_string.split(',') {
+            let offset = term_start;
+            term_start += term.len() + 1;
+            if term.contains('"') {
+                let ident = term.trim_matches(|c| c != '"').trim_matches('"').to_string();
+                join_terms.push(JoinTerm::new(ident, None));
+                continue
+            }
+            let ident = term.trim();
+            let start = line[..offset + term.find(ident).unwrap_or(0)].chars().count();
+            let span = Span {
+                line: line_no,
+                start,
+                end: start + ident.chars().count(),
+            };
+            let Some(var_type) = self.read_pairings.get(ident).copied() else {
+                return Err(ParseError::new(ParseErrorKind::UndeclaredJoinTerm(ident.to_string()), span))
+            };
+            self.reference(ident, span);
+            join_terms.push(JoinTerm::new(ident.to_string(), Some(var_type)));
+        }
 
-        Cmd::WriteJoin {
+        Ok(Cmd::WriteJoin {
             join_terms,
             output_comment: Vec::new(),
-        }
+        })
     }
 
-    fn parse_loop(&mut self) -> Cmd {
+    fn parse_loop(&mut self) -> ParseResult<Cmd> {
+        let keyword = self.last_token();
         match self.first_non_whitespace_token() {
-            None => panic!("Unexpected end of input: Loop stub not provided with loop count"),
-            Some(other) => Cmd::Loop {
-                count_var: String::from(other),
-                command: Box::new(self.parse_loopable()),
-            },
+            None => {
+                Err(ParseError::new(ParseErrorKind::MissingCount(keyword.text.to_string()), keyword.span))
+            }
+            Some(count) => {
+                self.reference(count.text, count.span);
+                Ok(Cmd::Loop {
+                    count_var: String::from(count.text),
+                    command: Box::new(self.parse_loopable(keyword)?),
+                })
+            }
         }
     }
 
-    fn parse_loopable(&mut self) -> Cmd {
-        match self.first_non_whitespace_token() {
-            Some("read") => self.parse_read(),
-            Some("write") => self.parse_write(),
-            Some("loopline") => self.parse_loopline(),
-            Some("loop") => self.parse_loop(),
-            Some(thing) => panic!("Error parsing loop command in stub generator, got: {}", thing),
-            None => panic!("Unexpected end of input, expecting command to loop through"),
+    fn parse_loopable(&mut self, keyword: Token) -> ParseResult<Cmd> {
+        let Some(token) = self.first_non_whitespace_token() else {
+            return Err(ParseError::new(ParseErrorKind::NothingToLoop, keyword.span))
+        };
+        match token.text {
+            "read" => self.parse_read(),
+            "write" => self.parse_write(),
+            "loopline" => self.parse_loopline(),
+            "loop" => self.parse_loop(),
+            thing => Err(ParseError::new(ParseErrorKind::NotLoopable(thing.to_string()), token.span)),
         }
     }
 
-    fn parse_loopline(&mut self) -> Cmd {
+    fn parse_loopline(&mut self) -> ParseResult<Cmd> {
+        let keyword = self.last_token();
         match self.first_non_whitespace_token() {
-            None => panic!("Unexpected end of input: Loopline stub not provided with count identifier"),
-            Some(other) => Cmd::LoopLine {
-                count_var: other.to_string(),
-                variables: self.parse_variables(),
-            },
+            None => {
+                Err(ParseError::new(ParseErrorKind::MissingCount(keyword.text.to_string()), keyword.span))
+            }
+            Some(count) => {
+                self.reference(count.text, count.span);
+                Ok(Cmd::LoopLine {
+                    count_var: count.text.to_string(),
+                    variables: self.parse_variables(keyword)?,
+                })
+            }
         }
     }
 
-    fn parse_variables(&mut self) -> Vec<VariableCommand> {
+    fn parse_variables(&mut self, keyword: Token) -> ParseResult<Vec<VariableCommand>> {
         let Some(tokens) = self.tokens_upto_newline() else {
-            panic!("Empty line after read keyword")
+            return Err(ParseError::new(ParseErrorKind::NoVariables(keyword.text.to_string()), keyword.span))
         };
 
-        tokens.into_iter().filter_map(|token| self.parse_variable(token)).collect()
+        let mut variables = Vec::new();
+        for token in tokens {
+            variables.extend(self.parse_variable(token)?);
+        }
+        Ok(variables)
     }
 
-    fn parse_variable(&mut self, token: &str) -> Option<VariableCommand> {
+    fn parse_variable(&mut self, token: Token) -> ParseResult<Option<VariableCommand>> {
         // A token may be empty if extra spaces were present: "read   x:int  "
-        if token.is_empty() {
-            return None
+        if token.text.is_empty() {
+            return Ok(None)
         }
-        let Some((ident, type_string)) = token.split_once(':') else {
-            panic!("Variable must have type")
+        let Some((ident, type_string)) = token.text.split_once(':') else {
+            return Err(ParseError::new(ParseErrorKind::UntypedVariable(token.text.to_string()), token.span))
         };
-        let (var_type, max_length) = Self::extract_type_and_length(type_string);
+        let (var_type, max_length) =
+            Self::extract_type_and_length(type_string).map_err(|kind| ParseError::new(kind, token.span))?;
         self.read_pairings.insert(String::from(ident), var_type);
 
-        Some(VariableCommand::new(ident.to_string(), var_type, max_length))
+        if let Some(max_length) = &max_length {
+            let type_start = token.text.len() - type_string.len();
+            let length_start = type_start + type_string.find('(').map_or(0, |index| index + 1);
+            let start = token.span.start + token.text[..length_start].chars().count();
+            let length_span = Span {
+                start,
+                end: start + max_length.chars().count(),
+                ..token.span
+            };
+            self.reference(max_length, length_span);
+        }
+        let name_span = Span {
+            end: token.span.start + ident.chars().count(),
+            ..token.span
+        };
+        self.analysis.variables.push(Variable {
+            name: ident.to_string(),
+            var_type: type_string.to_string(),
+            input_comment: None,
+            span: name_span,
+        });
+        self.analysis.references.push((name_span, self.analysis.variables.len() - 1));
+
+        Ok(Some(VariableCommand::new(ident.to_string(), var_type, max_length)))
     }
 
-    fn extract_type_and_length(type_string: &str) -> (VarType, Option<String>) {
+    fn extract_type_and_length(
+        type_string: &str,
+    ) -> std::result::Result<(VarType, Option<String>), ParseErrorKind> {
         match type_string.trim_end_matches(')').split_once('(') {
-            Some((var_type, max_length)) => (VarType::new_sized(var_type), Some(max_length.to_string())),
-            None => (VarType::new_unsized(type_string), None),
+            Some((var_type, max_length)) => match VarType::new_sized(var_type) {
+                Some(var_type) => Ok((var_type, Some(max_length.to_string()))),
+                None if VarType::new_unsized(var_type).is_some() => {
+                    Err(ParseErrorKind::UnexpectedLength(var_type.to_string()))
+                }
+                None => Err(ParseErrorKind::UnknownType(var_type.to_string())),
+            },
+            None => match VarType::new_unsized(type_string) {
+                Some(var_type) => Ok((var_type, None)),
+                None if VarType::new_sized(type_string).is_some() => {
+                    Err(ParseErrorKind::MissingLength(type_string.to_string()))
+                }
+                None => Err(ParseErrorKind::UnknownType(type_string.to_string())),
+            },
         }
     }
 
@@ -197,15 +399,51 @@ impl<'a> Parser<'a> {
     fn parse_input_comment(&mut self, previous_commands: &mut [Cmd]) {
         self.skip_line();
 
-        while let Some(line) = self.rest_of_line() {
+        loop {
+            let (line_no, _) = self.current_position();
+            let Some(line) = self.rest_of_line() else { break };
             if let Some((ic_ident, ic_comment)) = line.split_once(':') {
                 for cmd in previous_commands.iter_mut() {
                     Self::update_cmd_with_input_comment(cmd, ic_ident.trim(), ic_comment.trim());
                 }
+                self.record_input_comment(line_no, ic_ident.trim(), ic_comment.trim());
             }
         }
     }
 
+    /// Attaches the input comment to the variables (read before it) for the
+    /// language server.
+    fn record_input_comment(&mut self, line_no: usize, ident: &str, comment: &str) {
+        let start = self.lines[line_no].chars().take_while(|c| *c == ' ').count();
+        let span = Span {
+            line: line_no,
+            start,
+            end: start + ident.chars().count(),
+        };
+        let mut commented = false;
+        for variable in self.analysis.variables.iter_mut().filter(|variable| variable.name == ident) {
+            variable.input_comment = Some(comment.to_string());
+            commented = true;
+        }
+        match commented {
+            true => self.reference(ident, span),
+            false => self.analysis.diagnostics.push(Diagnostic {
+                span,
+                severity: Severity::Warning,
+                message: format!("Input comment for {}, which is not read before it", ident),
+            }),
+        }
+    }
+
+    /// Records a use of the variable `name` (if it is declared) for the
+    /// language server.
+    fn reference(&mut self, name: &str, span: Span) {
+        let variables = &self.analysis.variables;
+        if let Some(index) = variables.iter().rposition(|variable| variable.name == name) {
+            self.analysis.references.push((span, index));
+        }
+    }
+
     fn update_cmd_with_output_comment(cmd: &mut Cmd, new_comment: &Vec<String>) {
         match cmd {
             Cmd::Write {
@@ -251,36 +489,56 @@ impl<'a> Parser<'a> {
 
     fn skip_line(&mut self) {
         while let Some(token) = self.next_token() {
-            if token == "\n" {
+            if token.text == "\n" {
                 break
             }
         }
     }
 
-    fn next_token(&mut self) -> Option<&'a str> {
-        self.token_stream.next()
+    fn next_token(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.position).copied()?;
+        self.position += 1;
+        Some(token)
+    }
+
+    /// The token that was consumed last (an empty token at the start).
+    fn last_token(&self) -> Token<'a> {
+        self.position.checked_sub(1).map(|index| self.tokens[index]).unwrap_or_default()
     }
 
-    fn first_non_whitespace_token(&mut self) -> Option<&'a str> {
-        self.token_stream.by_ref().find(|&token| token != "\n" && !token.is_empty())
+    /// Line and column of the next token.
+    fn current_position(&self) -> (usize, usize) {
+        self.tokens
+            .get(self.position)
+            .map_or((0, 0), |token| (token.span.line, token.span.start))
+    }
+
+    fn first_non_whitespace_token(&mut self) -> Option<Token<'a>> {
+        while let Some(token) = self.next_token() {
+            if token.text != "\n" && !token.text.is_empty() {
+                return Some(token)
+            }
+        }
+        None
     }
 
     fn rest_of_line(&mut self) -> Option<String> {
-        Some(self.tokens_upto_newline()?.join(" ").trim().to_string())
+        let tokens: Vec<&str> = self.tokens_upto_newline()?.iter().map(|token| token.text).collect();
+        Some(tokens.join(" ").trim().to_string())
     }
 
     // Consumes the newline
-    fn tokens_upto_newline(&mut self) -> Option<Vec<&'a str>> {
+    fn tokens_upto_newline(&mut self) -> Option<Vec<Token<'a>>> {
         let mut buf = Vec::new();
 
         while let Some(token) = self.next_token() {
-            if token == "\n" {
+            if token.text == "\n" {
                 break
             }
             buf.push(token)
         }
 
-        if buf.iter().all(|s| s.is_empty()) {
+        if buf.iter().all(|token| token.text.is_empty()) {
             None
         } else {
             Some(buf)
@@ -288,5 +546,20 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Byte offset and text of the terms of the first join on the line that has
+/// something between its parens (if any).
+fn find_join_terms(line: &str) -> Option<(usize, &str)> {
+    let mut search_from = 0;
+    let terms_start = loop {
+        let index = search_from + line[search_from..].find("join(")?;
+        if !line[index..].starts_with("join()") {
+            break index + "join(".len()
+        }
+        search_from = index + "join()".len();
+    };
+    let terms_length = line[terms_start..].find(')')?;
+    Some((terms_start, &line[terms_start..terms_start + terms_length]))
+}
+
 #[cfg(test)]
 mod parser_tests;
@@ -18,7 +18,7 @@ pub type Preprocessor = fn(&mut Stub) -> ();
 /// may be wrapped in Cmd::External and its render method will be called by
 /// Renderer.
 pub trait Renderable: std::fmt::Debug + DynClone {
-    fn render(&self, renderer: &Renderer) -> String;
+    fn render(&self, renderer: &Renderer) -> crate::Result<String>;
 }
 
 dyn_clone::clone_trait_object!(Renderable);
@@ -69,13 +69,19 @@ struct ReadBatch {
 }
 
 impl Renderable for ReadBatch {
-    fn render(&self, renderer: &crate::stub::renderer::Renderer) -> String {
-        let nested_string: String =
-            self.nested_cmds.iter().map(|cmd| renderer.render_command(cmd, 0)).collect();
+    fn render(&self, renderer: &crate::stub::renderer::Renderer) -> crate::Result<String> {
+        let nested_string: String = self
+            .nested_cmds
+            .iter()
+            .map(|cmd| renderer.render_command(cmd, 0))
+            .collect::<crate::Result<_>>()?;
         let nested_lines: Vec<&str> = nested_string.lines().collect();
 
-        let read_lines: String =
-            self.line_readers.iter().map(|cmd| renderer.render_command(cmd, 0)).collect();
+        let read_lines: String = self
+            .line_readers
+            .iter()
+            .map(|cmd| renderer.render_command(cmd, 0))
+            .collect::<crate::Result<_>>()?;
         let read_lines: Vec<&str> = read_lines.lines().collect();
 
         let mut context = tera::Context::new();
@@ -1,9 +1,10 @@
-use anyhow::{Context as _, Result}; // To distinguish it from tera::Context
 use itertools::Itertools;
 use serde_json::json;
 use tera::{Context, Tera};
 
 use super::{Cmd, JoinTerm, Language, Stub, StubConfig, VariableCommand};
+use crate::error::Context as _; // To distinguish it from tera::Context
+use crate::{Error, Result};
 
 const ALPHABET: [char; 18] = [
     'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
@@ -24,7 +25,7 @@ impl Renderer {
         })
     }
 
-    pub(super) fn tera_render(&self, template_name: &str, context: &mut Context) -> String {
+    pub(super) fn tera_render(&self, template_name: &str, context: &mut Context) -> Result<String> {
         // Since these are (generally) shared across languages, it makes sense to
         // store it in the "global" context instead of accepting it as parameters.
         let format_symbols = json!({
@@ -42,13 +43,17 @@ impl Renderer {
         self.tera
             .render(&format!("{template_name}.{}.jinja", self.lang.source_file_ext), context)
             .with_context(|| format!("Failed to render {} template.", template_name))
-            .unwrap()
     }
 
-    pub(super) fn render(&self) -> String {
+    pub(super) fn render(&self) -> Result<String> {
         let mut context = Context::new();
 
-        let code: String = self.stub.commands.iter().map(|cmd| self.render_command(cmd, 0)).collect();
+        let code: String = self
+            .stub
+            .commands
+            .iter()
+            .map(|cmd| self.render_command(cmd, 0))
+            .collect::<Result<_>>()?;
         let code_lines: Vec<&str> = code.lines().collect();
 
         context.insert("statement", &self.stub.statement);
@@ -57,7 +62,7 @@ impl Renderer {
         self.tera_render("main", &mut context)
     }
 
-    pub(super) fn render_command(&self, cmd: &Cmd, nesting_depth: usize) -> String {
+    pub(super) fn render_command(&self, cmd: &Cmd, nesting_depth: usize) -> Result<String> {
         match cmd {
             Cmd::Read(vars) => self.render_read(vars, nesting_depth),
             Cmd::Write {
@@ -76,7 +81,7 @@ impl Renderer {
         }
     }
 
-    fn render_write(&self, lines: &[String], output_comments: &[String]) -> String {
+    fn render_write(&self, lines: &[String], output_comments: &[String]) -> Result<String> {
         let mut context = Context::new();
 
         context.insert("messages", lines);
@@ -85,7 +90,7 @@ impl Renderer {
         self.tera_render("write", &mut context)
     }
 
-    fn render_write_join(&self, terms: &[JoinTerm], output_comments: &[String]) -> String {
+    fn render_write_join(&self, terms: &[JoinTerm], output_comments: &[String]) -> Result<String> {
         let mut context = Context::new();
 
         let terms: Vec<JoinTerm> = terms
@@ -106,14 +111,14 @@ impl Renderer {
         self.tera_render("write_join", &mut context)
     }
 
-    fn render_read(&self, vars: &Vec<VariableCommand>, nesting_depth: usize) -> String {
+    fn render_read(&self, vars: &Vec<VariableCommand>, nesting_depth: usize) -> Result<String> {
         match vars.as_slice() {
             [var] => self.render_read_one(var),
             _ => self.render_read_many(vars, nesting_depth),
         }
     }
 
-    fn render_read_one(&self, var: &VariableCommand) -> String {
+    fn render_read_one(&self, var: &VariableCommand) -> Result<String> {
         let mut context = Context::new();
         let var = self.lang.variable_name_options.transform_variable_command(var);
 
@@ -123,7 +128,7 @@ impl Renderer {
         self.tera_render("read_one", &mut context)
     }
 
-    fn render_read_many(&self, vars: &[VariableCommand], nesting_depth: usize) -> String {
+    fn render_read_many(&self, vars: &[VariableCommand], nesting_depth: usize) -> Result<String> {
         let mut context = Context::new();
         let vars = vars
             .iter()
@@ -136,7 +141,7 @@ impl Renderer {
             _ => context.insert("single_type", &false),
         }
 
-        let index_ident = ALPHABET[nesting_depth];
+        let index_ident = index_ident(nesting_depth)?;
 
         context.insert("vars", &vars);
         context.insert("type_tokens", &self.lang.type_tokens);
@@ -145,11 +150,11 @@ impl Renderer {
         self.tera_render("read_many", &mut context)
     }
 
-    fn render_loop(&self, count_var: &str, cmd: &Cmd, nesting_depth: usize) -> String {
+    fn render_loop(&self, count_var: &str, cmd: &Cmd, nesting_depth: usize) -> Result<String> {
         let mut context = Context::new();
-        let inner_text = self.render_command(cmd, nesting_depth + 1);
+        let inner_text = self.render_command(cmd, nesting_depth + 1)?;
         let cased_count_var = self.lang.variable_name_options.transform_variable_name(count_var);
-        let index_ident = ALPHABET[nesting_depth];
+        let index_ident = index_ident(nesting_depth)?;
         context.insert("count_var", &cased_count_var);
         context.insert("inner", &inner_text.lines().collect::<Vec<&str>>());
         context.insert("index_ident", &index_ident);
@@ -157,7 +162,12 @@ impl Renderer {
         self.tera_render("loop", &mut context)
     }
 
-    fn render_loopline(&self, count_var: &str, vars: &[VariableCommand], nesting_depth: usize) -> String {
+    fn render_loopline(
+        &self,
+        count_var: &str,
+        vars: &[VariableCommand],
+        nesting_depth: usize,
+    ) -> Result<String> {
         let vars = vars
             .iter()
             .map(|var| self.lang.variable_name_options.transform_variable_command(var))
@@ -166,7 +176,7 @@ impl Renderer {
         let mut context = Context::new();
 
         let cased_count_var = self.lang.variable_name_options.transform_variable_name(count_var);
-        let index_ident = ALPHABET[nesting_depth];
+        let index_ident = index_ident(nesting_depth)?;
 
         context.insert("count_var", &cased_count_var);
         context.insert("vars", &vars);
@@ -176,3 +186,10 @@ impl Renderer {
         self.tera_render("loopline", &mut context)
     }
 }
+
+/// The name of the loop index variable at `nesting_depth`.
+fn index_ident(nesting_depth: usize) -> Result<char> {
+    ALPHABET.get(nesting_depth).copied().ok_or(Error::TooDeeplyNested {
+        max_depth: ALPHABET.len(),
+    })
+}
@@ -1,10 +1,26 @@
 use std::fs;
+use std::path::PathBuf;
 
-use anyhow::{Context, Result};
 use include_dir::include_dir;
 use tera::Tera;
 
 use super::Language;
+use crate::error::Context;
+use crate::solution::saved::SOURCE_FILE_PLACEHOLDER;
+use crate::{Error, Result};
+
+/// Why the stub generator of a language can not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ConfigError {
+    #[error("Embedded stub generator '{0}' has no stub_config.toml")]
+    MissingConfigFile(String),
+    #[error("Language directory path {0:?} is not valid utf8")]
+    NonUtf8Path(PathBuf),
+    /// The preprocessor setting of a stub_config.toml.
+    #[error("Unknown preprocessor {0} (expected lisp-like)")]
+    UnknownPreprocessor(String),
+}
 
 const HARDCODED_EMBEDDED_TEMPLATE_DIR: include_dir::Dir<'static> =
     include_dir!("$CARGO_MANIFEST_DIR/config/stub_templates");
@@ -18,31 +34,30 @@ pub struct StubConfig {
 impl StubConfig {
     pub fn read_from_dir(dir: std::path::PathBuf) -> Result<Self> {
         let toml_file = dir.join("stub_config.toml");
-        let toml_str = fs::read_to_string(toml_file)?;
-        let language: Language = toml::from_str(&toml_str)?;
+        let toml_str =
+            fs::read_to_string(&toml_file).with_context(|| format!("Unable to read {:?}", &toml_file))?;
+        let language = Language::from_toml(&toml_str, &format!("{:?}", &toml_file))?;
         let jinja_glob = dir.join("*.jinja");
-        let tera = Tera::new(jinja_glob.to_str().expect("language directory path should be valid utf8"))
-            .context("Failed to create Tera instance")?;
+        let jinja_glob = jinja_glob.to_str().ok_or_else(|| ConfigError::NonUtf8Path(dir.clone()))?;
+        let tera = Tera::new(jinja_glob).with_context(|| "Failed to create Tera instance".to_string())?;
         Ok(Self { language, tera })
     }
 
-    pub(super) fn read_from_embedded(lang_name: &str) -> Result<Self> {
+    pub fn read_from_embedded(lang_name: &str) -> Result<Self> {
         // If you just created a new template for a language and you get:
         // Error: No stub generator found for 'language'
         // you may need to recompile the binaries to update: `cargo build`
         let embedded_config_dir = HARDCODED_EMBEDDED_TEMPLATE_DIR
             .get_dir(lang_name)
-            .context(format!("No stub generator found for '{lang_name}'"))?;
-        let toml_file = embedded_config_dir
+            .ok_or_else(|| Error::NotFound(format!("No stub generator found for '{lang_name}'")))?;
+        let toml_str = embedded_config_dir
             .get_file(format!("{lang_name}/stub_config.toml"))
-            .expect("Embedded stub generators should have stub_config.toml");
-        let toml_str = toml_file
-            .contents_utf8()
-            .expect("Embedded stub_config.toml contents should be valid utf8");
-        let language: Language = toml::from_str(toml_str)?;
+            .and_then(|toml_file| toml_file.contents_utf8())
+            .ok_or_else(|| ConfigError::MissingConfigFile(lang_name.to_string()))?;
+        let language = Language::from_toml(toml_str, &format!("stub_config.toml of '{lang_name}'"))?;
         let templates = embedded_config_dir
             .find("*.jinja")
-            .expect("*.jinja should be a valid glob pattern")
+            .expect("*.jinja should be a valid glob")
             .filter_map(|dir_entry| {
                 let file = dir_entry.as_file()?;
                 Some((file.path().file_name()?.to_str()?, file.contents_utf8()?))
@@ -51,7 +66,34 @@ impl StubConfig {
         let mut tera = Tera::default();
 
         tera.add_raw_templates(templates)
-            .expect("Adding embedded templates to tera should not fail");
+            .with_context(|| format!("Unable to load the embedded templates of '{lang_name}'"))?;
         Ok(Self { language, tera })
     }
+
+    /// The file extension of source files in this language (without the
+    /// leading dot).
+    pub fn source_file_ext(&self) -> &str {
+        &self.language.source_file_ext
+    }
+
+    /// The default command that compiles `source_file` (if the language needs
+    /// to be compiled).
+    pub fn build_command(&self, source_file: &str) -> Option<String> {
+        let cmd = self.language.build_command.as_ref()?;
+        Some(cmd.replace(SOURCE_FILE_PLACEHOLDER, source_file))
+    }
+
+    /// The default command that executes `source_file`.
+    pub fn run_command(&self, source_file: &str) -> Option<String> {
+        let cmd = self.language.run_command.as_ref()?;
+        Some(cmd.replace(SOURCE_FILE_PLACEHOLDER, source_file))
+    }
+}
+
+/// Names of the languages that have stub templates embedded into the binary.
+pub fn embedded_languages() -> Vec<&'static str> {
+    HARDCODED_EMBEDDED_TEMPLATE_DIR
+        .dirs()
+        .filter_map(|dir| dir.path().file_name()?.to_str())
+        .collect()
 }
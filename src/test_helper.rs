@@ -1,9 +1,15 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
-use anyhow::{Context, Result};
-use clash::Clash;
+use clash::{Clash, PublicHandle};
+use workspace::{MemoryStorage, Storage, Workspace};
 
 use super::*;
+use crate::error::Context;
+use crate::Result;
+
+/// Handle of the `stub_and_solution_tester` sample puzzle.
+pub const SAMPLE_HANDLE: &str = "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2";
 
 pub fn sample_puzzle(name: &str) -> Result<Clash> {
     let puzzle_file: PathBuf = ["fixtures", "puzzles", format!("{}.json", name).as_str()].iter().collect();
@@ -14,3 +20,21 @@ pub fn sample_puzzle(name: &str) -> Result<Clash> {
 
     Ok(clash)
 }
+
+pub fn sample_handle() -> PublicHandle {
+    PublicHandle::from_str(SAMPLE_HANDLE).unwrap()
+}
+
+/// Stores the `stub_and_solution_tester` sample puzzle in `storage`.
+pub fn store_sample_clash(storage: &impl Storage) {
+    let contents = std::fs::read_to_string("fixtures/puzzles/stub_and_solution_tester.json").unwrap();
+    storage.write_clash(&sample_handle(), &contents).unwrap();
+}
+
+/// Workspace that keeps everything in memory, with the
+/// `stub_and_solution_tester` sample puzzle stored.
+pub fn workspace_with_sample_clash() -> Workspace<MemoryStorage> {
+    let storage = MemoryStorage::new();
+    store_sample_clash(&storage);
+    Workspace::new(storage)
+}
@@ -0,0 +1,432 @@
+//! The workflow of coctus (which clash is current, reading, picking and
+//! fetching clashes, running the tests of a solution, saved solutions,
+//! practice and hot-seat clashes and finding the stub templates of a
+//! language) for any frontend.
+//! Where the data is kept is up to a [Storage].
+
+mod checkup;
+mod games;
+mod solutions;
+mod storage;
+mod testing;
+mod workspace_file;
+
+use std::path::PathBuf;
+
+pub use checkup::{check_library, Finding, Severity};
+pub use games::PracticeSubmission;
+pub use solutions::Verification;
+pub use storage::{FileStorage, MemoryStorage, Storage};
+pub use testing::{BuildStatus, RunProfile, TestEvent, TestOptions, TestSummary};
+pub use workspace_file::{WorkspaceFile, WORKSPACE_FILE_NAME};
+
+use crate::clash::{Clash, HandleQuery, PublicHandle};
+use crate::error::Context;
+use crate::history::RunRecord;
+use crate::hooks::{HookContext, HookEvent, Hooks};
+use crate::metadata::{ClashMetadata, MetadataStore};
+use crate::navigation::Navigation;
+use crate::selection::{Candidate, ClashFilter, Strategy};
+use crate::solution::CommandOutput;
+use crate::stub::{self, StubConfig};
+use crate::{Error, Result};
+
+/// `Workspace` carries out what the user asks for on top of a [Storage].
+#[derive(Debug, Clone)]
+pub struct Workspace<S> {
+    storage: S,
+    hooks: Hooks,
+    /// Directory where the lookup of a [WorkspaceFile] starts (`None` to
+    /// ignore workspace files).
+    dir: Option<PathBuf>,
+    /// Where the output of builds and hooks goes.
+    command_output: CommandOutput,
+}
+
+impl<S: Storage> Workspace<S> {
+    /// Workspace without hooks that ignores workspace files.
+    pub fn new(storage: S) -> Self {
+        Workspace {
+            storage,
+            hooks: Hooks::default(),
+            dir: None,
+            command_output: CommandOutput::default(),
+        }
+    }
+
+    pub fn with_hooks(self, hooks: Hooks) -> Self {
+        Workspace { hooks, ..self }
+    }
+
+    /// Uses the workspace file of `dir` (or of its closest ancestor that has
+    /// one), like when coctus is run in `dir`.
+    pub fn with_dir(self, dir: PathBuf) -> Self {
+        Workspace {
+            dir: Some(dir),
+            ..self
+        }
+    }
+
+    pub fn with_command_output(self, command_output: CommandOutput) -> Self {
+        Workspace {
+            command_output,
+            ..self
+        }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// The workspace file that applies to the directory of the workspace (if
+    /// any). It is read again on every call, because another coctus may have
+    /// changed it in the meantime.
+    pub fn workspace_file(&self) -> Result<Option<WorkspaceFile>> {
+        match &self.dir {
+            Some(dir) => WorkspaceFile::discover(dir),
+            None => Ok(None),
+        }
+    }
+
+    /// The current clash: the one of the workspace file if it has one,
+    /// otherwise the one of the storage.
+    pub fn current_handle(&self) -> Result<PublicHandle> {
+        if let Some(handle) = self.workspace_file()?.and_then(|workspace_file| workspace_file.handle) {
+            return Ok(handle)
+        }
+        self.storage
+            .current_handle()?
+            .ok_or_else(|| Error::NotFound("No current clash (use `coctus next` to pick one)".to_string()))
+    }
+
+    /// Makes `handle` the current clash (of the workspace file if there is
+    /// one), remembers the previous one and runs the on-next hook. Returns
+    /// the errors of the hook, which do not undo the change.
+    pub fn set_current_handle(&self, handle: &PublicHandle) -> Result<Vec<Error>> {
+        let previous_handle = self.current_handle().ok();
+        match self.workspace_file()? {
+            Some(workspace_file) => WorkspaceFile {
+                handle: Some(handle.clone()),
+                ..workspace_file
+            }
+            .save()?,
+            None => self.storage.set_current_handle(handle)?,
+        }
+
+        let mut navigation = self.storage.navigation()?;
+        navigation.visit(previous_handle.as_ref(), handle);
+        self.storage.write_navigation(&navigation)?;
+
+        if !self.hooks.is_configured(HookEvent::OnNext) {
+            return Ok(Vec::new())
+        }
+        let hook_result = self
+            .read_clash(handle)
+            .and_then(|clash| self.run_hook(HookEvent::OnNext, &HookContext::new(handle, &clash)));
+        Ok(hook_result.err().into_iter().collect())
+    }
+
+    /// The clashes that were current before and the bookmarks.
+    pub fn navigation(&self) -> Result<Navigation> {
+        self.storage.navigation()
+    }
+
+    /// The clash that was current `steps` changes of the current clash ago.
+    pub fn previous_handle(&self, steps: usize) -> Result<PublicHandle> {
+        let navigation = self.storage.navigation()?;
+        match navigation.recent().get(steps.saturating_sub(1)) {
+            Some(handle) => Ok(handle.clone()),
+            None if navigation.recent().is_empty() => Err(Error::NotFound("No previous clash".to_string())),
+            None => Err(Error::NotFound(format!(
+                "Only {} previous clashes (see `coctus history --nav`)",
+                navigation.recent().len()
+            ))),
+        }
+    }
+
+    pub fn add_bookmark(&self, name: &str, handle: PublicHandle) -> Result<()> {
+        let mut navigation = self.storage.navigation()?;
+        navigation.add_bookmark(name, handle)?;
+        self.storage.write_navigation(&navigation)
+    }
+
+    pub fn remove_bookmark(&self, name: &str) -> Result<PublicHandle> {
+        let mut navigation = self.storage.navigation()?;
+        let handle = navigation
+            .remove_bookmark(name)
+            .ok_or_else(|| Error::NotFound(format!("No bookmark named {name}")))?;
+        self.storage.write_navigation(&navigation)?;
+        Ok(handle)
+    }
+
+    /// The clash bookmarked as `name`.
+    pub fn bookmark(&self, name: &str) -> Result<PublicHandle> {
+        self.storage
+            .navigation()?
+            .bookmark(name)
+            .cloned()
+            .ok_or_else(|| Error::NotFound(format!("No bookmark named {name} (see `coctus bookmark`)")))
+    }
+
+    /// The notes, tags and flags of all clashes.
+    pub fn metadata(&self) -> Result<MetadataStore> {
+        self.storage.metadata()
+    }
+
+    /// The notes, tags and flags of the clash with `handle`.
+    pub fn clash_metadata(&self, handle: &PublicHandle) -> Result<ClashMetadata> {
+        Ok(self.storage.metadata()?.get(handle))
+    }
+
+    /// Changes the metadata of the clash with `handle` with `f`. Returns the
+    /// changed metadata.
+    pub fn update_metadata<F: FnOnce(&mut ClashMetadata)>(
+        &self,
+        handle: &PublicHandle,
+        f: F,
+    ) -> Result<ClashMetadata> {
+        let mut metadata_store = self.storage.metadata()?;
+        metadata_store.update(handle, f);
+        self.storage.write_metadata(&metadata_store)?;
+        Ok(metadata_store.get(handle))
+    }
+
+    /// The run history, oldest first.
+    pub fn history(&self) -> Vec<RunRecord> {
+        self.storage.history()
+    }
+
+    /// Handles of the clashes that can be picked, sorted.
+    pub fn stored_handles(&self) -> Result<Vec<PublicHandle>> {
+        self.storage.stored_handles()
+    }
+
+    /// The handle that `query` refers to, with a handle prefix resolved to
+    /// the stored clash that it is the prefix of.
+    pub fn resolve(&self, query: &HandleQuery) -> Result<PublicHandle> {
+        query.resolve(&self.storage.known_handles())
+    }
+
+    pub fn read_clash(&self, handle: &PublicHandle) -> Result<Clash> {
+        let contents = self
+            .storage
+            .read_clash(handle)?
+            .ok_or_else(|| Error::NotFound(format!("Unable to find clash with handle {}", handle)))?;
+        serde_json::from_str(&contents).with_context(|| format!("Unable to deserialize clash {}", handle))
+    }
+
+    /// Picks a stored clash that matches `filter` using `strategy`.
+    pub fn pick_handle(&self, filter: &ClashFilter, strategy: Strategy) -> Result<PublicHandle> {
+        let handles = self.storage.stored_handles()?;
+        if handles.is_empty() {
+            return Err(Error::NotFound(
+                "No clashes to choose from (use `coctus fetch` to download some)".to_string(),
+            ))
+        }
+        let metadata_store = self.storage.metadata()?;
+        let handles: Vec<PublicHandle> = handles
+            .into_iter()
+            .filter(|handle| filter.matches_metadata(&metadata_store.get(handle)))
+            .collect();
+
+        // Only read the clashes when the filter or the strategy needs them
+        let candidates: Vec<Candidate> = if !filter.needs_clash() && strategy != Strategy::Weighted {
+            handles
+                .into_iter()
+                .map(|handle| Candidate {
+                    handle,
+                    upvotes: 0,
+                    downvotes: 0,
+                })
+                .collect()
+        } else {
+            let mut candidates = Vec::new();
+            for handle in handles {
+                let clash = self.read_clash(&handle)?;
+                if filter.matches(&clash) {
+                    candidates.push(Candidate {
+                        handle,
+                        upvotes: clash.upvotes(),
+                        downvotes: clash.downvotes(),
+                    });
+                }
+            }
+            candidates
+        };
+
+        if candidates.is_empty() {
+            return Err(Error::NotFound("No stored clash matches the given filters".to_string()))
+        }
+
+        let history = self.storage.history();
+        let mut rng = rand::thread_rng();
+        match strategy.choose(&candidates, &history, crate::game::unix_time_now(), &mut rng) {
+            Some(candidate) => Ok(candidate.handle.clone()),
+            None if strategy == Strategy::RetryFailed => {
+                Err(Error::NotFound("No failed clashes are due for a retry".to_string()))
+            }
+            None => Err(Error::NotFound(format!(
+                "Unable to pick the next clash with the {strategy} strategy"
+            ))),
+        }
+    }
+
+    /// Stores the clash with `handle` (as returned by CodinGame).
+    pub fn add_clash(&self, handle: &PublicHandle, contents: &str) -> Result<()> {
+        self.storage.write_clash(handle, contents)
+    }
+
+    /// Downloads the clash with `handle` from CodinGame and stores it.
+    pub fn fetch(&self, handle: &PublicHandle) -> Result<()> {
+        let contents = download_clash(handle)?;
+        self.add_clash(handle, &contents)
+    }
+
+    /// The stub templates of `language`: the ones of the user if they have
+    /// any, otherwise the ones embedded into coctus.
+    pub fn stub_config(&self, language: &str) -> Result<StubConfig> {
+        match self.storage.user_stub_config(language)? {
+            Some(stub_config) => Ok(stub_config),
+            None => StubConfig::read_from_embedded(language),
+        }
+    }
+
+    /// Languages with stub templates of the user (first) or embedded into
+    /// coctus.
+    pub fn available_languages(&self) -> Vec<String> {
+        let mut languages = self.storage.user_stub_languages();
+        for language in stub::embedded_languages() {
+            if !languages.iter().any(|known| known == language) {
+                languages.push(language.to_string());
+            }
+        }
+        languages
+    }
+
+    fn run_hook(&self, event: HookEvent, context: &HookContext) -> Result<()> {
+        self.hooks.run(event, context, self.command_output)
+    }
+}
+
+/// Downloads the JSON of the clash with `handle` from CodinGame.
+pub fn download_clash(handle: &PublicHandle) -> Result<String> {
+    let req = ureq::post("https://www.codingame.com/services/Contribution/findContribution")
+        .set("Content-Type", "application/json");
+    match req.send_string(&format!(r#"["{}", true]"#, handle)) {
+        Ok(res) => res
+            .into_string()
+            .with_context(|| format!("Unable to read the response for clash {}", handle)),
+        Err(ureq::Error::Status(status, res)) => {
+            Err(Error::Http(format!("HTTP {} {} from {}", status, res.status_text(), res.get_url())))
+        }
+        Err(err) => Err(Error::Http(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::clash::Mode;
+    use crate::test_helper::{sample_handle, workspace_with_sample_clash};
+
+    #[test]
+    fn test_current_handle() {
+        let workspace = workspace_with_sample_clash();
+        assert!(workspace.current_handle().is_err());
+        let query = HandleQuery::from_str("90435e").unwrap();
+        workspace.set_current_handle(&workspace.resolve(&query).unwrap()).unwrap();
+        assert_eq!(workspace.current_handle().unwrap(), sample_handle());
+        assert_eq!(workspace.read_clash(&sample_handle()).unwrap().title(), "Boggus test");
+    }
+
+    #[test]
+    fn test_pick_handle() {
+        let mut workspace = Workspace::new(MemoryStorage::new());
+        assert!(workspace.pick_handle(&ClashFilter::default(), Strategy::Uniform).is_err());
+
+        workspace = workspace_with_sample_clash();
+        for strategy in [
+            Strategy::Uniform,
+            Strategy::Weighted,
+            Strategy::Unseen,
+            Strategy::Daily,
+        ] {
+            assert_eq!(workspace.pick_handle(&ClashFilter::default(), strategy).unwrap(), sample_handle());
+        }
+        // Nothing was failed, so there is nothing to retry
+        assert!(workspace.pick_handle(&ClashFilter::default(), Strategy::RetryFailed).is_err());
+
+        let clash = workspace.read_clash(&sample_handle()).unwrap();
+        let missing_mode = [Mode::Fastest, Mode::Shortest, Mode::Reverse]
+            .into_iter()
+            .find(|mode| !clash.has_mode(*mode))
+            .unwrap();
+        let filter = ClashFilter::with_modes(vec![missing_mode]);
+        assert!(workspace.pick_handle(&filter, Strategy::Uniform).is_err());
+
+        workspace
+            .update_metadata(&sample_handle(), |metadata| metadata.hidden = true)
+            .unwrap();
+        assert!(workspace.pick_handle(&ClashFilter::default(), Strategy::Uniform).is_err());
+    }
+
+    #[test]
+    fn test_stub_config_precedence() {
+        let mut storage = MemoryStorage::new();
+        storage
+            .stub_configs
+            .insert(String::from("brainfuck"), StubConfig::read_from_embedded("c").unwrap());
+        storage
+            .stub_configs
+            .insert(String::from("python"), StubConfig::read_from_embedded("c").unwrap());
+        let workspace = Workspace::new(storage);
+
+        let languages = workspace.available_languages();
+        assert_eq!(languages[..2], ["brainfuck", "python"]);
+        assert_eq!(languages.iter().filter(|language| *language == "python").count(), 1);
+        assert_eq!(workspace.stub_config("python").unwrap().source_file_ext(), "c");
+        assert_eq!(workspace.stub_config("ruby").unwrap().source_file_ext(), "rb");
+        assert!(workspace.stub_config("klingon").is_err());
+    }
+
+    #[test]
+    fn test_workspace_file_takes_precedence() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let other = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap();
+        let workspace_file = WorkspaceFile {
+            handle: Some(other.clone()),
+            ..Default::default()
+        };
+        WorkspaceFile::create(&dir, workspace_file).unwrap();
+
+        let workspace = workspace_with_sample_clash().with_dir(dir.join("nested"));
+        assert_eq!(workspace.current_handle().unwrap(), other);
+        workspace.set_current_handle(&sample_handle()).unwrap();
+        assert_eq!(workspace.current_handle().unwrap(), sample_handle());
+        assert_eq!(WorkspaceFile::discover(&dir).unwrap().unwrap().handle, Some(sample_handle()));
+        // The current clash outside of the workspace is left alone
+        assert_eq!(workspace.storage().current_handle().unwrap(), None);
+        assert_eq!(workspace.previous_handle(1).unwrap(), other);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_navigation() {
+        let workspace = workspace_with_sample_clash();
+        let other = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap();
+        assert!(workspace.previous_handle(1).is_err());
+        workspace.set_current_handle(&other).unwrap();
+        workspace.set_current_handle(&sample_handle()).unwrap();
+        assert_eq!(workspace.previous_handle(1).unwrap(), other);
+        assert!(workspace.previous_handle(2).is_err());
+
+        workspace.add_bookmark("teaching", other.clone()).unwrap();
+        assert_eq!(workspace.bookmark("teaching").unwrap(), other);
+        assert_eq!(workspace.remove_bookmark("teaching").unwrap(), other);
+        assert!(workspace.bookmark("teaching").is_err());
+        assert!(workspace.remove_bookmark("teaching").is_err());
+    }
+}
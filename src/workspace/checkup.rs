@@ -0,0 +1,282 @@
+use std::error::Error as StdError;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{FileStorage, Storage, Workspace, WORKSPACE_FILE_NAME};
+use crate::clash::{Clash, PublicHandle};
+use crate::error::Context;
+use crate::library::MAIN_LIBRARY;
+use crate::solution::missing_program;
+use crate::stub::{self, StubConfig};
+use crate::{Error, Result};
+
+/// How serious a [Finding] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Ok,
+    /// Something that may be a problem, depending on how coctus is used.
+    Warning,
+    Error,
+}
+
+/// Outcome of a check of the setup of coctus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    /// How to fix the problem (empty if there is none).
+    pub fix: String,
+}
+
+impl Finding {
+    pub fn ok(message: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Ok,
+            message: message.into(),
+            fix: String::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Error,
+            message: message.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+impl<S: Storage> Workspace<S> {
+    /// Checks the workspace file that applies (`None` if there is none).
+    pub fn check_workspace_file(&self) -> Option<Finding> {
+        match self.workspace_file() {
+            Ok(Some(workspace_file)) => {
+                Some(Finding::ok(format!("Workspace file: {}", workspace_file.path().display())))
+            }
+            Ok(None) => None,
+            Err(err) => Some(Finding::error(
+                format!("Invalid workspace file: {}", describe(&err)),
+                format!("Fix or remove the {} file", WORKSPACE_FILE_NAME),
+            )),
+        }
+    }
+
+    /// Checks that the programs of the commands of the workspace file, of
+    /// the hooks and of the languages can be found.
+    pub fn check_commands(&self) -> Result<Vec<Finding>> {
+        let mut findings = Vec::new();
+        if let Ok(Some(workspace_file)) = self.workspace_file() {
+            let build_command = workspace_file.build_command.as_deref();
+            for (key, command) in [
+                ("build_command", build_command),
+                ("command", workspace_file.command.as_deref()),
+            ] {
+                let Some(program) = command.and_then(|command| missing_program(command, &workspace_file.dir))
+                else {
+                    continue
+                };
+                // The run command may start a program that is not built yet
+                if key == "command" && build_command.is_some() && program.contains('/') {
+                    continue
+                }
+                findings.push(Finding::error(
+                    format!("{} of the workspace runs {}, which can not be found", key, program),
+                    format!("Install {} or change `{}` in {}", program, key, workspace_file.path().display()),
+                ));
+            }
+        }
+
+        let dir = match &self.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                std::env::current_dir().with_context(|| "Unable to find the current directory".to_string())?
+            }
+        };
+        for (event, command) in self.hooks.configured() {
+            if let Some(program) = missing_program(command, &dir) {
+                findings.push(Finding::error(
+                    format!("Hook {} runs {}, which can not be found", event.name(), program),
+                    format!(
+                        "Install {} or change it with `coctus config set hooks.{}`",
+                        program,
+                        event.name()
+                    ),
+                ));
+            }
+        }
+
+        let mut num_ok = 0;
+        for language in self.available_languages() {
+            let Ok(stub_config) = self.stub_config(&language) else {
+                continue
+            };
+            let source_file = format!("solution.{}", stub_config.source_file_ext());
+            // The run command of compiled languages starts the built program
+            let command = stub_config.build_command(&source_file).or(stub_config.run_command(&source_file));
+            match command.and_then(|command| missing_program(&command, &dir)) {
+                Some(program) => findings.push(Finding::warning(
+                    format!("{} is not installed, it is needed to run {} solutions", program, language),
+                    format!("Install {} if you want to solve clashes in {}", program, language),
+                )),
+                None => num_ok += 1,
+            }
+        }
+        findings.push(Finding::ok(format!("{} languages can be run", num_ok)));
+        Ok(findings)
+    }
+}
+
+impl Workspace<FileStorage> {
+    /// Checks that there is a current clash and that it is stored.
+    pub fn check_current_clash(&self) -> Finding {
+        let workspace_handle = self.workspace_file().ok().flatten().and_then(|workspace| workspace.handle);
+        let handle = match workspace_handle {
+            Some(handle) => handle,
+            None => match self.storage.current_handle() {
+                Ok(Some(handle)) => handle,
+                Ok(None) => return Finding::warning("No current clash", "Pick one with `coctus next`"),
+                Err(err) => {
+                    return Finding::error(
+                        format!(
+                            "Invalid current clash in {}: {}",
+                            self.storage.current_clash_file().display(),
+                            describe(&err)
+                        ),
+                        "Pick a new clash with `coctus next`",
+                    )
+                }
+            },
+        };
+        match self.storage.clash_file(&handle) {
+            Some(path) => Finding::ok(format!("Current clash {} ({})", handle, path.display())),
+            None => Finding::error(
+                format!("Current clash {} is not stored in any library", handle),
+                format!("Fetch it with `coctus fetch {}` or pick another clash with `coctus next`", handle),
+            ),
+        }
+    }
+
+    /// Checks that the stub templates of the user and the ones embedded into
+    /// coctus can generate stubs.
+    pub fn check_stub_templates(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let fix = "Each language directory needs a stub_config.toml and the .jinja templates it uses";
+        if let Ok(entries) = std::fs::read_dir(self.storage.stub_templates_dir()) {
+            for path in entries.filter_map(|entry| Some(entry.ok()?.path())).filter(|path| path.is_dir()) {
+                match StubConfig::read_from_dir(path.clone()).and_then(stub::check_stub_config) {
+                    Ok(()) => findings.push(Finding::ok(format!("{}", path.display()))),
+                    Err(err) => {
+                        findings.push(Finding::error(format!("{}: {}", path.display(), describe(&err)), fix))
+                    }
+                }
+            }
+        }
+
+        let embedded_languages = stub::embedded_languages();
+        let broken_languages: Vec<&str> = embedded_languages
+            .iter()
+            .copied()
+            .filter(|language| {
+                StubConfig::read_from_embedded(language).and_then(stub::check_stub_config).is_err()
+            })
+            .collect();
+        findings.push(match broken_languages.as_slice() {
+            [] => Finding::ok(format!("{} embedded languages", embedded_languages.len())),
+            _ => Finding::error(
+                format!("Broken embedded stub templates: {}", broken_languages.join(", ")),
+                "This is a bug in coctus, please report it",
+            ),
+        });
+        findings
+    }
+}
+
+/// Checks that the clashes of the library `name` stored in `dir` can be
+/// read.
+pub fn check_library(name: &str, dir: &Path) -> Vec<Finding> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) if name == MAIN_LIBRARY => {
+            return vec![Finding::warning(
+                format!("No clashes stored in the {} library", name),
+                "Fetch some with `coctus fetch PUBLIC_HANDLE`",
+            )]
+        }
+        Err(err) => {
+            return vec![Finding::error(
+                format!("Unable to read library {} at {}: {}", name, dir.display(), err),
+                format!("Create the directory or remove the library with `coctus library forget {}`", name),
+            )]
+        }
+    };
+    let mut findings = Vec::new();
+    let mut num_clashes = 0;
+    let paths = entries.filter_map(|entry| Some(entry.ok()?.path()));
+    for path in paths.filter(|path| path.extension().is_some_and(|ext| ext == "json")) {
+        match read_clash_file(&path) {
+            Ok(_) => num_clashes += 1,
+            Err(err) => {
+                let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+                let fix = match PublicHandle::from_str(stem) {
+                    Ok(handle) => format!("Fetch it again with `coctus fetch {}` or delete the file", handle),
+                    Err(_) => String::from("Delete the file (clash files are named PUBLIC_HANDLE.json)"),
+                };
+                findings.push(Finding::error(format!("{}: {}", path.display(), describe(&err)), fix));
+            }
+        }
+    }
+    findings.push(Finding::ok(format!(
+        "Library {}: {} clashes in {}",
+        name,
+        num_clashes,
+        dir.display()
+    )));
+    findings
+}
+
+fn read_clash_file(path: &Path) -> Result<Clash> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("Unable to read {:?}", path))?;
+    serde_json::from_str(&contents).with_context(|| format!("Unable to deserialize {:?}", path))
+}
+
+/// `err` followed by its causes.
+fn describe(err: &Error) -> String {
+    let mut description = err.to_string();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        description.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broken_clash_files_are_errors() {
+        let dir = std::env::temp_dir().join(format!("coctus-checkup-library-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::copy("fixtures/puzzles/stub_and_solution_tester.json", dir.join("a.json")).unwrap();
+        std::fs::write(dir.join("682102420fbce0fce95e0ee56095ea2b9925.json"), "{").unwrap();
+
+        let findings = check_library("fixture", &dir);
+        let severities: Vec<Severity> = findings.iter().map(|finding| finding.severity).collect();
+        assert_eq!(severities, [Severity::Error, Severity::Ok]);
+        assert!(findings[0].fix.contains("coctus fetch 682102420fbce0fce95e0ee56095ea2b9925"));
+        assert!(findings[1].message.contains("1 clashes"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(check_library(MAIN_LIBRARY, &dir)[0].severity, Severity::Warning);
+        assert_eq!(check_library("fixture", &dir)[0].severity, Severity::Error);
+    }
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+use std::time::Duration;
+
+use super::{RunProfile, Storage, Workspace};
+use crate::clash::{Clash, Mode, PublicHandle};
+use crate::error::Context;
+use crate::game::{self, GameResult, Hotseat, PracticeSession};
+use crate::{solution, Error, Result};
+
+/// Outcome of [Workspace::submit_practice].
+#[derive(Debug, Clone)]
+pub enum PracticeSubmission {
+    /// The time limit had passed, so nothing was recorded.
+    TimeUp,
+    Submitted {
+        result: GameResult,
+        /// Best earlier result of the same clash and mode (if any).
+        previous_best: Option<GameResult>,
+    },
+}
+
+impl<S: Storage> Workspace<S> {
+    /// The practice clash in progress (if any).
+    pub fn practice_session(&self) -> Option<PracticeSession> {
+        self.storage.practice_session()
+    }
+
+    /// Starts a practice clash, replacing the one in progress (if any).
+    pub fn start_practice(
+        &self,
+        handle: PublicHandle,
+        mode: Mode,
+        time_limit: Duration,
+    ) -> Result<PracticeSession> {
+        let session = PracticeSession::start(handle, mode, time_limit);
+        self.storage.write_practice_session(Some(&session))?;
+        Ok(session)
+    }
+
+    /// Abandons the practice clash in progress.
+    pub fn stop_practice(&self) -> Result<()> {
+        if self.storage.practice_session().is_none() {
+            return Err(Error::NotFound("No practice clash in progress".to_string()))
+        }
+        self.storage.write_practice_session(None)
+    }
+
+    /// Ends the practice clash `session` by scoring the solution of
+    /// `profile` (which should be built already) against all testcases and
+    /// storing the result, unless the time is up. The length of the solution
+    /// is the number of characters in the sources of `profile`.
+    pub fn submit_practice(
+        &self,
+        session: &PracticeSession,
+        profile: &RunProfile,
+        timeout: Duration,
+    ) -> Result<PracticeSubmission> {
+        self.storage.write_practice_session(None)?;
+        if session.is_time_up() {
+            return Ok(PracticeSubmission::TimeUp)
+        }
+
+        let clash = self.read_clash(&session.handle)?;
+        let length = match profile.sources.as_slice() {
+            [] => None,
+            sources => {
+                let mut length = 0;
+                for source in sources {
+                    length += source_length(source)?;
+                }
+                Some(length)
+            }
+        };
+        let result = GameResult {
+            handle: session.handle.clone(),
+            mode: session.mode,
+            score: score(&clash, profile, timeout)?,
+            time: session.elapsed().as_secs(),
+            length,
+            submitted_at: game::unix_time_now(),
+        };
+
+        let previous_best = self
+            .storage
+            .results()
+            .into_iter()
+            .filter(|r| r.handle == result.handle && r.mode == result.mode)
+            .reduce(|best, r| if r.is_better_than(&best) { r } else { best });
+        self.storage.append_result(&result)?;
+        Ok(PracticeSubmission::Submitted {
+            result,
+            previous_best,
+        })
+    }
+
+    /// Results of the finished practice clashes, oldest first.
+    pub fn results(&self) -> Vec<GameResult> {
+        self.storage.results()
+    }
+
+    /// The hot-seat clash in progress.
+    pub fn hotseat(&self) -> Result<Hotseat> {
+        self.storage.hotseat()?.ok_or_else(|| {
+            Error::NotFound("No hot-seat clash in progress (use `coctus hotseat start`)".to_string())
+        })
+    }
+
+    /// Starts a hot-seat clash, replacing the one in progress (if any).
+    pub fn start_hotseat(&self, handle: PublicHandle, mode: Mode, players: Vec<String>) -> Result<Hotseat> {
+        let hotseat = Hotseat::start(handle, mode, players)?;
+        self.storage.write_hotseat(&hotseat)?;
+        Ok(hotseat)
+    }
+
+    /// Scores the solution of `player` in `source_file`, run with `profile`
+    /// (which should be built already), and records it as their submission
+    /// to the hot-seat clash in progress.
+    pub fn submit_hotseat(
+        &self,
+        player: &str,
+        source_file: &Path,
+        profile: &RunProfile,
+        timeout: Duration,
+    ) -> Result<(Hotseat, GameResult)> {
+        let mut hotseat = self.hotseat()?;
+        hotseat.check_player(player)?;
+        let length = source_length(source_file)?;
+        let clash = self.read_clash(&hotseat.handle)?;
+        let score = score(&clash, profile, timeout)?;
+
+        let result = hotseat.submit(player, score, Some(length))?.clone();
+        self.storage.write_hotseat(&hotseat)?;
+        Ok((hotseat, result))
+    }
+}
+
+/// Score of the solution of `profile` on all testcases of `clash`.
+fn score(clash: &Clash, profile: &RunProfile, timeout: Duration) -> Result<u32> {
+    let mut run_command = profile.run_command()?;
+    let num_passed = solution::lazy_run(clash.testcases(), &mut run_command, &timeout)
+        .into_iter()
+        .filter(|(_, test_result)| test_result.is_success())
+        .count();
+    Ok(game::score(num_passed, clash.testcases().len()))
+}
+
+/// Number of characters in `source_file`.
+fn source_length(source_file: &Path) -> Result<usize> {
+    let contents =
+        std::fs::read_to_string(source_file).with_context(|| format!("Unable to read {:?}", source_file))?;
+    Ok(contents.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_helper::{sample_handle, workspace_with_sample_clash};
+
+    fn profile(command: &str) -> RunProfile {
+        RunProfile {
+            command: command.to_string(),
+            sources: vec![["fixtures", "workspace", "sol.sh"].iter().collect()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn practice_results_are_stored() {
+        let (workspace, handle) = (workspace_with_sample_clash(), sample_handle());
+        assert!(workspace.stop_practice().is_err());
+        let timeout = Duration::from_secs(5);
+
+        let session = workspace
+            .start_practice(handle.clone(), Mode::Shortest, game::DEFAULT_TIME_LIMIT)
+            .unwrap();
+        let PracticeSubmission::Submitted {
+            result,
+            previous_best,
+        } = workspace.submit_practice(&session, &profile("cat"), timeout).unwrap()
+        else {
+            panic!("the time should not be up")
+        };
+        assert_eq!(result.score, 0);
+        assert!(result.length.is_some());
+        assert!(previous_best.is_none());
+        assert!(workspace.practice_session().is_none());
+
+        let session = workspace.start_practice(handle, Mode::Shortest, game::DEFAULT_TIME_LIMIT).unwrap();
+        let PracticeSubmission::Submitted {
+            result,
+            previous_best,
+        } = workspace.submit_practice(&session, &profile("tr X b"), timeout).unwrap()
+        else {
+            panic!("the time should not be up")
+        };
+        assert_eq!(result.score, 100);
+        assert_eq!(previous_best.unwrap().score, 0);
+        assert_eq!(workspace.results().len(), 2);
+    }
+
+    #[test]
+    fn practice_after_the_time_limit_is_not_scored() {
+        let (workspace, handle) = (workspace_with_sample_clash(), sample_handle());
+        let mut session = workspace.start_practice(handle, Mode::Fastest, game::DEFAULT_TIME_LIMIT).unwrap();
+        session.started_at -= 16 * 60;
+        let submission = workspace
+            .submit_practice(&session, &profile("tr X b"), Duration::from_secs(5))
+            .unwrap();
+        assert!(matches!(submission, PracticeSubmission::TimeUp));
+        assert!(workspace.results().is_empty());
+    }
+
+    #[test]
+    fn hotseat_submissions_are_stored() {
+        let (workspace, handle) = (workspace_with_sample_clash(), sample_handle());
+        assert!(workspace.hotseat().is_err());
+        let players = ["alice", "bob"].map(String::from).to_vec();
+        workspace.start_hotseat(handle, Mode::Fastest, players).unwrap();
+
+        let source_file: PathBuf = ["fixtures", "workspace", "sol.sh"].iter().collect();
+        let timeout = Duration::from_secs(5);
+        assert!(workspace.submit_hotseat("eve", &source_file, &profile("tr X b"), timeout).is_err());
+        let (hotseat, result) =
+            workspace.submit_hotseat("alice", &source_file, &profile("tr X b"), timeout).unwrap();
+        assert_eq!(result.score, 100);
+        assert_eq!(hotseat.waiting_for(), ["bob"]);
+        assert_eq!(workspace.hotseat().unwrap().submissions.len(), 1);
+    }
+}
@@ -0,0 +1,325 @@
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+
+use super::{BuildStatus, RunProfile, Storage, TestEvent, TestOptions, Workspace};
+use crate::clash::{Clash, PublicHandle, Testcase};
+use crate::error::Context;
+use crate::solution::saved::{self, SavedSolution, StoredSolution};
+use crate::solution::TestResult;
+use crate::{Error, Result};
+
+/// Outcome of [Workspace::verify_solution].
+#[derive(Debug)]
+pub struct Verification {
+    /// The clash that the solution was verified against.
+    pub clash: Clash,
+    pub build: BuildStatus,
+    pub passed: usize,
+    pub total: usize,
+    /// Testcases that failed, with their results.
+    pub failures: Vec<(Testcase, TestResult)>,
+}
+
+impl Verification {
+    pub fn all_passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl<S: Storage> Workspace<S> {
+    /// Directory of the saved solutions.
+    pub fn solutions_dir(&self) -> Result<&Path> {
+        self.storage
+            .solutions_dir()
+            .ok_or_else(|| Error::NotFound("Solutions can not be saved in this workspace".to_string()))
+    }
+
+    /// The saved solutions (to the clash with `handle` and in `language` if
+    /// given), sorted by handle and language.
+    pub fn saved_solutions(
+        &self,
+        handle: Option<&PublicHandle>,
+        language: Option<&str>,
+    ) -> Result<Vec<StoredSolution>> {
+        let Some(solutions_dir) = self.storage.solutions_dir() else {
+            return Ok(Vec::new())
+        };
+        let solutions = match handle {
+            Some(handle) => saved::saved_solutions_for_handle(solutions_dir, handle)?,
+            None => saved::saved_solutions(solutions_dir)?,
+        };
+        Ok(solutions
+            .into_iter()
+            .filter(|solution| language.is_none_or(|language| saved::stored_language(solution) == language))
+            .collect())
+    }
+
+    /// The saved solution to the clash with `handle` in `language`.
+    pub fn saved_solution(&self, handle: &PublicHandle, language: &str) -> Result<SavedSolution> {
+        self.saved_solutions(Some(handle), Some(language))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::NotFound(format!("No saved {} solution for clash {}", language, handle)))?
+            .map_err(|broken| broken.error)
+    }
+
+    /// Builds `solution` and runs it against all testcases of its clash
+    /// (only the time limits of `options` are used), then records in its
+    /// `solution.toml` whether it passed. The hooks are not run and the run
+    /// history is left alone.
+    pub fn verify_solution(
+        &self,
+        solution: &mut SavedSolution,
+        options: &TestOptions,
+    ) -> Result<Verification> {
+        let clash = self.read_clash(&solution.handle)?;
+        let profile = RunProfile {
+            command: solution.command(),
+            build_command: solution.build_command(),
+            sources: vec![solution.source_path()],
+            dir: Some(solution.dir.clone()),
+        };
+        let options = TestOptions {
+            record: false,
+            ..TestOptions::new(options.timeout, options.build_timeout)
+        };
+
+        let mut build = BuildStatus::NotNeeded;
+        let mut failures = Vec::new();
+        let summary = self.run_tests(&solution.handle, &profile, &options, |event| {
+            match event {
+                TestEvent::Build(status) => build = status,
+                TestEvent::Result { testcase, result, .. } => {
+                    if !result.is_success() {
+                        failures.push((testcase.clone(), result));
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        })?;
+
+        let passed = summary.all_passed();
+        if solution.meta.passed != Some(passed) {
+            solution.meta.passed = Some(passed);
+            solution.write_meta()?;
+        }
+        Ok(Verification {
+            clash,
+            build,
+            passed: summary.passed,
+            total: summary.total,
+            failures,
+        })
+    }
+
+    /// Saves a copy of `source_file` as the solution to the clash with
+    /// `handle` in `language` and verifies it with the time limits of
+    /// `options`. Without a `command`, the commands of the previously saved
+    /// solution are used, or else the ones of the stub templates of
+    /// `language`.
+    ///
+    /// The previously saved solution is only replaced (along with its source
+    /// file, which may have another name) once the new one could be verified,
+    /// so it is kept if the new one does not build.
+    pub fn save_solution(
+        &self,
+        handle: &PublicHandle,
+        language: &str,
+        source_file: &Path,
+        command: Option<String>,
+        build_command: Option<String>,
+        options: &TestOptions,
+    ) -> Result<(SavedSolution, Verification)> {
+        let solutions_dir = self.solutions_dir()?;
+        // Make sure the clash exists before saving anything
+        self.read_clash(handle)?;
+
+        let previous = self.saved_solution(handle, language).ok();
+        let (command, build_command) = match (command, previous) {
+            (Some(command), previous) => {
+                (command, build_command.or(previous.and_then(|previous| previous.meta.build_command)))
+            }
+            (None, Some(previous)) => (previous.meta.command, build_command.or(previous.meta.build_command)),
+            (None, None) => {
+                let stub_config = self.stub_config(language).ok();
+                let command = stub_config
+                    .as_ref()
+                    .and_then(|stub_config| stub_config.run_command(saved::SOURCE_FILE_PLACEHOLDER))
+                    .ok_or_else(|| {
+                        Error::NotFound(format!(
+                            "No run command is known for {language} (use --command to specify it)"
+                        ))
+                    })?;
+                let build_command = build_command.or(stub_config
+                    .and_then(|stub_config| stub_config.build_command(saved::SOURCE_FILE_PLACEHOLDER)));
+                (command, build_command)
+            }
+        };
+
+        // Names that are not handles are skipped when listing saved solutions
+        let staging_dir = solutions_dir.join(format!(".saving-{}-{}", handle, language));
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)
+                .with_context(|| format!("Unable to remove {:?}", &staging_dir))?;
+        }
+        let mut solution = SavedSolution::write_to_dir(
+            staging_dir.clone(),
+            handle.clone(),
+            language,
+            source_file,
+            command,
+            build_command,
+        )?;
+        let verification = match self.verify_solution(&mut solution, options) {
+            Ok(verification) => verification,
+            Err(err) => {
+                let _ = std::fs::remove_dir_all(&staging_dir);
+                return Err(err)
+            }
+        };
+        solution.move_to(saved::solution_dir(solutions_dir, handle, language))?;
+        Ok((solution, verification))
+    }
+
+    /// Copies the saved solution to the clash with `handle` in `language` to
+    /// `output` (default: its file name in the current directory) and returns
+    /// it along with the path of the copy. An existing file is only
+    /// overwritten if `overwrite` is true.
+    pub fn checkout_solution(
+        &self,
+        handle: &PublicHandle,
+        language: &str,
+        output: Option<&Path>,
+        overwrite: bool,
+    ) -> Result<(SavedSolution, PathBuf)> {
+        let solution = self.saved_solution(handle, language)?;
+        let output = match output {
+            Some(output) => output.to_path_buf(),
+            None => PathBuf::from(&solution.meta.file),
+        };
+        if output.exists() && !overwrite {
+            return Err(Error::InvalidInput(format!(
+                "{} already exists (use --force to overwrite it)",
+                output.display()
+            )))
+        }
+        std::fs::copy(solution.source_path(), &output)
+            .with_context(|| format!("Unable to write {:?}", &output))?;
+        Ok((solution, output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_helper::{sample_handle, store_sample_clash};
+    use crate::workspace::MemoryStorage;
+
+    fn options() -> TestOptions {
+        TestOptions::new(Duration::from_secs(5), Duration::from_secs(5))
+    }
+
+    #[test]
+    fn saved_solutions_are_verified_and_checked_out() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-solutions-{}", std::process::id()));
+        let mut storage = MemoryStorage::new();
+        storage.solutions_dir = Some(dir.join("solutions"));
+        store_sample_clash(&storage);
+        let workspace = Workspace::new(storage);
+        let source_file: PathBuf = ["fixtures", "workspace", "sol.sh"].iter().collect();
+
+        assert!(workspace
+            .save_solution(&sample_handle(), "bash", &source_file, None, None, &options())
+            .is_err());
+        let command = Some(String::from("bash {file}"));
+        let (solution, verification) = workspace
+            .save_solution(&sample_handle(), "bash", &source_file, command, None, &options())
+            .unwrap();
+        assert!(verification.all_passed());
+        assert_eq!(verification.passed, verification.total);
+        assert_eq!(solution.meta.passed, Some(true));
+        // Verifying saved solutions does not count as solving the clash
+        assert!(workspace.history().is_empty());
+
+        // The command of the previous solution is used again
+        let (solution, _) = workspace
+            .save_solution(&sample_handle(), "bash", &source_file, None, None, &options())
+            .unwrap();
+        assert_eq!(solution.meta.command, "bash {file}");
+        assert_eq!(workspace.saved_solutions(None, Some("bash")).unwrap().len(), 1);
+        assert!(workspace.saved_solutions(Some(&sample_handle()), Some("ruby")).unwrap().is_empty());
+
+        let output = dir.join("checkout.sh");
+        let (_, path) = workspace.checkout_solution(&sample_handle(), "bash", Some(&output), false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            std::fs::read_to_string(&source_file).unwrap()
+        );
+        assert!(workspace.checkout_solution(&sample_handle(), "bash", Some(&output), false).is_err());
+        assert!(workspace.checkout_solution(&sample_handle(), "bash", Some(&output), true).is_ok());
+        assert!(workspace.checkout_solution(&sample_handle(), "ruby", Some(&output), true).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn solutions_are_only_replaced_once_verified() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-replace-{}", std::process::id()));
+        let mut storage = MemoryStorage::new();
+        storage.solutions_dir = Some(dir.join("solutions"));
+        store_sample_clash(&storage);
+        let workspace = Workspace::new(storage);
+        std::fs::create_dir_all(&dir).unwrap();
+        let first_file = dir.join("first.sh");
+        std::fs::write(&first_file, "tr X b").unwrap();
+        let command = Some(String::from("bash {file}"));
+        workspace
+            .save_solution(&sample_handle(), "bash", &first_file, command.clone(), None, &options())
+            .unwrap();
+
+        let second_file = dir.join("second.sh");
+        std::fs::write(&second_file, "cat").unwrap();
+        let build_command = Some(String::from("false"));
+        assert!(workspace
+            .save_solution(&sample_handle(), "bash", &second_file, command.clone(), build_command, &options())
+            .is_err());
+        let solution = workspace.saved_solution(&sample_handle(), "bash").unwrap();
+        assert_eq!(solution.meta.file, "first.sh");
+        assert_eq!(solution.meta.passed, Some(true));
+        assert_eq!(workspace.saved_solutions(None, None).unwrap().len(), 1);
+
+        let (solution, _) = workspace
+            .save_solution(&sample_handle(), "bash", &second_file, command, None, &options())
+            .unwrap();
+        assert_eq!(solution.meta.file, "second.sh");
+        assert!(solution.source_path().is_file());
+        // The source file of the previous solution is gone
+        assert!(!solution.dir.join("first.sh").exists());
+        assert_eq!(std::fs::read_dir(dir.join("solutions")).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failing_solutions_are_recorded_as_failed() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-failing-{}", std::process::id()));
+        let mut storage = MemoryStorage::new();
+        storage.solutions_dir = Some(dir.clone());
+        store_sample_clash(&storage);
+        let workspace = Workspace::new(storage);
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_file = dir.join("sol.sh");
+        std::fs::write(&source_file, "cat").unwrap();
+
+        let command = Some(String::from("bash {file}"));
+        let (_, verification) = workspace
+            .save_solution(&sample_handle(), "bash", &source_file, command, None, &options())
+            .unwrap();
+        assert!(!verification.all_passed());
+        assert_eq!(verification.failures.len(), verification.total - verification.passed);
+        let mut solution = workspace.saved_solution(&sample_handle(), "bash").unwrap();
+        assert_eq!(solution.meta.passed, Some(false));
+        assert!(!workspace.verify_solution(&mut solution, &options()).unwrap().all_passed());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,485 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::game::{GameResult, Hotseat, PracticeSession};
+use crate::history::{self, RunRecord};
+use crate::library::{self, Libraries};
+use crate::metadata::MetadataStore;
+use crate::navigation::Navigation;
+use crate::solution::build::CachedBuild;
+use crate::stub::StubConfig;
+use crate::{Error, Result};
+
+/// `Storage` is where a [Workspace](super::Workspace) keeps its clashes and its
+/// state.
+pub trait Storage {
+    /// The current clash, `None` if no clash was made current yet.
+    fn current_handle(&self) -> Result<Option<PublicHandle>>;
+
+    fn set_current_handle(&self, handle: &PublicHandle) -> Result<()>;
+
+    /// Handles of the clashes that can be picked, sorted.
+    fn stored_handles(&self) -> Result<Vec<PublicHandle>>;
+
+    /// Handles of all stored clashes, including the ones that can only be
+    /// opened by handle (like the clashes of other libraries).
+    fn known_handles(&self) -> Vec<PublicHandle>;
+
+    /// The JSON of the clash with `handle`, `None` if it is not stored.
+    fn read_clash(&self, handle: &PublicHandle) -> Result<Option<String>>;
+
+    fn write_clash(&self, handle: &PublicHandle, contents: &str) -> Result<()>;
+
+    fn metadata(&self) -> Result<MetadataStore>;
+
+    fn write_metadata(&self, metadata: &MetadataStore) -> Result<()>;
+
+    /// The run history, oldest first.
+    fn history(&self) -> Vec<RunRecord>;
+
+    /// Stub templates of `language` that the user added (or customized),
+    /// `None` if the embedded templates should be used.
+    fn user_stub_config(&self, language: &str) -> Result<Option<StubConfig>>;
+
+    /// Languages that the user has stub templates for.
+    fn user_stub_languages(&self) -> Vec<String>;
+
+    fn append_history(&self, record: &RunRecord) -> Result<()>;
+
+    /// The clashes that were current before and the bookmarks.
+    fn navigation(&self) -> Result<Navigation>;
+
+    fn write_navigation(&self, navigation: &Navigation) -> Result<()>;
+
+    /// The last successful build with `key` (the build command and the
+    /// directory it runs in), if it is still remembered.
+    fn cached_build(&self, key: &str) -> Option<CachedBuild>;
+
+    /// Remembers the last successful build with `key` (`None` forgets it).
+    fn write_cached_build(&self, key: &str, build: Option<&CachedBuild>) -> Result<()>;
+
+    /// The practice clash in progress (if any).
+    fn practice_session(&self) -> Option<PracticeSession>;
+
+    /// Stores the practice clash in progress (`None` ends it).
+    fn write_practice_session(&self, session: Option<&PracticeSession>) -> Result<()>;
+
+    /// The hot-seat clash in progress (if any).
+    fn hotseat(&self) -> Result<Option<Hotseat>>;
+
+    fn write_hotseat(&self, hotseat: &Hotseat) -> Result<()>;
+
+    /// Results of the finished practice clashes, oldest first.
+    fn results(&self) -> Vec<GameResult>;
+
+    fn append_result(&self, result: &GameResult) -> Result<()>;
+
+    /// Directory of the saved solutions, `None` if solutions can not be
+    /// saved.
+    fn solutions_dir(&self) -> Option<&Path>;
+}
+
+/// `FileStorage` keeps the clashes in the directories of the [Libraries] and
+/// the rest in files of the data directory.
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    /// Directory of the active library, where clashes are picked from and
+    /// fetched to.
+    clash_dir: PathBuf,
+    /// Directories of all libraries, where the clashes that are not in the
+    /// active library are looked up.
+    library_dirs: Vec<PathBuf>,
+    current_clash_file: PathBuf,
+    metadata_file: PathBuf,
+    history_file: PathBuf,
+    navigation_file: PathBuf,
+    build_cache_file: PathBuf,
+    practice_session_file: PathBuf,
+    hotseat_file: PathBuf,
+    results_file: PathBuf,
+    solutions_dir: PathBuf,
+    stub_templates_dir: PathBuf,
+}
+
+impl FileStorage {
+    /// Storage with the data files in `data_dir` and `library` of
+    /// `libraries` as the active library. The stub templates of the user are
+    /// looked up in `stub_templates_dir/LANGUAGE`.
+    pub fn new(
+        data_dir: &Path,
+        stub_templates_dir: &Path,
+        libraries: &Libraries,
+        library: &str,
+    ) -> Result<Self> {
+        Ok(FileStorage {
+            clash_dir: libraries.dir(library)?.to_path_buf(),
+            library_dirs: libraries.iter().map(|(_, dir)| dir.to_path_buf()).collect(),
+            current_clash_file: data_dir.join("current"),
+            metadata_file: data_dir.join("metadata.json"),
+            history_file: data_dir.join("history.jsonl"),
+            navigation_file: data_dir.join("navigation.json"),
+            build_cache_file: data_dir.join("build_cache.json"),
+            practice_session_file: data_dir.join("practice_session.json"),
+            hotseat_file: data_dir.join("hotseat.json"),
+            results_file: data_dir.join("results.jsonl"),
+            solutions_dir: data_dir.join("solutions"),
+            stub_templates_dir: stub_templates_dir.to_path_buf(),
+        })
+    }
+
+    pub fn clash_dir(&self) -> &Path {
+        &self.clash_dir
+    }
+
+    pub fn current_clash_file(&self) -> &Path {
+        &self.current_clash_file
+    }
+
+    pub fn stub_templates_dir(&self) -> &Path {
+        &self.stub_templates_dir
+    }
+
+    /// Path of the stored clash with `handle`. Clashes are looked up in the
+    /// active library first and then in the other libraries.
+    pub fn clash_file(&self, handle: &PublicHandle) -> Option<PathBuf> {
+        std::iter::once(&self.clash_dir)
+            .chain(&self.library_dirs)
+            .map(|dir| library::clash_file(dir, handle))
+            .find(|path| path.is_file())
+    }
+
+    fn build_cache(&self) -> BTreeMap<String, CachedBuild> {
+        std::fs::read_to_string(&self.build_cache_file)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Storage for FileStorage {
+    fn current_handle(&self) -> Result<Option<PublicHandle>> {
+        let content = match std::fs::read_to_string(&self.current_clash_file) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            content => content.with_context(|| format!("Unable to read {:?}", &self.current_clash_file))?,
+        };
+        PublicHandle::from_str(&content).map(Some)
+    }
+
+    fn set_current_handle(&self, handle: &PublicHandle) -> Result<()> {
+        std::fs::write(&self.current_clash_file, handle.to_string())
+            .with_context(|| format!("Unable to write {:?}", &self.current_clash_file))
+    }
+
+    fn stored_handles(&self) -> Result<Vec<PublicHandle>> {
+        library::stored_handles(&self.clash_dir)
+    }
+
+    fn known_handles(&self) -> Vec<PublicHandle> {
+        self.library_dirs
+            .iter()
+            .flat_map(|dir| library::stored_handles(dir).unwrap_or_default())
+            .collect()
+    }
+
+    fn read_clash(&self, handle: &PublicHandle) -> Result<Option<String>> {
+        let Some(clash_file) = self.clash_file(handle) else {
+            return Ok(None)
+        };
+        let contents = std::fs::read_to_string(&clash_file)
+            .with_context(|| format!("Unable to read clash from {:?}", &clash_file))?;
+        Ok(Some(contents))
+    }
+
+    fn write_clash(&self, handle: &PublicHandle, contents: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.clash_dir)
+            .with_context(|| format!("Unable to create {:?}", &self.clash_dir))?;
+        let clash_file = library::clash_file(&self.clash_dir, handle);
+        std::fs::write(&clash_file, contents).with_context(|| format!("Unable to write {:?}", &clash_file))
+    }
+
+    fn metadata(&self) -> Result<MetadataStore> {
+        MetadataStore::read_from_file(&self.metadata_file)
+    }
+
+    fn write_metadata(&self, metadata: &MetadataStore) -> Result<()> {
+        metadata.write_to_file(&self.metadata_file)
+    }
+
+    fn history(&self) -> Vec<RunRecord> {
+        history::read_history(&self.history_file)
+    }
+
+    fn user_stub_config(&self, language: &str) -> Result<Option<StubConfig>> {
+        let lang_template_dir = self.stub_templates_dir.join(language);
+        match lang_template_dir.is_dir() {
+            true => StubConfig::read_from_dir(lang_template_dir).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    fn user_stub_languages(&self) -> Vec<String> {
+        match std::fs::read_dir(&self.stub_templates_dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok()?.file_name().into_string().ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn append_history(&self, record: &RunRecord) -> Result<()> {
+        history::append_to_history(&self.history_file, record)
+    }
+
+    fn navigation(&self) -> Result<Navigation> {
+        Navigation::read_from_file(&self.navigation_file)
+    }
+
+    fn write_navigation(&self, navigation: &Navigation) -> Result<()> {
+        navigation.write_to_file(&self.navigation_file)
+    }
+
+    fn cached_build(&self, key: &str) -> Option<CachedBuild> {
+        self.build_cache().remove(key)
+    }
+
+    fn write_cached_build(&self, key: &str, build: Option<&CachedBuild>) -> Result<()> {
+        let mut build_cache = self.build_cache();
+        match build {
+            Some(build) => build_cache.insert(key.to_string(), build.clone()),
+            None => build_cache.remove(key),
+        };
+        if let Some(parent) = self.build_cache_file.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("Unable to create {:?}", parent))?;
+        }
+        let contents =
+            serde_json::to_string_pretty(&build_cache).map_err(|err| Error::serialize("build cache", err))?;
+        std::fs::write(&self.build_cache_file, contents)
+            .with_context(|| format!("Unable to write {:?}", &self.build_cache_file))
+    }
+
+    fn practice_session(&self) -> Option<PracticeSession> {
+        let contents = std::fs::read_to_string(&self.practice_session_file).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn write_practice_session(&self, session: Option<&PracticeSession>) -> Result<()> {
+        let Some(session) = session else {
+            return match std::fs::remove_file(&self.practice_session_file) {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => {
+                    result.with_context(|| format!("Unable to remove {:?}", &self.practice_session_file))
+                }
+            }
+        };
+        let contents =
+            serde_json::to_string(session).map_err(|err| Error::serialize("practice session", err))?;
+        std::fs::write(&self.practice_session_file, contents)
+            .with_context(|| format!("Unable to write {:?}", &self.practice_session_file))
+    }
+
+    fn hotseat(&self) -> Result<Option<Hotseat>> {
+        let contents = match std::fs::read_to_string(&self.hotseat_file) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            contents => contents.with_context(|| format!("Unable to read {:?}", &self.hotseat_file))?,
+        };
+        serde_json::from_str(&contents)
+            .map(Some)
+            .with_context(|| format!("Unable to deserialize {:?}", &self.hotseat_file))
+    }
+
+    fn write_hotseat(&self, hotseat: &Hotseat) -> Result<()> {
+        let contents =
+            serde_json::to_string_pretty(hotseat).map_err(|err| Error::serialize("hot-seat clash", err))?;
+        std::fs::write(&self.hotseat_file, contents)
+            .with_context(|| format!("Unable to write {:?}", &self.hotseat_file))
+    }
+
+    fn results(&self) -> Vec<GameResult> {
+        match std::fs::read_to_string(&self.results_file) {
+            Ok(contents) => contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn append_result(&self, result: &GameResult) -> Result<()> {
+        let mut line = serde_json::to_string(result).map_err(|err| Error::serialize("result", err))?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.results_file)
+            .with_context(|| format!("Unable to open {:?}", &self.results_file))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("Unable to write {:?}", &self.results_file))
+    }
+
+    fn solutions_dir(&self) -> Option<&Path> {
+        Some(&self.solutions_dir)
+    }
+}
+
+/// `MemoryStorage` keeps everything in memory, for frontends that manage
+/// their own files (and for tests).
+#[derive(Default)]
+pub struct MemoryStorage {
+    current_handle: RefCell<Option<PublicHandle>>,
+    /// Contents of the clashes by handle.
+    clashes: RefCell<BTreeMap<String, String>>,
+    pub metadata: RefCell<MetadataStore>,
+    pub history: RefCell<Vec<RunRecord>>,
+    /// Stub templates of the user by language.
+    pub stub_configs: BTreeMap<String, StubConfig>,
+    pub navigation: RefCell<Navigation>,
+    pub build_cache: RefCell<BTreeMap<String, CachedBuild>>,
+    pub practice_session: RefCell<Option<PracticeSession>>,
+    pub hotseat: RefCell<Option<Hotseat>>,
+    pub results: RefCell<Vec<GameResult>>,
+    /// Directory of the saved solutions (solutions can only be saved if
+    /// there is one).
+    pub solutions_dir: Option<PathBuf>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn current_handle(&self) -> Result<Option<PublicHandle>> {
+        Ok(self.current_handle.borrow().clone())
+    }
+
+    fn set_current_handle(&self, handle: &PublicHandle) -> Result<()> {
+        *self.current_handle.borrow_mut() = Some(handle.clone());
+        Ok(())
+    }
+
+    fn stored_handles(&self) -> Result<Vec<PublicHandle>> {
+        self.clashes.borrow().keys().map(|handle| PublicHandle::from_str(handle)).collect()
+    }
+
+    fn known_handles(&self) -> Vec<PublicHandle> {
+        self.stored_handles().unwrap_or_default()
+    }
+
+    fn read_clash(&self, handle: &PublicHandle) -> Result<Option<String>> {
+        Ok(self.clashes.borrow().get(handle.as_str()).cloned())
+    }
+
+    fn write_clash(&self, handle: &PublicHandle, contents: &str) -> Result<()> {
+        self.clashes.borrow_mut().insert(handle.to_string(), contents.to_string());
+        Ok(())
+    }
+
+    fn metadata(&self) -> Result<MetadataStore> {
+        Ok(self.metadata.borrow().clone())
+    }
+
+    fn write_metadata(&self, metadata: &MetadataStore) -> Result<()> {
+        *self.metadata.borrow_mut() = metadata.clone();
+        Ok(())
+    }
+
+    fn history(&self) -> Vec<RunRecord> {
+        self.history.borrow().clone()
+    }
+
+    fn user_stub_config(&self, language: &str) -> Result<Option<StubConfig>> {
+        Ok(self.stub_configs.get(language).cloned())
+    }
+
+    fn user_stub_languages(&self) -> Vec<String> {
+        self.stub_configs.keys().cloned().collect()
+    }
+
+    fn append_history(&self, record: &RunRecord) -> Result<()> {
+        self.history.borrow_mut().push(record.clone());
+        Ok(())
+    }
+
+    fn navigation(&self) -> Result<Navigation> {
+        Ok(self.navigation.borrow().clone())
+    }
+
+    fn write_navigation(&self, navigation: &Navigation) -> Result<()> {
+        *self.navigation.borrow_mut() = navigation.clone();
+        Ok(())
+    }
+
+    fn cached_build(&self, key: &str) -> Option<CachedBuild> {
+        self.build_cache.borrow().get(key).cloned()
+    }
+
+    fn write_cached_build(&self, key: &str, build: Option<&CachedBuild>) -> Result<()> {
+        let mut build_cache = self.build_cache.borrow_mut();
+        match build {
+            Some(build) => build_cache.insert(key.to_string(), build.clone()),
+            None => build_cache.remove(key),
+        };
+        Ok(())
+    }
+
+    fn practice_session(&self) -> Option<PracticeSession> {
+        self.practice_session.borrow().clone()
+    }
+
+    fn write_practice_session(&self, session: Option<&PracticeSession>) -> Result<()> {
+        *self.practice_session.borrow_mut() = session.cloned();
+        Ok(())
+    }
+
+    fn hotseat(&self) -> Result<Option<Hotseat>> {
+        Ok(self.hotseat.borrow().clone())
+    }
+
+    fn write_hotseat(&self, hotseat: &Hotseat) -> Result<()> {
+        *self.hotseat.borrow_mut() = Some(hotseat.clone());
+        Ok(())
+    }
+
+    fn results(&self) -> Vec<GameResult> {
+        self.results.borrow().clone()
+    }
+
+    fn append_result(&self, result: &GameResult) -> Result<()> {
+        self.results.borrow_mut().push(result.clone());
+        Ok(())
+    }
+
+    fn solutions_dir(&self) -> Option<&Path> {
+        self.solutions_dir.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_storage() {
+        let mut libraries =
+            Libraries::read_from_file(Path::new("missing.json"), Path::new("missing")).unwrap();
+        libraries.add("fixture", ["fixtures", "library"].iter().collect()).unwrap();
+        let storage =
+            FileStorage::new(Path::new("missing"), Path::new("missing"), &libraries, library::MAIN_LIBRARY)
+                .unwrap();
+
+        // Clashes of other libraries can be read but are not picked
+        let handle = PublicHandle::from_str("682102420fbce0fce95e0ee56095ea2b9924").unwrap();
+        assert!(storage.read_clash(&handle).unwrap().is_some());
+        assert!(storage.clash_file(&handle).is_some());
+        assert_eq!(storage.known_handles(), [handle]);
+        assert!(storage.stored_handles().is_err());
+        assert_eq!(storage.current_handle().unwrap(), None);
+        assert!(storage.user_stub_languages().is_empty());
+        // Missing state files are empty states
+        assert!(storage.navigation().unwrap().recent().is_empty());
+        assert!(storage.practice_session().is_none());
+        assert!(storage.hotseat().unwrap().is_none());
+        assert!(storage.cached_build("gcc sol.c").is_none());
+    }
+}
@@ -0,0 +1,560 @@
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{Storage, Workspace, WORKSPACE_FILE_NAME};
+use crate::clash::{PublicHandle, Testcase};
+use crate::error::Context;
+use crate::history::RunRecord;
+use crate::hooks::{HookContext, HookEvent};
+use crate::solution::build::{self, CachedBuild};
+use crate::solution::{self, TestResult};
+use crate::{game, Error, Result};
+
+/// How to build and run a solution.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunProfile {
+    pub command: String,
+    pub build_command: Option<String>,
+    /// Source files of the solution. Building is skipped if they have not
+    /// changed since the last build.
+    pub sources: Vec<PathBuf>,
+    /// Directory to run the commands in (default: current directory).
+    pub dir: Option<PathBuf>,
+}
+
+impl RunProfile {
+    /// The command that runs the solution.
+    pub fn run_command(&self) -> Result<Command> {
+        let mut command = solution::parse_command(&self.command)?;
+        if let Some(dir) = &self.dir {
+            command.current_dir(dir);
+        }
+        Ok(command)
+    }
+}
+
+/// How [Workspace::run_tests] runs the tests.
+#[derive(Debug, Clone)]
+pub struct TestOptions {
+    /// Time limit of every testcase.
+    pub timeout: Duration,
+    pub build_timeout: Duration,
+    /// Build even if the sources have not changed since the last build.
+    pub rebuild: bool,
+    /// Indices of the testcases to run, starting from 1 (default: all).
+    pub testcases: Option<Vec<usize>>,
+    /// Leave out the validators.
+    pub spoiler_free: bool,
+    /// Run the validators anyway in spoiler-free mode, after the other
+    /// testcases and without showing them.
+    pub validators: bool,
+    /// Stop at the first testcase that fails (the validators are all run).
+    pub fail_fast: bool,
+    /// Run the hooks and add the run to the run history.
+    pub record: bool,
+}
+
+impl TestOptions {
+    /// Options that run all testcases with the given time limits.
+    pub fn new(timeout: Duration, build_timeout: Duration) -> Self {
+        TestOptions {
+            timeout,
+            build_timeout,
+            rebuild: false,
+            testcases: None,
+            spoiler_free: false,
+            validators: false,
+            fail_fast: false,
+            record: true,
+        }
+    }
+}
+
+/// What [Workspace::build] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStatus {
+    /// The solution has no build command.
+    NotNeeded,
+    /// The sources have not changed since the last build.
+    Skipped,
+    /// The build succeeded in the given time.
+    Built(Duration),
+}
+
+/// Progress of [Workspace::run_tests].
+#[derive(Debug)]
+pub enum TestEvent<'a> {
+    /// The solution was built (or did not need to be), before any testcase
+    /// is run. Not sent for solutions without a build command.
+    Build(BuildStatus),
+    /// A testcase was run. The input and the expected output of `hidden`
+    /// testcases (validators in spoiler-free mode) should not be shown.
+    Result {
+        testcase: &'a Testcase,
+        result: TestResult,
+        hidden: bool,
+    },
+}
+
+/// Outcome of [Workspace::run_tests].
+#[derive(Debug, Default)]
+pub struct TestSummary {
+    /// Testcases that passed, without the hidden validators.
+    pub passed: usize,
+    /// Testcases that were selected, without the hidden validators.
+    pub total: usize,
+    pub validators_passed: usize,
+    /// Hidden validators that were run.
+    pub validators: usize,
+    /// How long it took to run the testcases (without the hidden
+    /// validators).
+    pub elapsed: Duration,
+    /// Errors after the tests (of the hooks or of updating the run history)
+    /// that do not change their outcome.
+    pub warnings: Vec<Error>,
+}
+
+impl TestSummary {
+    /// Returns true if all testcases and validators that were run passed.
+    pub fn all_passed(&self) -> bool {
+        self.passed + self.validators_passed == self.total + self.validators
+    }
+}
+
+impl<S: Storage> Workspace<S> {
+    /// How to build and run the solution: with the given commands, falling
+    /// back to the ones of the workspace file. The sources default to the
+    /// solution of the workspace file.
+    pub fn run_profile(
+        &self,
+        command: Option<String>,
+        build_command: Option<String>,
+        sources: Option<Vec<PathBuf>>,
+    ) -> Result<RunProfile> {
+        if let Some(command) = command {
+            return Ok(RunProfile {
+                command,
+                build_command,
+                sources: sources.unwrap_or_default(),
+                dir: None,
+            })
+        }
+
+        let no_command = || {
+            Error::NotFound(format!(
+                "No command given and no command set in a {WORKSPACE_FILE_NAME} workspace file"
+            ))
+        };
+        let Some(workspace_file) = self.workspace_file()? else {
+            return Err(no_command())
+        };
+        let sources = sources.unwrap_or_else(|| workspace_file.solution_path().into_iter().collect());
+        let Some(command) = workspace_file.command else {
+            return Err(no_command())
+        };
+        Ok(RunProfile {
+            command,
+            build_command: build_command.or(workspace_file.build_command),
+            sources,
+            dir: Some(workspace_file.dir),
+        })
+    }
+
+    /// Runs the build command of `profile` (if any), unless none of its
+    /// sources have changed since the last successful build with the same
+    /// command in the same directory and the files that the build created are
+    /// still there.
+    pub fn build(&self, profile: &RunProfile, timeout: Duration, rebuild: bool) -> Result<BuildStatus> {
+        let Some(command) = &profile.build_command else {
+            return Ok(BuildStatus::NotNeeded)
+        };
+        let mut build_command = solution::parse_command(command)?;
+        if let Some(dir) = &profile.dir {
+            build_command.current_dir(dir);
+        }
+        self.command_output.apply(&mut build_command);
+
+        let build_dir = match &profile.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                std::env::current_dir().with_context(|| "Unable to find the current directory".to_string())?
+            }
+        };
+        // The same command builds another solution in another directory
+        let build_dir = build_dir.canonicalize().unwrap_or(build_dir);
+        let cache_key = format!("{} (in {})", command, build_dir.display());
+
+        // Without sources there is nothing to tell whether the build is up to date
+        let fingerprint = match profile.sources.as_slice() {
+            [] => None,
+            sources => Some(
+                sources
+                    .iter()
+                    .map(|source| source.canonicalize())
+                    .collect::<std::io::Result<Vec<PathBuf>>>()
+                    .and_then(|sources| build::fingerprint(command, &sources))
+                    .with_context(|| "Unable to read source files".to_string())?,
+            ),
+        };
+        if let (false, Some(fingerprint)) = (rebuild, &fingerprint) {
+            let cached_build = self.storage.cached_build(&cache_key);
+            if cached_build.is_some_and(|cached_build| cached_build.is_up_to_date(fingerprint)) {
+                return Ok(BuildStatus::Skipped)
+            }
+        }
+
+        let build_start = SystemTime::now();
+        let start_time = Instant::now();
+        let build_result = build::run_build(&mut build_command, &timeout);
+        let elapsed = start_time.elapsed();
+
+        if let Some(fingerprint) = fingerprint {
+            let cached_build = build_result
+                .is_success()
+                .then(|| CachedBuild::new(fingerprint, &build_dir, build_start));
+            // Failing to update the cache only means building again next time
+            let _ = self.storage.write_cached_build(&cache_key, cached_build.as_ref());
+        }
+        build_result.into_result(elapsed)?;
+        Ok(BuildStatus::Built(elapsed))
+    }
+
+    /// Builds the solution and runs it against the testcases of the clash
+    /// with `handle`, reporting the progress to `on_event`. Breaking from
+    /// `on_event` stops the run, which is then not recorded.
+    ///
+    /// Unless `options` say otherwise, runs of all testcases are added to the
+    /// run history (in spoiler-free mode only if the validators were run).
+    /// The pre-build hook is run before the build and stops the run if it
+    /// fails, the post-run and on-pass or on-fail hooks are run after the
+    /// tests.
+    pub fn run_tests<F>(
+        &self,
+        handle: &PublicHandle,
+        profile: &RunProfile,
+        options: &TestOptions,
+        mut on_event: F,
+    ) -> Result<TestSummary>
+    where
+        F: FnMut(TestEvent) -> ControlFlow<()>,
+    {
+        let clash = self.read_clash(handle)?;
+        let all_testcases = clash.testcases();
+        let selected: Vec<&Testcase> = match &options.testcases {
+            Some(indices) => indices
+                .iter()
+                .map(|&index| {
+                    all_testcases
+                        .get(index.wrapping_sub(1))
+                        .ok_or_else(|| Error::InvalidInput(format!("The clash has no testcase {index}")))
+                })
+                .collect::<Result<_>>()?,
+            None => all_testcases.iter().collect(),
+        };
+        let (testcases, validators): (Vec<&Testcase>, Vec<&Testcase>) = match options.spoiler_free {
+            true => selected.into_iter().partition(|testcase| !testcase.is_validator),
+            false => (selected, Vec::new()),
+        };
+        let validators = if options.validators {
+            validators
+        } else {
+            Vec::new()
+        };
+        // Nothing passing is not the same as everything passing
+        if testcases.is_empty() && validators.is_empty() {
+            return Err(Error::InvalidInput(String::from(
+                "No testcases to run (in spoiler-free mode, validators are only run with --validators)",
+            )))
+        }
+
+        let mut hook_context = HookContext::new(handle, &clash);
+        hook_context.solution = profile.sources.first().cloned();
+        if options.record {
+            self.run_hook(HookEvent::PreBuild, &hook_context)?;
+        }
+
+        let mut run_command = profile.run_command()?;
+        let mut summary = TestSummary {
+            total: testcases.len(),
+            validators: validators.len(),
+            ..Default::default()
+        };
+        match self.build(profile, options.build_timeout, options.rebuild)? {
+            BuildStatus::NotNeeded => (),
+            status => {
+                if on_event(TestEvent::Build(status)).is_break() {
+                    return Ok(summary)
+                }
+            }
+        }
+
+        let start_time = Instant::now();
+        for testcase in testcases {
+            let result = solution::run_testcase(testcase, &mut run_command, &options.timeout);
+            let passed = result.is_success();
+            if passed {
+                summary.passed += 1;
+            }
+            let event = TestEvent::Result {
+                testcase,
+                result,
+                hidden: false,
+            };
+            if on_event(event).is_break() {
+                return Ok(summary)
+            }
+            if !passed && options.fail_fast {
+                break
+            }
+        }
+        summary.elapsed = start_time.elapsed();
+
+        for validator in validators {
+            let result = solution::run_testcase(validator, &mut run_command, &options.timeout);
+            if result.is_success() {
+                summary.validators_passed += 1;
+            }
+            let event = TestEvent::Result {
+                testcase: validator,
+                result,
+                hidden: true,
+            };
+            if on_event(event).is_break() {
+                return Ok(summary)
+            }
+        }
+
+        if !options.record {
+            return Ok(summary)
+        }
+        let passed = summary.passed + summary.validators_passed;
+        let total = summary.total + summary.validators;
+        // Recording a partial run would count passing some testcases as solving the
+        // clash
+        if options.testcases.is_none() && (!options.spoiler_free || options.validators) {
+            let run_record = RunRecord {
+                handle: handle.clone(),
+                passed: passed == total,
+                timestamp: game::unix_time_now(),
+            };
+            if let Err(err) = self.storage.append_history(&run_record) {
+                summary.warnings.push(err);
+            }
+        }
+
+        hook_context.passed = Some(passed);
+        hook_context.total = Some(total);
+        let outcome_event = if passed == total {
+            HookEvent::OnPass
+        } else {
+            HookEvent::OnFail
+        };
+        for event in [HookEvent::PostRun, outcome_event] {
+            if let Err(err) = self.run_hook(event, &hook_context) {
+                summary.warnings.push(err);
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_helper::{sample_handle, workspace_with_sample_clash};
+
+    fn profile(command: &str) -> RunProfile {
+        RunProfile {
+            command: command.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn options() -> TestOptions {
+        TestOptions::new(Duration::from_secs(5), Duration::from_secs(5))
+    }
+
+    #[test]
+    fn passing_run_is_recorded() {
+        let workspace = workspace_with_sample_clash();
+        let mut num_results = 0;
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("tr X b"), &options(), |_| {
+                num_results += 1;
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert!(summary.all_passed());
+        assert_eq!(num_results, summary.total);
+        assert_eq!(summary.validators, 0);
+        let history = workspace.history();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].passed);
+    }
+
+    #[test]
+    fn selected_testcases_are_not_recorded() {
+        let workspace = workspace_with_sample_clash();
+        let options = TestOptions {
+            testcases: Some(vec![2, 1]),
+            ..options()
+        };
+        let mut indices = Vec::new();
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("cat"), &options, |event| {
+                if let TestEvent::Result { testcase, .. } = event {
+                    indices.push(testcase.index);
+                }
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(indices, [2, 1]);
+        assert_eq!((summary.passed, summary.total), (0, 2));
+        assert!(workspace.history().is_empty());
+
+        let options = TestOptions {
+            testcases: Some(vec![99]),
+            ..options
+        };
+        assert!(workspace
+            .run_tests(&sample_handle(), &profile("cat"), &options, |_| ControlFlow::Continue(()))
+            .is_err());
+    }
+
+    #[test]
+    fn spoiler_free_runs_hide_the_validators() {
+        let workspace = workspace_with_sample_clash();
+        let options = TestOptions {
+            spoiler_free: true,
+            ..options()
+        };
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("tr X b"), &options, |_| ControlFlow::Continue(()))
+            .unwrap();
+        assert_eq!(summary.validators, 0);
+        assert!(workspace.history().is_empty());
+
+        let options = TestOptions {
+            validators: true,
+            fail_fast: true,
+            ..options
+        };
+        let mut hidden = Vec::new();
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("cat"), &options, |event| {
+                if let TestEvent::Result {
+                    hidden: is_hidden, ..
+                } = event
+                {
+                    hidden.push(is_hidden);
+                }
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        // Failing fast stops after the first testcase but the validators are all run
+        assert_eq!(hidden.iter().filter(|is_hidden| !**is_hidden).count(), 1);
+        assert_eq!(hidden.iter().filter(|is_hidden| **is_hidden).count(), summary.validators);
+        assert!(summary.validators > 0);
+        assert!(!workspace.history()[0].passed);
+    }
+
+    #[test]
+    fn selecting_only_hidden_validators_is_an_error() {
+        let workspace = workspace_with_sample_clash();
+        let options = TestOptions {
+            testcases: Some(vec![2]),
+            spoiler_free: true,
+            ..options()
+        };
+        let mut num_events = 0;
+        let err = workspace
+            .run_tests(&sample_handle(), &profile("tr X b"), &options, |_| {
+                num_events += 1;
+                ControlFlow::Continue(())
+            })
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidInput(_)), "{:?}", err);
+        assert_eq!(num_events, 0);
+
+        let options = TestOptions {
+            validators: true,
+            ..options
+        };
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("tr X b"), &options, |_| ControlFlow::Continue(()))
+            .unwrap();
+        assert_eq!((summary.validators_passed, summary.validators), (1, 1));
+    }
+
+    #[test]
+    fn stopped_runs_are_not_recorded() {
+        let workspace = workspace_with_sample_clash();
+        let summary = workspace
+            .run_tests(&sample_handle(), &profile("tr X b"), &options(), |_| ControlFlow::Break(()))
+            .unwrap();
+        assert_eq!(summary.passed, 1);
+        assert!(workspace.history().is_empty());
+    }
+
+    #[test]
+    fn failed_builds_stop_the_run() {
+        let workspace = workspace_with_sample_clash();
+        let profile = RunProfile {
+            build_command: Some(String::from("false")),
+            ..profile("tr X b")
+        };
+        let err = workspace
+            .run_tests(&sample_handle(), &profile, &options(), |_| ControlFlow::Continue(()))
+            .unwrap_err();
+        assert!(matches!(err, Error::Build(build::BuildError::Failure(Some(1)))), "{:?}", err);
+        assert!(workspace.history().is_empty());
+    }
+
+    #[test]
+    fn builds_are_skipped_when_the_sources_are_unchanged() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-build-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let workspace = workspace_with_sample_clash();
+        let profile = RunProfile {
+            build_command: Some(String::from("true")),
+            sources: vec![["fixtures", "puzzles", "stub_and_solution_tester.json"].iter().collect()],
+            dir: Some(dir.clone()),
+            ..profile("tr X b")
+        };
+        let timeout = Duration::from_secs(5);
+        assert!(matches!(workspace.build(&profile, timeout, false).unwrap(), BuildStatus::Built(_)));
+        assert_eq!(workspace.build(&profile, timeout, false).unwrap(), BuildStatus::Skipped);
+        assert!(matches!(workspace.build(&profile, timeout, true).unwrap(), BuildStatus::Built(_)));
+        assert_eq!(
+            workspace.build(&RunProfile::default(), timeout, false).unwrap(),
+            BuildStatus::NotNeeded
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn builds_in_other_dirs_are_not_skipped() {
+        let dir = std::env::temp_dir().join(format!("coctus-workspace-build-dirs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("sol.c"), "int main() {}").unwrap();
+        let workspace = workspace_with_sample_clash();
+        let timeout = Duration::from_secs(5);
+        // The same command and the same source, but the builds go to different
+        // directories
+        for name in ["one", "two"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            let profile = RunProfile {
+                build_command: Some(String::from("true")),
+                sources: vec![dir.join("sol.c")],
+                dir: Some(dir.join(name)),
+                ..profile("./a.out")
+            };
+            assert!(matches!(workspace.build(&profile, timeout, false).unwrap(), BuildStatus::Built(_)));
+            assert_eq!(workspace.build(&profile, timeout, false).unwrap(), BuildStatus::Skipped);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
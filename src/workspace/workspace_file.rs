@@ -0,0 +1,115 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::clash::PublicHandle;
+use crate::error::Context;
+use crate::{Error, Result};
+
+/// Name of the file that marks a directory as a workspace.
+pub const WORKSPACE_FILE_NAME: &str = ".coctus.toml";
+
+/// State of a workspace: a directory (and its subdirectories) with its own
+/// current clash, instead of the global one in the data directory.
+///
+/// # Examples
+///
+/// ```toml
+/// handle = "682102420fbce0fce95e0ee56095ea2b9924"
+/// language = "rust"
+/// solution = "src/main.rs"
+/// build-command = "cargo build --release"
+/// command = "./target/release/sol"
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WorkspaceFile {
+    /// Directory that contains the workspace file.
+    #[serde(skip)]
+    pub dir: PathBuf,
+    /// Current clash of the workspace.
+    pub handle: Option<PublicHandle>,
+    /// Programming language of the solution.
+    pub language: Option<String>,
+    /// Source file of the solution (relative to the workspace directory).
+    pub solution: Option<PathBuf>,
+    /// Command that executes the solution (run from the workspace directory).
+    pub command: Option<String>,
+    /// Command that compiles the solution (run from the workspace directory).
+    pub build_command: Option<String>,
+}
+
+impl WorkspaceFile {
+    /// Looks for a workspace file in `start` and its ancestors.
+    pub fn discover(start: &Path) -> Result<Option<Self>> {
+        for dir in start.ancestors() {
+            let path = dir.join(WORKSPACE_FILE_NAME);
+            if path.is_file() {
+                let contents =
+                    std::fs::read_to_string(&path).with_context(|| format!("Unable to read {:?}", path))?;
+                let mut workspace: WorkspaceFile = toml::from_str(&contents)
+                    .with_context(|| format!("Invalid workspace file {:?}", path))?;
+                workspace.dir = dir.to_path_buf();
+                return Ok(Some(workspace))
+            }
+        }
+        Ok(None)
+    }
+
+    /// Creates a workspace file in `dir`. Fails if there already is one.
+    pub fn create(dir: &Path, workspace: WorkspaceFile) -> Result<Self> {
+        let workspace = WorkspaceFile {
+            dir: dir.to_path_buf(),
+            ..workspace
+        };
+        if workspace.path().exists() {
+            return Err(Error::InvalidInput(format!("{:?} already exists", workspace.path())))
+        }
+        workspace.save()?;
+        Ok(workspace)
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(WORKSPACE_FILE_NAME)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let contents = toml::to_string(self).map_err(|err| Error::serialize("workspace file", err))?;
+        std::fs::write(self.path(), contents).with_context(|| format!("Unable to write {:?}", self.path()))
+    }
+
+    /// Path of the solution source file (if any).
+    pub fn solution_path(&self) -> Option<PathBuf> {
+        self.solution.as_ref().map(|solution| self.dir.join(solution))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace_dir() -> PathBuf {
+        ["fixtures", "workspace"].iter().collect()
+    }
+
+    #[test]
+    fn discover_from_subdirectory() {
+        let workspace = WorkspaceFile::discover(&workspace_dir().join("nested")).unwrap().unwrap();
+        assert_eq!(workspace.dir, workspace_dir());
+        assert_eq!(workspace.handle.unwrap().to_string(), "90435e82d1d5e3fe5f9d3dd813770f0d5a7d2");
+        assert_eq!(workspace.language.as_deref(), Some("bash"));
+        assert_eq!(workspace.command.as_deref(), Some("bash sol.sh"));
+        assert_eq!(workspace.build_command, None);
+    }
+
+    #[test]
+    fn solution_path_is_relative_to_workspace() {
+        let workspace = WorkspaceFile::discover(&workspace_dir()).unwrap().unwrap();
+        assert_eq!(workspace.solution_path(), Some(workspace_dir().join("sol.sh")));
+    }
+
+    #[test]
+    fn create_refuses_to_overwrite() {
+        assert!(WorkspaceFile::create(&workspace_dir(), WorkspaceFile::default()).is_err());
+    }
+}